@@ -0,0 +1,347 @@
+use crate::clock::TimeControl;
+use crate::game::GameResult;
+use crate::game_tree::GameTree;
+use crate::pgn::{read_pgn, write_pgn};
+
+use chrono::{DateTime, Local};
+use std::env;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const INDEX_FILE: &str = "index.tsv";
+const INDEX_HEADER: &str = "id\tdate\twhite\tblack\tresult\ttermination\ttime_control";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+    TimeForfeit,
+    Unterminated,
+}
+
+impl Termination {
+    /// Value of the PGN `Termination` tag, which only tells normal endings from the rest.
+    pub fn pgn_value(&self) -> &str {
+        match self {
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Unterminated => "unterminated",
+            _ => "normal",
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let termination = match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::Repetition => "repetition",
+            Termination::FiftyMoves => "fifty moves",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Unterminated => "unterminated",
+        };
+        write!(f, "{}", termination)
+    }
+}
+
+impl FromStr for Termination {
+    type Err = Error;
+
+    fn from_str(termination: &str) -> Result<Termination, Error> {
+        [
+            Termination::Checkmate,
+            Termination::Stalemate,
+            Termination::Repetition,
+            Termination::FiftyMoves,
+            Termination::InsufficientMaterial,
+            Termination::TimeForfeit,
+            Termination::Unterminated,
+        ]
+        .into_iter()
+        .find(|known| known.to_string() == termination)
+        .ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unknown termination {}", termination),
+        ))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameMetadata {
+    pub white: String,
+    pub black: String,
+    /// Search depth of the bot, if one of the players is the bot.
+    pub bot_depth: Option<u8>,
+    /// `None` for a game saved before it ended.
+    pub result: Option<GameResult>,
+    pub termination: Termination,
+    pub time_control: Option<TimeControl>,
+}
+
+/// One line of the archive index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchiveEntry {
+    pub id: String,
+    pub date: String,
+    pub white: String,
+    pub black: String,
+    pub result: String,
+    pub termination: String,
+    pub time_control: String,
+}
+
+impl ArchiveEntry {
+    fn to_index_line(&self) -> String {
+        [
+            &self.id,
+            &self.date,
+            &self.white,
+            &self.black,
+            &self.result,
+            &self.termination,
+            &self.time_control,
+        ]
+        .map(|field| field.replace(['\t', '\n'], " "))
+        .join("\t")
+    }
+
+    fn from_index_line(line: &str) -> Option<ArchiveEntry> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            [id, date, white, black, result, termination, time_control] => Some(ArchiveEntry {
+                id: id.to_string(),
+                date: date.to_string(),
+                white: white.to_string(),
+                black: black.to_string(),
+                result: result.to_string(),
+                termination: termination.to_string(),
+                time_control: time_control.to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ArchiveEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} - {} {} ({})",
+            self.date, self.white, self.black, self.result, self.termination
+        )
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Directory of finished (or saved) games. Every game is stored as `<id>.pgn` with the metadata
+/// in its tags and as `<id>.json`, and gets a line in `index.tsv`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Archive {
+    pub dir: PathBuf,
+}
+
+impl Archive {
+    pub fn new(dir: impl Into<PathBuf>) -> Archive {
+        Archive { dir: dir.into() }
+    }
+
+    /// `CHESS_ARCHIVE_DIR` if it is set, `./games` otherwise.
+    pub fn from_env() -> Archive {
+        Archive::new(env::var("CHESS_ARCHIVE_DIR").unwrap_or("./games".to_owned()))
+    }
+
+    pub fn save(&self, tree: &GameTree, metadata: &GameMetadata) -> Result<ArchiveEntry, Error> {
+        self.save_at(tree, metadata, Local::now())
+    }
+
+    fn save_at(
+        &self,
+        tree: &GameTree,
+        metadata: &GameMetadata,
+        time: DateTime<Local>,
+    ) -> Result<ArchiveEntry, Error> {
+        fs::create_dir_all(&self.dir)?;
+
+        // no spaces or colons, which not every filesystem takes
+        let timestamp = time.format("%Y-%m-%d_%H-%M-%S").to_string();
+        let mut id = timestamp.clone();
+        let mut n = 2;
+        while self.dir.join(format!("{}.pgn", id)).exists() {
+            id = format!("{}_{}", timestamp, n);
+            n += 1;
+        }
+
+        let result = metadata
+            .result
+            .map(|result| result.to_string())
+            .unwrap_or("*".to_owned());
+        let time_control = metadata
+            .time_control
+            .as_ref()
+            .map(|time_control| time_control.to_string())
+            .unwrap_or("-".to_owned());
+
+        let mut tree = tree.clone();
+        tree.set_tag("Date", &time.format("%Y.%m.%d").to_string());
+        tree.set_tag("White", &metadata.white);
+        tree.set_tag("Black", &metadata.black);
+        tree.set_tag("Result", &result);
+        tree.set_tag("Termination", metadata.termination.pgn_value());
+        tree.set_tag("TimeControl", &time_control);
+        if let Some(depth) = metadata.bot_depth {
+            tree.set_tag("BotDepth", &depth.to_string());
+        }
+        let pgn = write_pgn(&tree);
+        fs::write(self.dir.join(format!("{}.pgn", id)), &pgn)?;
+
+        let json = format!(
+            "{{\n  \"id\": {},\n  \"date\": {},\n  \"white\": {},\n  \"black\": {},\n  \"bot_depth\": {},\n  \"result\": {},\n  \"termination\": {},\n  \"time_control\": {},\n  \"pgn\": {}\n}}\n",
+            json_string(&id),
+            json_string(&time.to_rfc3339()),
+            json_string(&metadata.white),
+            json_string(&metadata.black),
+            metadata
+                .bot_depth
+                .map(|depth| depth.to_string())
+                .unwrap_or("null".to_owned()),
+            json_string(&result),
+            json_string(&metadata.termination.to_string()),
+            match &metadata.time_control {
+                Some(time_control) => json_string(&time_control.to_string()),
+                None => "null".to_owned(),
+            },
+            json_string(&pgn),
+        );
+        fs::write(self.dir.join(format!("{}.json", id)), json)?;
+
+        let entry = ArchiveEntry {
+            id,
+            date: time.format("%Y-%m-%d %H:%M").to_string(),
+            white: metadata.white.clone(),
+            black: metadata.black.clone(),
+            result,
+            termination: metadata.termination.to_string(),
+            time_control,
+        };
+        let index_path = self.dir.join(INDEX_FILE);
+        let is_new_index = !index_path.exists();
+        let mut index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(index_path)?;
+        if is_new_index {
+            writeln!(index, "{}", INDEX_HEADER)?;
+        }
+        writeln!(index, "{}", entry.to_index_line())?;
+
+        Ok(entry)
+    }
+
+    /// Saved games, oldest first. An archive that doesn't exist yet is empty.
+    pub fn list(&self) -> Result<Vec<ArchiveEntry>, Error> {
+        let index_path = self.dir.join(INDEX_FILE);
+        if !index_path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(fs::read_to_string(index_path)?
+            .lines()
+            .skip(1)
+            .filter_map(ArchiveEntry::from_index_line)
+            .collect())
+    }
+
+    pub fn load(&self, id: &str) -> Result<GameTree, Error> {
+        read_pgn(&fs::read_to_string(self.dir.join(format!("{}.pgn", id)))?)
+    }
+}
+
+#[cfg(test)]
+mod test_archive {
+    use crate::archive::{Archive, GameMetadata, Termination};
+    use crate::board::Board;
+    use crate::clock::TimeControl;
+    use crate::game::GameResult;
+    use crate::game_tree::GameTree;
+    use crate::helpers::Move;
+    use crate::pieces::Color;
+    use chrono::{Local, TimeZone};
+    use std::fs;
+
+    fn temp_archive(name: &str) -> Archive {
+        let dir = std::env::temp_dir().join(format!("rust-chess-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Archive::new(dir)
+    }
+
+    #[test]
+    fn test_save_list_and_load() {
+        let archive = temp_archive("archive");
+        assert!(archive.list().unwrap().is_empty());
+
+        let mut tree = GameTree::new(Board::new());
+        let mut node = tree.root();
+        for (from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")] {
+            node = tree.add_move(node, Move::new(from.parse().unwrap(), to.parse().unwrap()));
+        }
+        let metadata = GameMetadata {
+            white: "Player".to_owned(),
+            black: "ChessBot \"deep\"".to_owned(),
+            bot_depth: Some(4),
+            result: Some(GameResult::Win(Color::Black)),
+            termination: Termination::Checkmate,
+            time_control: Some(TimeControl::fischer(
+                std::time::Duration::from_secs(180),
+                std::time::Duration::from_secs(2),
+            )),
+        };
+        let time = Local.with_ymd_and_hms(2024, 3, 9, 14, 5, 0).unwrap();
+        let first = archive.save_at(&tree, &metadata, time).unwrap();
+        let second = archive.save_at(&tree, &metadata, time).unwrap();
+        assert_eq!(first.id, "2024-03-09_14-05-00");
+        assert_eq!(second.id, "2024-03-09_14-05-00_2");
+
+        let entries = archive.list().unwrap();
+        assert_eq!(entries, vec![first.clone(), second]);
+        assert_eq!(entries[0].result, "0-1");
+        assert_eq!(entries[0].time_control, "180+2");
+        assert_eq!(
+            entries[0].termination.parse::<Termination>().unwrap(),
+            Termination::Checkmate
+        );
+
+        let loaded = archive.load(&first.id).unwrap();
+        assert_eq!(loaded.mainline().len(), 4);
+        assert_eq!(loaded.get_tag("BotDepth"), Some("4"));
+        assert_eq!(loaded.get_tag("Termination"), Some("normal"));
+        assert_eq!(loaded.get_tag("Result"), Some("0-1"));
+
+        let json = fs::read_to_string(archive.dir.join(format!("{}.json", first.id))).unwrap();
+        assert!(json.contains("\"black\": \"ChessBot \\\"deep\\\"\""));
+        assert!(json.contains("\"bot_depth\": 4"));
+        assert!(json.contains("\"termination\": \"checkmate\""));
+
+        fs::remove_dir_all(&archive.dir).unwrap();
+    }
+}
@@ -1,23 +1,37 @@
-use crate::helpers::{Move, Position};
-use crate::moves::{get_rook_old_and_new_castling_positions, is_field_in_check};
+use crate::bot::{evaluate, MATE_SCORE};
+use crate::helpers::{Move, Square};
+use crate::moves::{
+    filter_check_moves, get_attackers, get_king_moves, get_rook_old_and_new_castling_positions,
+    get_squares_between, is_field_in_check,
+};
 use crate::pieces::{Color, Piece, PieceKind};
-use crate::utils::{chess_coord_to_position, get_en_passant, was_en_passant_played};
+use crate::utils::{get_en_passant, was_en_passant_played};
 
-use chrono::Local;
 use eframe::egui::ahash::HashMapExt;
 use fnv::FnvHashMap;
-use std::fs;
+use std::cmp::max;
 use std::io::Error;
 use std::mem::swap;
 
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Piece values for exchanges in centipawns. The king is worth more than anything it could win,
+/// so it never captures into an attacked square.
+fn exchange_value(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::K => MATE_SCORE,
+        kind => Piece::new(Color::White, kind, Square::new(0)).points * 100,
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Board {
-    pub board: [[Option<Piece>; 8]; 8],
-    pub king_positions: FnvHashMap<Color, Position>,
+    pub board: [Option<Piece>; 64],
+    pub king_positions: FnvHashMap<Color, Square>,
     pub turn: Color,
     pub next_turn: Color,
-    pub en_passant: Option<Position>,
-    pub promotion_position: Option<Position>,
+    pub en_passant: Option<Square>,
+    pub promotion_position: Option<Square>,
     pub castling: FnvHashMap<Color, [bool; 2]>,
     pub n_half_moves: u16,
     pub n_full_moves: u16,
@@ -26,88 +40,24 @@ pub struct Board {
 
 impl Board {
     pub fn new() -> Board {
-        let temp_board = [
-            ['R', 'N', 'B', 'K', 'Q', 'B', 'N', 'R'],
-            ['P', 'P', 'P', 'P', 'P', 'P', 'P', 'P'],
-            [' ', ' ', ' ', ' ', ' ', ' ', ' ', ' '],
-            [' ', ' ', ' ', ' ', ' ', ' ', ' ', ' '],
-            [' ', ' ', ' ', ' ', ' ', ' ', ' ', ' '],
-            [' ', ' ', ' ', ' ', ' ', ' ', ' ', ' '],
-            ['p', 'p', 'p', 'p', 'p', 'p', 'p', 'p'],
-            ['r', 'n', 'b', 'k', 'q', 'b', 'n', 'r'],
-        ];
-
-        let mut king_positions = FnvHashMap::with_capacity_and_hasher(2, Default::default());
-        king_positions.insert(
-            Color::White,
-            chess_coord_to_position(String::from("e1")).unwrap(),
-        );
-        king_positions.insert(
-            Color::Black,
-            chess_coord_to_position(String::from("e8")).unwrap(),
-        );
-
-        let mut castling = FnvHashMap::with_capacity_and_hasher(2, Default::default());
-        castling.insert(Color::White, [true, true]);
-        castling.insert(Color::Black, [true, true]);
-
-        let mut result_board: Board = Board {
-            board: [
-                [None, None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None, None],
-            ],
-            king_positions,
-            turn: Color::White,
-            next_turn: Color::Black,
-            en_passant: None,
-            promotion_position: None,
-            castling,
-            n_half_moves: 0_u16,
-            n_full_moves: 1_u16,
-            history: vec!["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_owned()],
-        };
-
-        for (row_i, row) in temp_board.iter().enumerate() {
-            for (col_i, field) in row.iter().enumerate() {
-                if field != &' ' {
-                    let (piece_kind, color) = Piece::get_piece_kind_and_color(field);
-                    let position = Position::new(col_i, row_i);
-
-                    result_board.board[col_i][row_i] = Some(Piece::new(color, piece_kind, position))
-                }
-            }
-        }
-
-        result_board
+        Board::from_fen(STARTING_FEN).unwrap()
     }
 
     pub fn print_board(&self, turn: &Color) {
-        let mut transposed_board: Vec<Vec<Option<Piece>>> = (0..8)
-            .map(|col| (0..8).map(|row| self.board[row][col]).collect())
-            .collect();
-        let mut column_label = "    H G F E D C B A";
-
-        if turn == &Color::White {
-            transposed_board.reverse();
-            column_label = "    A B C D E F G H"
+        let mut ranks: Vec<u8> = (0..8).rev().collect();
+        let mut files: Vec<u8> = (0..8).collect();
+        let mut column_label = "    A B C D E F G H";
+
+        if turn == &Color::Black {
+            ranks.reverse();
+            files.reverse();
+            column_label = "    H G F E D C B A"
         }
 
-        for (row_i, row) in transposed_board.iter().enumerate() {
-            let mut row_label = row_i + 1;
-            let mut row_to_loop = row.clone();
-            if turn == &Color::White {
-                row_to_loop.reverse();
-                row_label = 9 - row_label;
-            }
-            print!("{}.| ", row_label);
-            for piece in row_to_loop.iter() {
-                match piece {
+        for rank in ranks {
+            print!("{}.| ", rank + 1);
+            for file in files.iter() {
+                match self.board[Square::from_file_rank(*file, rank).index()] {
                     Some(piece) => print!("{} ", piece),
                     None => print!("- "),
                 };
@@ -118,41 +68,39 @@ impl Board {
         println!("{}", column_label);
     }
 
-    pub fn move_piece(&mut self, from: &Position, to: &Position) {
-        match self.board[from.x][from.y] {
-            Some(piece) => match self.board[to.x][to.y] {
+    pub fn move_piece(&mut self, from: &Square, to: &Square) {
+        match self.board[from.index()] {
+            Some(piece) => match self.board[to.index()] {
                 Some(old_piece) => {
                     if piece.color == old_piece.color {
                         panic!("Something went wrong, trying to overwrite same color piece");
                     } else {
-                        self.board[to.x][to.y] = Some(piece);
+                        self.board[to.index()] = Some(piece);
                     }
                 }
-                None => self.board[to.x][to.y] = Some(piece),
+                None => self.board[to.index()] = Some(piece),
             },
-            None => panic!("No piece at the position {:?}", from),
+            None => panic!("No piece at the position {}", from),
         }
-        self.board[from.x][from.y] = None;
+        self.board[from.index()] = None;
     }
 
-    pub fn get_piece_from_position(&self, position: &Position) -> &Option<Piece> {
-        &self.board[position.x][position.y]
+    pub fn get_piece_from_position(&self, position: &Square) -> &Option<Piece> {
+        &self.board[position.index()]
     }
 
-    pub fn remove_piece(&mut self, position: &Position) {
-        self.board[position.x][position.y] = None
+    pub fn remove_piece(&mut self, position: &Square) {
+        self.board[position.index()] = None
     }
 
     pub fn get_pieces(&self) -> [Vec<Piece>; 2] {
         let mut white: Vec<Piece> = Vec::new();
         let mut black: Vec<Piece> = Vec::new();
-        for row in self.board {
-            for piece in row.into_iter().flatten() {
-                if piece.color == Color::White {
-                    white.push(piece);
-                } else {
-                    black.push(piece);
-                }
+        for piece in self.board.into_iter().flatten() {
+            if piece.color == Color::White {
+                white.push(piece);
+            } else {
+                black.push(piece);
             }
         }
         [white, black]
@@ -185,11 +133,277 @@ impl Board {
         all_moves
     }
 
-    pub fn get_color_positions(&self, pieces: &[Piece]) -> Vec<Position> {
+    /// Legal moves of the side to move as captures and quiets, see `split_moves`.
+    pub fn split_captures_and_quiets(&self) -> (Vec<Move>, Vec<Move>) {
+        self.split_moves(self.get_all_moves_of_color(self.turn))
+    }
+
+    /// Sorts moves into captures and quiets, expanding pawn moves to the last rank into promotions.
+    /// Capturing promotions are all captures; of the others only the queen promotion is.
+    fn split_moves(&self, moves: Vec<Move>) -> (Vec<Move>, Vec<Move>) {
+        let mut captures: Vec<Move> = Vec::new();
+        let mut quiets: Vec<Move> = Vec::new();
+        let promotion_rank = if self.turn == Color::White { 7 } else { 0 };
+
+        for le_move in moves {
+            let piece_kind = self.get_piece_from_position(&le_move.from).unwrap().kind;
+            let is_pawn = piece_kind == PieceKind::P;
+            let is_capture = self.get_piece_from_position(&le_move.to).is_some()
+                || (is_pawn && Some(le_move.to) == self.en_passant);
+
+            if is_pawn && le_move.to.rank() == promotion_rank {
+                captures.push(Move::new_promotion(le_move.from, le_move.to, PieceKind::Q));
+                for piece_kind in [PieceKind::N, PieceKind::R, PieceKind::B] {
+                    let promotion = Move::new_promotion(le_move.from, le_move.to, piece_kind);
+                    if is_capture {
+                        captures.push(promotion);
+                    } else {
+                        quiets.push(promotion);
+                    }
+                }
+            } else if is_capture {
+                captures.push(le_move);
+            } else {
+                quiets.push(le_move);
+            }
+        }
+        (captures, quiets)
+    }
+
+    /// Legal captures of the side to move, en passant, queen promotions and capturing
+    /// underpromotions included.
+    pub fn generate_captures(&self) -> Vec<Move> {
+        self.split_captures_and_quiets().0
+    }
+
+    /// Legal non-capturing moves of the side to move, with the underpromotions that don't capture.
+    pub fn generate_quiets(&self) -> Vec<Move> {
+        self.split_captures_and_quiets().1
+    }
+
+    /// All legal moves of the side to move with the promotion piece filled in, captures first.
+    pub fn generate_moves(&self) -> Vec<Move> {
+        let (mut captures, mut quiets) = self.split_captures_and_quiets();
+        captures.append(&mut quiets);
+        captures
+    }
+
+    /// Legal moves of the side to move while its king is in check: king moves, and unless it is
+    /// a double check, captures of the checking piece and blocks on the line to the king.
+    /// Falls back to `generate_moves` when not in check.
+    pub fn generate_evasions(&self) -> Vec<Move> {
+        let king_position = self.king_positions[&self.turn];
+        let checkers = get_attackers(king_position, self.next_turn, |square| {
+            *self.get_piece_from_position(&square)
+        });
+        if checkers.is_empty() {
+            return self.generate_moves();
+        }
+
+        let all_positions = self.get_all_positions();
+        let friendly_positions = &all_positions[(self.turn == Color::Black) as usize];
+        let opponent_positions = &all_positions[(self.turn != Color::Black) as usize];
+        let king = self.get_piece_from_position(&king_position).unwrap();
+
+        let king_moves = get_king_moves(&king_position, self, friendly_positions, false);
+        let mut evasions: Vec<Move> = filter_check_moves(king_position, king_moves, self, king)
+            .into_iter()
+            .map(|to| Move::new(king_position, to))
+            .collect();
+
+        if checkers.len() == 1 {
+            let checker = checkers[0];
+            let mut targets = get_squares_between(checker, king_position);
+            targets.push(checker);
+            let en_passant_capture = self.en_passant.filter(|en_passant| {
+                en_passant.file() == checker.file()
+                    && en_passant.rank().abs_diff(checker.rank()) == 1
+                    && self.get_piece_from_position(&checker).unwrap().kind == PieceKind::P
+            });
+
+            for piece in self.board.iter().flatten() {
+                if (piece.color != self.turn) | (piece.kind == PieceKind::K) {
+                    continue;
+                }
+                let blocking_moves = piece
+                    .get_pseudo_legal_moves(friendly_positions, opponent_positions, self)
+                    .into_iter()
+                    .filter(|to| {
+                        targets.contains(to)
+                            || (piece.kind == PieceKind::P && Some(*to) == en_passant_capture)
+                    })
+                    .collect();
+                evasions.extend(
+                    filter_check_moves(piece.position, blocking_moves, self, *piece)
+                        .into_iter()
+                        .map(|to| Move::new(piece.position, to)),
+                );
+            }
+        }
+
+        let (mut captures, mut quiets) = self.split_moves(evasions);
+        captures.append(&mut quiets);
+        captures
+    }
+
+    /// Whether the move checks the opponent king, directly or by discovery, without making it.
+    /// A pawn reaching the last rank without a promotion piece is taken as a queen.
+    pub fn gives_check(&self, le_move: Move) -> bool {
+        let piece = match self.get_piece_from_position(&le_move.from) {
+            Some(piece) => *piece,
+            None => return false,
+        };
+        let opponent = if piece.color == Color::White {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let opponent_king = self.king_positions[&opponent];
+
+        let mut moved_piece = piece;
+        moved_piece.position = le_move.to;
+        if (piece.kind == PieceKind::P) & ((le_move.to.rank() == 0) | (le_move.to.rank() == 7)) {
+            moved_piece.kind = le_move.promotion.unwrap_or(PieceKind::Q);
+        }
+
+        let mut vacated = vec![le_move.from];
+        let mut castled_rook: Option<(Square, Piece)> = None;
+        if (piece.kind == PieceKind::P) & (Some(le_move.to) == self.en_passant) {
+            vacated.push(Square::from_file_rank(
+                le_move.to.file(),
+                le_move.from.rank(),
+            ));
+        } else if (piece.kind == PieceKind::K)
+            & (le_move.from.file().abs_diff(le_move.to.file()) == 2)
+        {
+            let (old_rook_position, new_rook_position) =
+                get_rook_old_and_new_castling_positions(&le_move.to);
+            if let Some(rook) = self.get_piece_from_position(&old_rook_position) {
+                vacated.push(old_rook_position);
+                castled_rook = Some((new_rook_position, *rook));
+            }
+        }
+
+        let piece_at = |square: Square| -> Option<Piece> {
+            if square == le_move.to {
+                return Some(moved_piece);
+            }
+            if let Some((rook_position, rook)) = castled_rook {
+                if square == rook_position {
+                    return Some(rook);
+                }
+            }
+            if vacated.contains(&square) {
+                return None;
+            }
+            *self.get_piece_from_position(&square)
+        };
+        !get_attackers(opponent_king, piece.color, piece_at).is_empty()
+    }
+
+    /// The piece the move takes, the pawn beside the target square for en passant.
+    pub fn captured_piece(&self, le_move: Move) -> Option<Piece> {
+        if let Some(captured) = self.get_piece_from_position(&le_move.to) {
+            return Some(*captured);
+        }
+        let piece = (*self.get_piece_from_position(&le_move.from))?;
+        if piece.kind == PieceKind::P && Some(le_move.to) == self.en_passant {
+            let square = Square::from_file_rank(le_move.to.file(), le_move.from.rank());
+            return *self.get_piece_from_position(&square);
+        }
+        None
+    }
+
+    /// Material the move wins on the spot in centipawns: the captured piece and what a pawn
+    /// gains by promoting.
+    pub fn material_gain(&self, le_move: Move) -> i32 {
+        let piece = match self.get_piece_from_position(&le_move.from) {
+            Some(piece) => *piece,
+            None => return 0,
+        };
+        let mut gain = match self.get_piece_from_position(&le_move.to) {
+            Some(captured) => exchange_value(captured.kind),
+            None if (piece.kind == PieceKind::P) & (Some(le_move.to) == self.en_passant) => {
+                exchange_value(PieceKind::P)
+            }
+            None => 0,
+        };
+        if (piece.kind == PieceKind::P) & ((le_move.to.rank() == 0) | (le_move.to.rank() == 7)) {
+            gain += exchange_value(le_move.promotion.unwrap_or(PieceKind::Q))
+                - exchange_value(PieceKind::P);
+        }
+        gain
+    }
+
+    /// Static exchange evaluation: the material the side to move wins (in centipawns) when both
+    /// sides keep recapturing on the target square with their least valuable piece, each side
+    /// free to stop when going on would lose. Pieces behind the capturers join in as the line
+    /// opens up.
+    pub fn static_exchange(&self, le_move: Move) -> i32 {
+        let piece = match self.get_piece_from_position(&le_move.from) {
+            Some(piece) => *piece,
+            None => return 0,
+        };
+
+        let mut removed = vec![le_move.from];
+        if (piece.kind == PieceKind::P)
+            & (Some(le_move.to) == self.en_passant)
+            & self.get_piece_from_position(&le_move.to).is_none()
+        {
+            removed.push(Square::from_file_rank(
+                le_move.to.file(),
+                le_move.from.rank(),
+            ));
+        }
+        let mut on_target = piece.kind;
+        if (piece.kind == PieceKind::P) & ((le_move.to.rank() == 0) | (le_move.to.rank() == 7)) {
+            on_target = le_move.promotion.unwrap_or(PieceKind::Q);
+        }
+
+        let mut gains = vec![self.material_gain(le_move)];
+        let mut side = if piece.color == Color::White {
+            Color::Black
+        } else {
+            Color::White
+        };
+        loop {
+            let attackers = get_attackers(le_move.to, side, |square| {
+                if removed.contains(&square) {
+                    return None;
+                }
+                *self.get_piece_from_position(&square)
+            });
+            let least_valuable = attackers.into_iter().min_by_key(|square| {
+                exchange_value(self.get_piece_from_position(square).unwrap().kind)
+            });
+            let attacker = match least_valuable {
+                Some(attacker) => attacker,
+                None => break,
+            };
+            gains.push(exchange_value(on_target) - gains[gains.len() - 1]);
+            on_target = self.get_piece_from_position(&attacker).unwrap().kind;
+            removed.push(attacker);
+            side = if side == Color::White {
+                Color::Black
+            } else {
+                Color::White
+            };
+        }
+
+        // going backwards, every side only recaptures if that doesn't lose material
+        while gains.len() > 1 {
+            let last = gains.pop().unwrap();
+            let previous = gains.len() - 1;
+            gains[previous] = -max(-gains[previous], last);
+        }
+        gains[0]
+    }
+
+    pub fn get_color_positions(&self, pieces: &[Piece]) -> Vec<Square> {
         pieces.iter().map(|piece| piece.position).collect()
     }
 
-    pub fn get_all_positions(&self) -> [Vec<Position>; 2] {
+    pub fn get_all_positions(&self) -> [Vec<Square>; 2] {
         let pieces = self.get_pieces();
         let white_positions = self.get_color_positions(&pieces[0]);
         let black_positions = self.get_color_positions(&pieces[1]);
@@ -215,18 +429,10 @@ impl Board {
     pub fn to_fen(&self) -> String {
         let mut fen_string = "".to_owned();
 
-        let prepared_board: Vec<Vec<Option<Piece>>> = (0..8)
-            .map(|col| {
-                (0..8)
-                    .map(|row| self.board[7 - row][7 - col])
-                    .collect::<Vec<Option<Piece>>>()
-            })
-            .collect();
-
-        for (i, board_row) in prepared_board.iter().enumerate() {
+        for rank in (0..8).rev() {
             let mut blank_squares = 0;
-            for square in board_row.iter() {
-                match square {
+            for file in 0..8 {
+                match self.board[Square::from_file_rank(file, rank).index()] {
                     Some(piece) => {
                         if blank_squares > 0 {
                             fen_string.push_str(&blank_squares.to_string())
@@ -240,7 +446,7 @@ impl Board {
             if blank_squares > 0 {
                 fen_string.push_str(&blank_squares.to_string())
             }
-            if i < 7 {
+            if rank > 0 {
                 fen_string.push('/');
             }
         }
@@ -267,11 +473,14 @@ impl Board {
         if self.castling[&Color::Black][1] {
             fen_string.push('q');
         }
+        if fen_string.ends_with(' ') {
+            fen_string.push('-');
+        }
 
         fen_string.push(' ');
 
         match self.en_passant {
-            Some(position) => fen_string.push_str(&position.get_as_chess_string()),
+            Some(position) => fen_string.push_str(&position.to_string()),
             None => fen_string.push('-'),
         }
 
@@ -305,44 +514,44 @@ impl Board {
             return Err(invalid_fen_error);
         }
 
-        let mut board: [[Option<Piece>; 8]; 8] = [
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-        ];
+        let mut board: [Option<Piece>; 64] = [None; 64];
 
-        let mut king_positions: FnvHashMap<Color, Position> = FnvHashMap::new();
+        let mut king_positions: FnvHashMap<Color, Square> = FnvHashMap::new();
 
-        for (y, row) in board_pieces.iter().enumerate() {
-            let mut x: usize = 0;
-            for fen_char in row.chars().rev() {
-                if (x > 7) | (y > 7) {
+        for (rank, row) in board_pieces.iter().enumerate() {
+            let mut file: usize = 0;
+            for fen_char in row.chars() {
+                if (file > 7) | (rank > 7) {
                     return Err(invalid_fen_error);
                 }
                 let fen_char_digit = fen_char as usize;
 
                 if (48..=56).contains(&fen_char_digit) {
                     let n_empty_spaces = fen_char_digit - '0' as usize;
-                    x += n_empty_spaces
+                    file += n_empty_spaces
                 } else {
                     if !['p', 'r', 'n', 'b', 'k', 'q'].contains(&fen_char.to_ascii_lowercase()) {
                         return Err(invalid_fen_error);
                     }
                     let (piece_kind, piece_color) = Piece::get_piece_kind_and_color(&fen_char);
-                    let position = Position::new(x, y);
-                    board[x][y] = Some(Piece::new(piece_color, piece_kind, position));
+                    let position = Square::from_file_rank(file as u8, rank as u8);
+                    let mut piece = Piece::new(piece_color, piece_kind, position);
+                    // pawns off their starting rank can't do the double step anymore
+                    let starting_rank = if piece_color == Color::White { 1 } else { 6 };
+                    if (piece_kind == PieceKind::P) & (rank != starting_rank) {
+                        piece.has_moved = true;
+                    }
+                    board[position.index()] = Some(piece);
                     if piece_kind == PieceKind::K {
                         king_positions.insert(piece_color, position);
                     }
-                    x += 1;
+                    file += 1;
                 }
             }
         }
+        if king_positions.len() != 2 {
+            return Err(invalid_fen_error);
+        }
         let turn: Color;
         let next_turn: Color;
         if fen_parts[1] == "w" {
@@ -366,9 +575,9 @@ impl Board {
             let mut black = [false, false];
 
             for castling_char in castling_str.chars() {
-                let i: usize = if castling_char.to_ascii_lowercase() == 'k' {
+                let i: usize = if castling_char.eq_ignore_ascii_case(&'k') {
                     0
-                } else if castling_char.to_ascii_lowercase() == 'q' {
+                } else if castling_char.eq_ignore_ascii_case(&'q') {
                     1
                 } else {
                     return Err(invalid_fen_error);
@@ -384,8 +593,7 @@ impl Board {
             castling.insert(Color::Black, black);
         }
 
-        // TODO: castling
-        let en_passant = chess_coord_to_position(String::from(fen_parts[3]));
+        let en_passant = fen_parts[3].parse::<Square>().ok();
 
         let n_half_moves = match fen_parts[4].parse::<u16>() {
             Ok(x) => x,
@@ -421,26 +629,23 @@ impl Board {
         self.n_half_moves = 0;
     }
 
-    pub fn is_repetition(&self, n_moves: u16) -> bool {
-        let history_len = self.history.len();
-        let history_subset: Vec<String> = if history_len >= n_moves as usize {
-            self.history[history_len - ((n_moves + 1) as usize)..history_len - 1].to_vec()
-        } else {
-            self.history[..history_len - 1].to_vec()
+    /// Whether the side to move has a legal en passant capture, so that the en passant square
+    /// actually changes the position.
+    pub fn has_legal_en_passant(&self) -> bool {
+        let en_passant = match self.en_passant {
+            Some(en_passant) => en_passant,
+            None => return false,
         };
-
-        let fen_items_to_find: Vec<&str> = self.history.last().unwrap().split(' ').collect();
-
-        let mut repetition_count = 0_u8;
-        for historical_board in history_subset {
-            let historical_board_split: Vec<&str> = historical_board.split(' ').collect();
-
-            // checks if board and turn are same
-            if (historical_board_split[0] == fen_items_to_find[0])
-                & (historical_board_split[1] == fen_items_to_find[1])
+        let rank_step = if self.turn == Color::White { -1 } else { 1 };
+        for file_step in [-1, 1] {
+            if let Some(Some(pawn)) = en_passant
+                .offset(file_step, rank_step)
+                .map(|square| self.get_piece_from_position(&square))
             {
-                repetition_count += 1;
-                if repetition_count >= 2 {
+                if (pawn.kind == PieceKind::P)
+                    & (pawn.color == self.turn)
+                    & !filter_check_moves(pawn.position, vec![en_passant], self, *pawn).is_empty()
+                {
                     return true;
                 }
             }
@@ -448,22 +653,72 @@ impl Board {
         false
     }
 
+    /// The parts of the position that count for repetitions under FIDE rules: piece placement,
+    /// side to move, castling rights and the en passant square if it can be captured on.
+    pub fn repetition_key(&self) -> String {
+        let fen = self.to_fen();
+        let fen_items: Vec<&str> = fen.split(' ').collect();
+        let en_passant = if self.has_legal_en_passant() {
+            fen_items[3]
+        } else {
+            "-"
+        };
+        format!(
+            "{} {} {} {}",
+            fen_items[0], fen_items[1], fen_items[2], en_passant
+        )
+    }
+
+    fn fen_to_repetition_key(fen: &str) -> Option<String> {
+        let fen_items: Vec<&str> = fen.split(' ').collect();
+        if fen_items.len() < 4 {
+            return None;
+        }
+        if fen_items[3] == "-" {
+            return Some(fen_items[..4].join(" "));
+        }
+        Board::from_fen(fen)
+            .ok()
+            .map(|board| board.repetition_key())
+    }
+
+    /// How many times the current position has occurred, counting itself. Only positions since
+    /// the last capture or pawn move are compared, as nothing before them can repeat; lost
+    /// castling rights are told apart by the key.
+    pub fn repetition_count(&self) -> usize {
+        let current_fen = self.to_fen();
+        let current_key = self.repetition_key();
+        let window = (self.n_half_moves as usize + 1).min(self.history.len());
+        let history_subset = &self.history[self.history.len() - window..];
+
+        let mut repetition_count = history_subset
+            .iter()
+            .filter(|fen| Board::fen_to_repetition_key(fen).as_ref() == Some(&current_key))
+            .count();
+        if self.history.last() != Some(&current_fen) {
+            repetition_count += 1;
+        }
+        repetition_count
+    }
+
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+
+    /// Score in centipawns from white's point of view, `MATE_SCORE` when a side is mated.
     pub fn count_points(&self) -> i32 {
         if self.no_possible_moves() {
             if self.is_king_in_check(&self.turn) {
                 if self.turn == Color::White {
-                    return -1000;
+                    return -MATE_SCORE;
                 } else {
-                    return 1000;
+                    return MATE_SCORE;
                 }
             } else {
                 return 0;
             }
         }
-        let all_pieces = self.get_pieces();
-
-        all_pieces[0].iter().map(|piece| piece.points).sum::<i32>()
-            - all_pieces[1].iter().map(|piece| piece.points).sum::<i32>()
+        evaluate(self).total()
     }
 
     pub fn try_move(&self, move_to_try: Move) -> Board {
@@ -474,6 +729,23 @@ impl Board {
         board
     }
 
+    /// The position with the side to move passing, for the null-move search.
+    pub fn try_null_move(&self) -> Board {
+        let mut board = self.clone();
+        board.en_passant = None;
+        board.n_half_moves += 1;
+        board.set_values_at_the_end(false);
+        board
+    }
+
+    /// Whether the side has anything besides its king and pawns.
+    pub fn has_non_pawn_material(&self, color: Color) -> bool {
+        self.board
+            .iter()
+            .flatten()
+            .any(|piece| piece.color == color && !matches!(piece.kind, PieceKind::K | PieceKind::P))
+    }
+
     pub fn set_values_at_the_end(&mut self, save_history: bool) {
         if self.turn == Color::Black {
             self.increase_full_move()
@@ -484,14 +756,6 @@ impl Board {
         }
     }
 
-    pub fn write_to_file(&self) {
-        let mut filename = "./logs/".to_owned();
-        filename.push_str(&Local::now().to_string());
-        filename.push_str(".txt");
-
-        fs::write(filename, self.history.join("\n")).expect("");
-    }
-
     pub fn is_king_in_check(&self, color: &Color) -> bool {
         is_field_in_check(self.king_positions[color], self)
     }
@@ -513,6 +777,35 @@ impl Board {
         is_draw
     }
 
+    /// Whether the color could mate by any series of legal moves, which decides if running out
+    /// of time loses or draws. A lone minor piece needs help from the opponent's own men to mate,
+    /// except that bishops on one square color can never mate.
+    pub fn has_mating_material(&self, color: Color) -> bool {
+        let all_pieces = self.get_pieces();
+        let (own, other) = if color == Color::White {
+            (&all_pieces[0], &all_pieces[1])
+        } else {
+            (&all_pieces[1], &all_pieces[0])
+        };
+        let square_color = |piece: &&Piece| (piece.position.file() + piece.position.rank()) % 2;
+
+        let own_men: Vec<&Piece> = own.iter().filter(|p| p.kind != PieceKind::K).collect();
+        let other_men: Vec<&Piece> = other.iter().filter(|p| p.kind != PieceKind::K).collect();
+        let all_men: Vec<&Piece> = own_men.iter().chain(other_men.iter()).copied().collect();
+        let only_bishops_on_one_color = all_men.iter().all(|p| p.kind == PieceKind::B)
+            && all_men
+                .iter()
+                .all(|p| square_color(p) == square_color(&all_men[0]));
+
+        if own_men.is_empty() || only_bishops_on_one_color {
+            return false;
+        }
+        if (own_men.len() == 1) & [PieceKind::N, PieceKind::B].contains(&own_men[0].kind) {
+            return !other_men.is_empty();
+        }
+        true
+    }
+
     pub fn bust_a_move(&mut self, piece_move: Move) {
         let piece = self.get_piece_from_position(&piece_move.from).unwrap();
         let piece_kind = piece.kind;
@@ -520,7 +813,7 @@ impl Board {
         // can we make this bit better? use the self.chosen_piece as mutable reference
         // so we dont have to dig it out again?
         // only if I knew how...
-        let piece_to_move = match &mut self.board[piece_move.from.x][piece_move.from.y] {
+        let piece_to_move = match &mut self.board[piece_move.from.index()] {
             Some(piece_to_move) => piece_to_move,
             None => panic!("Oh no! There should be a piece at this position."),
         };
@@ -532,11 +825,11 @@ impl Board {
         // update king position
         if piece_kind == PieceKind::K {
             self.king_positions.insert(self.turn, piece_move.to);
-            if piece_move.from.x.abs_diff(piece_move.to.x) == 2 {
+            if piece_move.from.file().abs_diff(piece_move.to.file()) == 2 {
                 let (old_rook_position, new_rook_position) =
                     get_rook_old_and_new_castling_positions(&piece_move.to);
 
-                let rook_to_move = match &mut self.board[old_rook_position.x][old_rook_position.y] {
+                let rook_to_move = match &mut self.board[old_rook_position.index()] {
                     Some(rook_to_move) => rook_to_move,
                     None => panic!("Oh no! There should be a piece at this position."),
                 };
@@ -545,15 +838,18 @@ impl Board {
                 self.move_piece(&old_rook_position, &new_rook_position);
             }
             self.castling.insert(self.turn, [false, false]);
-        } else if (piece_kind == PieceKind::P) & ((piece_move.to.y == 0) | (piece_move.to.y == 7)) {
+        } else if (piece_kind == PieceKind::P)
+            & ((piece_move.to.rank() == 0) | (piece_move.to.rank() == 7))
+        {
             self.promotion_position = Some(piece_move.to);
         }
+        let home_rank = if self.turn == Color::White { 0 } else { 7 };
         let castling = self.castling[&self.turn];
         if castling.into_iter().any(|x| x) {
-            let is_rook = piece_kind == PieceKind::R;
+            let is_home_rook = (piece_kind == PieceKind::R) & (piece_move.from.rank() == home_rank);
             let new_castling = [
-                castling[0] & !((is_rook) & (piece_move.from.x == 0)),
-                castling[1] & !((is_rook) & (piece_move.from.x == 7)),
+                castling[0] & !(is_home_rook & (piece_move.from.file() == 7)),
+                castling[1] & !(is_home_rook & (piece_move.from.file() == 0)),
             ];
             if castling != new_castling {
                 self.castling.insert(self.turn, new_castling);
@@ -561,14 +857,12 @@ impl Board {
         }
         let opponent_castling = self.castling[&self.next_turn];
         if opponent_castling.into_iter().any(|x| x) {
-            let opponent_row = if self.next_turn == Color::White {
-                0_usize
-            } else {
-                7_usize
-            };
+            let opponent_rank = 7 - home_rank;
             let new_castling = [
-                opponent_castling[0] && !(piece_move.to.x == 0 && piece_move.to.y == opponent_row),
-                opponent_castling[1] && !(piece_move.to.x == 7 && piece_move.to.y == opponent_row),
+                opponent_castling[0]
+                    && !(piece_move.to.file() == 7 && piece_move.to.rank() == opponent_rank),
+                opponent_castling[1]
+                    && !(piece_move.to.file() == 0 && piece_move.to.rank() == opponent_rank),
             ];
             if opponent_castling != new_castling {
                 self.castling.insert(self.next_turn, new_castling);
@@ -576,11 +870,21 @@ impl Board {
         }
 
         if was_en_passant_played(&piece_kind, &piece_move.to, &self.en_passant) {
-            self.remove_piece(&Position::new(piece_move.to.x, piece_move.from.y));
+            self.remove_piece(&Square::from_file_rank(
+                piece_move.to.file(),
+                piece_move.from.rank(),
+            ));
         }
 
         self.en_passant = get_en_passant(&piece_kind, &piece_move.from, &piece_move.to);
         self.move_piece(&piece_move.from, &piece_move.to);
+        if let (Some(promotion_kind), Some(promotion_position)) =
+            (piece_move.promotion, self.promotion_position)
+        {
+            self.board[promotion_position.index()] =
+                Some(Piece::new(self.turn, promotion_kind, promotion_position));
+            self.promotion_position = None;
+        }
         if reset_half_moves {
             self.reset_half_move();
         } else {
@@ -592,24 +896,295 @@ impl Board {
 #[cfg(test)]
 mod test_board {
     use crate::board::Board;
-    use crate::helpers::Position;
+    use crate::helpers::{Move, Square};
     use crate::pieces::{Color, PieceKind};
 
     #[test]
     fn test_move_piece() {
         let mut board = Board::new();
 
-        board.move_piece(&Position::new(0, 1), &Position::new(0, 7));
+        let from: Square = "h2".parse().unwrap();
+        let to: Square = "h8".parse().unwrap();
+        board.move_piece(&from, &to);
 
-        let target_piece: crate::pieces::Piece = board.board[0][7].unwrap();
+        let target_piece: crate::pieces::Piece = board.board[to.index()].unwrap();
 
         assert_eq!(target_piece.kind, PieceKind::P);
         assert_eq!(target_piece.color, Color::White);
 
-        match board.board[0][1] {
-            Some(_) => assert!(false),
-            None => assert!(true),
+        assert!(board.board[from.index()].is_none());
+    }
+
+    #[test]
+    fn test_castling_moves_rook() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        board.bust_a_move(Move::new("e1".parse().unwrap(), "g1".parse().unwrap()));
+        board.set_values_at_the_end(false);
+        board.bust_a_move(Move::new("e8".parse().unwrap(), "c8".parse().unwrap()));
+        board.set_values_at_the_end(false);
+
+        assert_eq!(board.to_fen(), "2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2");
+        assert_eq!(board.king_positions[&Color::White], "g1".parse().unwrap());
+        assert_eq!(board.king_positions[&Color::Black], "c8".parse().unwrap());
+    }
+
+    #[test]
+    fn test_castling_rights_and_check() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let king_moves = |board: &Board| -> Vec<String> {
+            board
+                .get_all_moves_of_color(board.turn)
+                .into_iter()
+                .filter(|m| m.from == board.king_positions[&board.turn])
+                .map(|m| m.to.to_string())
+                .collect()
+        };
+        assert!(king_moves(&board).contains(&"g1".to_owned()));
+        assert!(king_moves(&board).contains(&"c1".to_owned()));
+
+        // rook captured on h1 removes white's short castling
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        board.bust_a_move(Move::new("h8".parse().unwrap(), "h1".parse().unwrap()));
+        assert_eq!(board.castling[&Color::White], [false, true]);
+        assert_eq!(board.castling[&Color::Black], [false, true]);
+
+        // no castling out of check or through an attacked square
+        let board = Board::from_fen("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1").unwrap();
+        assert!(!king_moves(&board).contains(&"g1".to_owned()));
+        let board = Board::from_fen("3rk3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert!(!king_moves(&board).contains(&"c1".to_owned()));
+        assert!(king_moves(&board).contains(&"g1".to_owned()));
+    }
+
+    fn perft(board: &Board, depth: u8) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+        board
+            .generate_moves()
+            .into_iter()
+            .map(|le_move| perft(&board.try_move(le_move), depth - 1))
+            .sum()
+    }
+
+    #[test]
+    fn test_perft() {
+        assert_eq!(perft(&Board::new(), 1), 20);
+        assert_eq!(perft(&Board::new(), 2), 400);
+        assert_eq!(perft(&Board::new(), 3), 8902);
+
+        let kiwipete =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        assert_eq!(perft(&kiwipete, 1), 48);
+        assert_eq!(perft(&kiwipete, 2), 2039);
+
+        let promotions = Board::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap();
+        assert_eq!(perft(&promotions, 1), 24);
+        assert_eq!(perft(&promotions, 2), 496);
+    }
+
+    #[test]
+    fn test_generate_captures_and_quiets() {
+        let board = Board::from_fen("4k3/1P6/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let captures = board.generate_captures();
+        let quiets = board.generate_quiets();
+
+        let en_passant = Move::new("e5".parse().unwrap(), "d6".parse().unwrap());
+        let queen_promotion =
+            Move::new_promotion("b7".parse().unwrap(), "b8".parse().unwrap(), PieceKind::Q);
+        assert_eq!(captures, vec![en_passant, queen_promotion]);
+
+        assert!(!quiets.contains(&en_passant));
+        assert!(quiets.contains(&Move::new("e5".parse().unwrap(), "e6".parse().unwrap())));
+        assert_eq!(quiets.iter().filter(|m| m.promotion.is_some()).count(), 3);
+        assert_eq!(
+            board.generate_moves().len(),
+            board.get_all_moves_of_color(board.turn).len() + 3
+        );
+
+        // taking on a8 with any piece is a capture, pushing to b8 only with the queen
+        let board = Board::from_fen("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let captures = board.generate_captures();
+        for kind in [PieceKind::Q, PieceKind::R, PieceKind::B, PieceKind::N] {
+            let take = Move::new_promotion("b7".parse().unwrap(), "a8".parse().unwrap(), kind);
+            assert!(captures.contains(&take));
         }
+        assert_eq!(captures.len(), 5);
+        assert_eq!(
+            board
+                .generate_quiets()
+                .iter()
+                .filter(|m| m.promotion.is_some())
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_static_exchange() {
+        let see = |fen: &str, from: &str, to: &str| -> i32 {
+            let board = Board::from_fen(fen).unwrap();
+            board.static_exchange(Move::new(from.parse().unwrap(), to.parse().unwrap()))
+        };
+
+        // free pawn, defended pawn, and a defended pawn taken by a pawn
+        assert_eq!(see("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1", "d1", "d5"), 100);
+        assert_eq!(see("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", "d1", "d5"), -800);
+        assert_eq!(see("4k3/8/4p3/3p4/4P3/8/8/4K3 w - - 0 1", "e4", "d5"), 0);
+        // the rook behind the queen joins in once the queen has captured
+        assert_eq!(
+            see("4k3/3r4/3r4/3p4/8/8/3Q4/3RK3 w - - 0 1", "d2", "d5"),
+            -800
+        );
+        assert_eq!(see("3rk3/8/8/3p4/8/3R4/3R4/4K3 w - - 0 1", "d3", "d5"), 100);
+        // the king only recaptures when nothing can take it back
+        assert_eq!(see("8/8/8/2kp4/8/8/3Q4/4K3 w - - 0 1", "d2", "d5"), -800);
+        assert_eq!(see("8/8/8/2kp4/8/8/3Q4/3RK3 w - - 0 1", "d2", "d5"), 100);
+        // quiet move onto an attacked square
+        assert_eq!(see("4k3/8/4p3/8/8/8/8/3QK3 w - - 0 1", "d1", "d5"), -900);
+        // en passant
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", "e5", "d6"), 100);
+    }
+
+    #[test]
+    fn test_gives_check() {
+        let gives_check = |fen: &str, from: &str, to: &str| -> bool {
+            let board = Board::from_fen(fen).unwrap();
+            let le_move = Move::new(from.parse().unwrap(), to.parse().unwrap());
+            assert_eq!(
+                board.gives_check(le_move),
+                board.try_move(le_move).is_king_in_check(&board.next_turn)
+            );
+            board.gives_check(le_move)
+        };
+
+        // direct and quiet
+        assert!(gives_check("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1", "a8"));
+        assert!(!gives_check("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1", "a7"));
+        assert!(!gives_check("4k3/8/8/8/8/8/5N2/4K3 w - - 0 1", "f2", "d3"));
+        assert!(gives_check("4k3/8/8/8/8/5N2/8/4K3 w - - 0 1", "f3", "d6"));
+        // discovered by a knight and by a pawn push
+        assert!(gives_check("4k3/8/8/8/4N3/8/8/4RK2 w - - 0 1", "e4", "c5"));
+        assert!(gives_check("8/8/8/k2P3R/8/8/8/4K3 w - - 0 1", "d5", "d6"));
+        // en passant removing the blocker of the bishop
+        assert!(gives_check(
+            "8/8/8/1k1pP3/8/8/8/4KB2 w - d6 0 2",
+            "e5",
+            "d6"
+        ));
+        // en passant clearing the rank for the rook
+        assert!(gives_check("8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 2", "e5", "d6"));
+        // castling rook checks
+        assert!(gives_check("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "e1", "g1"));
+        assert!(!gives_check("4k3/8/8/8/8/8/8/4K2R w K - 0 1", "e1", "g1"));
+        // pawn checks
+        assert!(gives_check("8/8/8/3k4/8/4P3/8/4K3 w - - 0 1", "e3", "e4"));
+        assert!(gives_check("8/8/8/8/8/4p3/8/3K1k2 b - - 0 1", "e3", "e2"));
+
+        let board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let (from, to) = ("a7".parse().unwrap(), "a8".parse().unwrap());
+        assert!(board.gives_check(Move::new_promotion(from, to, PieceKind::Q)));
+        assert!(!board.gives_check(Move::new_promotion(from, to, PieceKind::N)));
+    }
+
+    #[test]
+    fn test_generate_evasions() {
+        let sorted = |mut moves: Vec<Move>| {
+            moves.sort_by_key(|m| (m.from, m.to, m.promotion.map(|p| p as u8)));
+            moves
+        };
+        for fen in [
+            // single check by a rook, can be blocked or captured
+            "4k3/8/8/8/8/2n5/r7/R3K2R w KQ - 0 1",
+            // knight check, no castling out of it
+            "r3k2r/8/8/8/8/3n4/8/R3K2R w KQkq - 0 1",
+            // pawn check that can be captured en passant
+            "8/8/8/2k5/3pP3/8/8/4K3 b - e3 0 1",
+            // blocking by promotion
+            "r3k3/1P6/8/8/8/8/8/4K3 b - - 0 1",
+            "K6r/1P6/8/8/8/8/8/4k3 w - - 0 1",
+            // double check
+            "4k3/8/8/8/8/5n2/8/r3K3 w - - 0 1",
+            // not in check
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(
+                sorted(board.generate_evasions()),
+                sorted(board.generate_moves()),
+                "{}",
+                fen
+            );
+        }
+
+        let double_check = Board::from_fen("4k3/8/8/8/8/5n2/8/r3K1R1 w - - 0 1").unwrap();
+        let evasions = double_check.generate_evasions();
+        assert!(!evasions.is_empty());
+        assert!(evasions.iter().all(|m| m.from == "e1".parse().unwrap()));
+    }
+
+    fn play(board: &mut Board, moves: &[&str]) {
+        for le_move in moves {
+            let (from, to) = le_move.split_at(2);
+            board.bust_a_move(Move::new(from.parse().unwrap(), to.parse().unwrap()));
+            board.set_values_at_the_end(true);
+        }
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let knight_shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut board = Board::new();
+        play(&mut board, &knight_shuffle);
+        assert_eq!(board.repetition_count(), 2);
+        assert!(!board.is_threefold_repetition());
+        play(&mut board, &knight_shuffle);
+        assert!(board.is_threefold_repetition());
+
+        // a position reached without saving it to the history still counts itself
+        let board = board.try_move(Move::new("g1".parse().unwrap(), "f3".parse().unwrap()));
+        assert_eq!(board.repetition_count(), 3);
+    }
+
+    #[test]
+    fn test_repetition_needs_same_castling_rights() {
+        let king_walk = ["e1e2", "e8e7", "e2e1", "e7e8"];
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        play(&mut board, &king_walk);
+        play(&mut board, &king_walk);
+        // the same placement three times, but the first one still had castling rights
+        assert_eq!(board.repetition_count(), 2);
+        play(&mut board, &king_walk);
+        assert!(board.is_threefold_repetition());
+    }
+
+    #[test]
+    fn test_repetition_en_passant() {
+        let king_walk = ["e1e2", "e8e7", "e2e1", "e7e8"];
+
+        // after c5 the d5 pawn may take en passant, so that position is different
+        let mut board = Board::from_fen("4k3/2p5/8/3P4/8/8/8/4K3 b - - 0 1").unwrap();
+        play(&mut board, &["c7c5"]);
+        assert_eq!(board.repetition_key(), "4k3/8/8/2pP4/8/8/8/4K3 w - c6");
+        play(&mut board, &king_walk);
+        play(&mut board, &king_walk);
+        assert_eq!(board.repetition_count(), 2);
+
+        // without a pawn to capture, the en passant square doesn't matter
+        let mut board = Board::from_fen("4k3/2p5/8/8/3P4/8/8/4K3 b - - 0 1").unwrap();
+        play(&mut board, &["c7c5"]);
+        assert_eq!(board.repetition_key(), "4k3/8/8/2p5/3P4/8/8/4K3 w - -");
+        play(&mut board, &king_walk);
+        play(&mut board, &king_walk);
+        assert!(board.is_threefold_repetition());
+
+        // en passant capture that would expose the own king is not legal
+        let pinned = Board::from_fen("8/8/8/8/k2Pp2R/8/8/4K3 b - d3 0 1").unwrap();
+        assert!(!pinned.has_legal_en_passant());
+        assert_eq!(pinned.repetition_key(), "8/8/8/8/k2Pp2R/8/8/4K3 b - -");
+        let not_pinned = Board::from_fen("8/8/8/8/k2Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        assert!(not_pinned.has_legal_en_passant());
     }
 
     #[test]
@@ -620,6 +1195,27 @@ mod test_board {
         assert_eq!(board.unwrap(), Board::new())
     }
 
+    #[test]
+    fn test_from_fen_advanced_pawns_cannot_double_step() {
+        let board = Board::from_fen("4k3/8/4p3/8/8/3P4/4P3/4K3 w - - 0 1").unwrap();
+        let moved = |square: &str| {
+            board.board[square.parse::<Square>().unwrap().index()]
+                .unwrap()
+                .has_moved
+        };
+        assert!(!moved("e2"));
+        assert!(moved("d3"));
+        assert!(moved("e6"));
+
+        let d3: Square = "d3".parse().unwrap();
+        let pawn_moves: Vec<Move> = board
+            .generate_moves()
+            .into_iter()
+            .filter(|le_move| le_move.from == d3)
+            .collect();
+        assert_eq!(pawn_moves, vec![Move::new(d3, "d4".parse().unwrap())]);
+    }
+
     #[test]
     fn test_to_fen_new_game() {
         let new_board_fen = Board::new().to_fen();
@@ -0,0 +1,352 @@
+use crate::game_tree::GameTree;
+use crate::pgn::{read_pgn, split_pgn_games};
+use crate::pieces::Color;
+use crate::polyglot::{
+    book_move_to_string, encode_book_move, game_ply, result_weights, BookEntry, OpeningBook,
+    PolyglotKeys, DEFAULT_BOOK_DEPTH, NATIVE_BOOK_HEADER,
+};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+/// How a game ended for the side that played a move.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MoveStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BookFormat {
+    Polyglot,
+    /// Text with the wins, draws and losses of every move, see `NATIVE_BOOK_HEADER`.
+    Native,
+}
+
+impl BookFormat {
+    /// Polyglot for `.bin` files, the native text format otherwise.
+    pub fn from_path(path: impl AsRef<Path>) -> BookFormat {
+        match path.as_ref().extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("bin") => BookFormat::Polyglot,
+            _ => BookFormat::Native,
+        }
+    }
+}
+
+/// Collects the moves of the main lines of finished games, with how each game ended for the
+/// side that played the move, into an opening book.
+#[derive(Clone, Debug)]
+pub struct BookBuilder {
+    /// Moves played in fewer games are left out of the book.
+    pub min_games: u32,
+    /// Only moves played before this ply are counted.
+    pub max_ply: u16,
+    /// Only counts the games of this player, and only the moves they played.
+    pub player: Option<String>,
+    pub n_games: usize,
+    /// Games without a result, without the player, or that couldn't be read.
+    pub n_skipped: usize,
+    /// The standard Polyglot keys unless set otherwise.
+    pub keys: PolyglotKeys,
+    /// By position key and move bits, sorted the way books are.
+    stats: BTreeMap<(u64, u16), MoveStats>,
+}
+
+impl BookBuilder {
+    pub fn new() -> BookBuilder {
+        BookBuilder {
+            min_games: 1,
+            max_ply: DEFAULT_BOOK_DEPTH,
+            player: None,
+            n_games: 0,
+            n_skipped: 0,
+            keys: PolyglotKeys::builtin(),
+            stats: BTreeMap::new(),
+        }
+    }
+
+    /// The color the player had in the game, `None` if they didn't play it.
+    fn player_color(&self, tree: &GameTree, player: &str) -> Option<Color> {
+        let plays = |tag: &str| {
+            tree.get_tag(tag)
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case(player.trim()))
+        };
+        if plays("White") {
+            Some(Color::White)
+        } else if plays("Black") {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+
+    /// Counts the main line of the game, returns false if the game was skipped.
+    pub fn add_game(&mut self, tree: &GameTree) -> bool {
+        let winner = match tree.get_tag("Result") {
+            Some("1-0") => Some(Color::White),
+            Some("0-1") => Some(Color::Black),
+            Some("1/2-1/2") => None,
+            _ => {
+                self.n_skipped += 1;
+                return false;
+            }
+        };
+        let only_color = match &self.player {
+            Some(player) => match self.player_color(tree, player) {
+                Some(color) => Some(color),
+                None => {
+                    self.n_skipped += 1;
+                    return false;
+                }
+            },
+            None => None,
+        };
+
+        let mut board = &tree.node(tree.root()).board;
+        for node_id in tree.mainline() {
+            let node = tree.node(node_id);
+            if game_ply(board) >= self.max_ply {
+                break;
+            }
+            let le_move = node.le_move.unwrap();
+            if only_color.is_none_or(|color| color == board.turn) {
+                let key = (self.keys.hash(board), encode_book_move(board, le_move));
+                let stats = self.stats.entry(key).or_default();
+                match winner {
+                    None => stats.draws += 1,
+                    Some(color) if color == board.turn => stats.wins += 1,
+                    Some(_) => stats.losses += 1,
+                }
+            }
+            board = &node.board;
+        }
+        self.n_games += 1;
+        true
+    }
+
+    /// Counts every game of a PGN database. Games that can't be read are skipped, one bad game
+    /// shouldn't spoil a whole collection.
+    pub fn add_pgn(&mut self, pgn: &str) {
+        for game in split_pgn_games(pgn) {
+            match read_pgn(game) {
+                Ok(tree) => {
+                    self.add_game(&tree);
+                }
+                Err(_) => self.n_skipped += 1,
+            }
+        }
+    }
+
+    /// Position keys, move bits and results of the moves played often enough.
+    pub fn moves(&self) -> Vec<(u64, u16, MoveStats)> {
+        self.stats
+            .iter()
+            .filter(|(_, stats)| stats.games() >= self.min_games)
+            .map(|((key, le_move), stats)| (*key, *le_move, *stats))
+            .collect()
+    }
+
+    /// Number of positions with at least one move in the book.
+    pub fn n_positions(&self) -> usize {
+        let mut keys: Vec<u64> = self.moves().iter().map(|(key, _, _)| *key).collect();
+        keys.dedup();
+        keys.len()
+    }
+
+    pub fn entries(&self) -> Vec<BookEntry> {
+        let moves = self.moves();
+        let results: Vec<(u32, u32)> = moves
+            .iter()
+            .map(|(_, _, stats)| (stats.wins, stats.draws))
+            .collect();
+        moves
+            .iter()
+            .zip(result_weights(&results))
+            .map(|((key, le_move, _), weight)| BookEntry {
+                key: *key,
+                le_move: *le_move,
+                weight,
+                learn: 0,
+            })
+            .collect()
+    }
+
+    pub fn to_native(&self) -> String {
+        let mut text = format!("{}\n# key move wins draws losses\n", NATIVE_BOOK_HEADER);
+        for (key, le_move, stats) in self.moves() {
+            text += &format!(
+                "{:016x} {} {} {} {}\n",
+                key,
+                book_move_to_string(le_move),
+                stats.wins,
+                stats.draws,
+                stats.losses
+            );
+        }
+        text
+    }
+
+    pub fn to_bytes(&self, format: BookFormat) -> Vec<u8> {
+        match format {
+            BookFormat::Polyglot => OpeningBook::write_entries(&self.entries()),
+            BookFormat::Native => self.to_native().into_bytes(),
+        }
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>, format: BookFormat) -> Result<(), Error> {
+        fs::write(path, self.to_bytes(format))
+    }
+}
+
+#[cfg(test)]
+mod test_book_builder {
+    use crate::board::Board;
+    use crate::book_builder::{BookBuilder, BookFormat, MoveStats};
+    use crate::helpers::{chess_move, Move};
+    use crate::polyglot::{
+        encode_book_move, BookSelection, OpeningBook, PolyglotKeys, NATIVE_BOOK_HEADER,
+        POLYGLOT_START_POSITION_KEY,
+    };
+
+    const DATABASE: &str = r#"[White "Team"]
+[Black "Rival"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 1-0
+
+[White "Rival"]
+[Black "Team"]
+[Result "1/2-1/2"]
+
+1. e4 c5 2. Nf3 1/2-1/2
+
+[White "Team"]
+[Black "Rival"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+
+[White "Team"]
+[Black "Rival"]
+[Result "*"]
+
+1. e4 e5 *
+
+[Result "1-0"]
+
+1. e4 e5 2. Ke3 1-0
+"#;
+
+    fn stats(builder: &BookBuilder, board: &Board, le_move: Move) -> Option<MoveStats> {
+        let key = PolyglotKeys::builtin().hash(board);
+        let bits = encode_book_move(board, le_move);
+        builder
+            .moves()
+            .into_iter()
+            .find(|(k, m, _)| *k == key && *m == bits)
+            .map(|(_, _, stats)| stats)
+    }
+
+    #[test]
+    fn test_counts_results_for_the_side_to_move() {
+        let mut builder = BookBuilder::new();
+        builder.add_pgn(DATABASE);
+        // the unfinished game and the one with an illegal move are skipped
+        assert_eq!((builder.n_games, builder.n_skipped), (3, 2));
+
+        let start = Board::new();
+        let e4 = chess_move("e2", "e4");
+        let expected = MoveStats {
+            wins: 1,
+            draws: 1,
+            losses: 0,
+        };
+        assert_eq!(stats(&builder, &start, e4), Some(expected));
+        let after_d4 = start.try_move(chess_move("d2", "d4"));
+        assert_eq!(
+            stats(&builder, &after_d4, chess_move("d7", "d5"))
+                .unwrap()
+                .wins,
+            1
+        );
+        let after_e4 = start.try_move(e4);
+        assert_eq!(
+            stats(&builder, &after_e4, chess_move("e7", "e5"))
+                .unwrap()
+                .losses,
+            1
+        );
+
+        builder.min_games = 2;
+        assert_eq!(builder.moves().len(), 1);
+        assert_eq!(builder.n_positions(), 1);
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut builder = BookBuilder::new();
+        builder.max_ply = 2;
+        builder.add_pgn(DATABASE);
+        // 1. e4, 1. d4 and the three replies to them
+        assert_eq!(builder.moves().len(), 5);
+
+        let mut builder = BookBuilder::new();
+        builder.player = Some("team".to_owned());
+        builder.add_pgn(DATABASE);
+        assert_eq!((builder.n_games, builder.n_skipped), (3, 2));
+        let after_e4 = Board::new().try_move(chess_move("e2", "e4"));
+        // the moves of the rival aren't in the repertoire
+        assert_eq!(stats(&builder, &after_e4, chess_move("e7", "e5")), None);
+        assert!(stats(&builder, &after_e4, chess_move("c7", "c5")).is_some());
+    }
+
+    #[test]
+    fn test_written_books_can_be_read() {
+        let mut builder = BookBuilder::new();
+        builder.add_pgn(DATABASE);
+        let start = Board::new();
+        for format in [BookFormat::Polyglot, BookFormat::Native] {
+            let path = std::env::temp_dir().join(format!(
+                "rust-chess-built-book-{}-{:?}",
+                std::process::id(),
+                format
+            ));
+            builder.write(&path, format).unwrap();
+            let mut book = OpeningBook::open(&path, PolyglotKeys::builtin()).unwrap();
+            if format == BookFormat::Native {
+                let text = std::fs::read_to_string(&path).unwrap();
+                assert!(text.starts_with(NATIVE_BOOK_HEADER));
+                assert!(text.contains(" e2e4 1 1 0\n"));
+            }
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(book.len(), builder.moves().len());
+            book.selection = BookSelection::Best;
+            assert_eq!(book.choose(&start), Some(chess_move("e2", "e4")));
+            // d4 only lost
+            assert!(book.moves(&start).contains(&(chess_move("d2", "d4"), 0)));
+        }
+        assert_eq!(BookFormat::from_path("book.BIN"), BookFormat::Polyglot);
+    }
+
+    #[test]
+    fn test_books_use_the_standard_keys() {
+        let mut builder = BookBuilder::new();
+        builder.add_pgn(DATABASE);
+        let e4 = encode_book_move(&Board::new(), chess_move("e2", "e4"));
+        assert!(builder
+            .entries()
+            .iter()
+            .any(|entry| entry.key == POLYGLOT_START_POSITION_KEY && entry.le_move == e4));
+        assert_eq!(BookFormat::from_path("book.txt"), BookFormat::Native);
+    }
+}
@@ -1,178 +1,1337 @@
 use crate::board::Board;
-use crate::helpers::{Move, Position};
+use crate::bot::{
+    evaluate_with, is_mate_score, is_null_move, mate_distance, mated_score, null_move,
+    score_to_node, score_to_root, Bound, EvalWeights, MoveOrdering, Pruning, SearchLimits,
+    SkillLevel, TableEntry, Tablebases, Term, TimeManager, TranspositionTable, Wdl,
+    ASPIRATION_MIN_DEPTH, ASPIRATION_WINDOW, DEFAULT_HASH_SIZE_MB, MAX_ASPIRATION_WINDOW,
+    MAX_DEPTH,
+};
+#[cfg(feature = "nnue")]
+use crate::bot::{Accumulators, Network};
+use crate::clock::Clock;
+use crate::helpers::{Move, Square};
 use crate::pieces::Color;
+use crate::polyglot::OpeningBook;
+use crate::zobrist::zobrist_key;
 
-use rand::seq::SliceRandom;
-use std::cmp::{max, min};
-use std::time::Instant;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::cmp::{max, min, Reverse};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-struct AlphaBetaResult {
-    le_move: Move,
-    score: i32,
+const DELTA_MARGIN: i32 = 200;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchLine {
+    pub score: i32,
+    pub pv: Vec<Move>,
+}
+
+pub struct AlphaBetaResult {
+    pub le_move: Move,
+    pub score: i32,
+    pub depth: u8,
+    pub pv: Vec<Move>,
+    pub lines: Vec<SearchLine>,
+    pub from_book: bool,
+    pub chosen_line: usize,
 }
 
 impl AlphaBetaResult {
-    fn new(le_move: Move, score: i32) -> AlphaBetaResult {
-        AlphaBetaResult { le_move, score }
+    fn from_lines(lines: Vec<SearchLine>) -> AlphaBetaResult {
+        match lines.first() {
+            Some(best) => AlphaBetaResult {
+                le_move: best.pv[0],
+                score: best.score,
+                depth: 0,
+                pv: best.pv.clone(),
+                lines,
+                from_book: false,
+                chosen_line: 0,
+            },
+            None => AlphaBetaResult::default(),
+        }
     }
 
     fn default() -> AlphaBetaResult {
         AlphaBetaResult {
-            le_move: Move::new(Position::new(10, 10), Position::new(10, 10)),
-            score: std::i32::MAX,
+            le_move: Move::new(Square::new(0), Square::new(0)),
+            score: i32::MAX,
+            depth: 0,
+            pv: Vec::new(),
+            lines: Vec::new(),
+            from_book: false,
+            chosen_line: 0,
         }
     }
 }
 
+pub fn format_score(score: i32) -> String {
+    if let Some(plies) = mate_distance(score) {
+        let sign = if plies > 0 { '+' } else { '-' };
+        return format!("{}mate in {}", sign, (plies.abs() + 1) / 2);
+    }
+    format!("{:+.2}", score as f32 / 100.)
+}
+
 pub struct ChessBot {
     pub color: Color,
     pub n_calculations: usize,
     pub max_depth: u8,
+    pub move_time: Option<Duration>,
+    pub transposition_table: Arc<TranspositionTable>,
+    pub move_ordering: MoveOrdering,
+    pub quiescence_checks: bool,
+    pub book: Option<OpeningBook>,
+    pub tablebases: Option<Arc<Tablebases>>,
+    pub threads: usize,
+    pub multi_pv: usize,
+    pub pruning: Pruning,
+    pub skill: SkillLevel,
+    pub weights: EvalWeights,
+    pub contempt: i32,
+    #[cfg(feature = "nnue")]
+    pub network: Option<Arc<Network>>,
+    #[cfg(feature = "nnue")]
+    accumulators: Accumulators,
+    time_manager: Option<TimeManager>,
+    node_limit: Option<usize>,
+    stopped: bool,
+    stop_signal: Arc<AtomicBool>,
+    pv_table: Vec<Vec<Move>>,
+    thread_pool: Option<Arc<ThreadPool>>,
 }
 
 impl ChessBot {
     pub fn new(color: Color, max_depth: u8) -> ChessBot {
+        ChessBot::with_table(
+            color,
+            max_depth,
+            Arc::new(TranspositionTable::new(DEFAULT_HASH_SIZE_MB)),
+        )
+    }
+
+    pub fn with_table(
+        color: Color,
+        max_depth: u8,
+        transposition_table: Arc<TranspositionTable>,
+    ) -> ChessBot {
         ChessBot {
             color,
             n_calculations: 0,
             max_depth,
+            move_time: None,
+            transposition_table,
+            move_ordering: MoveOrdering::new(),
+            quiescence_checks: true,
+            book: None,
+            tablebases: None,
+            threads: 1,
+            multi_pv: 1,
+            pruning: Pruning::default(),
+            skill: SkillLevel::default(),
+            weights: EvalWeights::default(),
+            contempt: 0,
+            #[cfg(feature = "nnue")]
+            network: None,
+            #[cfg(feature = "nnue")]
+            accumulators: Accumulators::default(),
+            node_limit: None,
+            time_manager: None,
+            stopped: false,
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            pv_table: Vec::new(),
+            thread_pool: None,
+        }
+    }
+
+    pub fn with_skill(color: Color, skill: SkillLevel, move_time: Duration) -> ChessBot {
+        ChessBot {
+            skill,
+            ..ChessBot::with_move_time(color, move_time)
+        }
+    }
+
+    pub fn with_move_time(color: Color, move_time: Duration) -> ChessBot {
+        ChessBot {
+            move_time: Some(move_time),
+            ..ChessBot::new(color, MAX_DEPTH)
+        }
+    }
+
+    fn is_out_of_time(&mut self) -> bool {
+        if !self.stopped && self.stop_signal.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        if !self.stopped
+            && self
+                .time_manager
+                .is_some_and(|time_manager| time_manager.is_hard_limit_reached())
+        {
+            self.stopped = true;
+        }
+        if !self.stopped
+            && self
+                .node_limit
+                .is_some_and(|nodes| self.n_calculations >= nodes)
+        {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    fn evaluate_board(&self, board: &Board, ply: usize) -> i32 {
+        if let Some(score) = self.network_eval(board, ply) {
+            return score;
+        }
+        let evaluation = evaluate_with(board, &self.weights);
+        let total = self.weights.total(&evaluation, self.color);
+        let weight = self.skill.positional_weight();
+        if weight >= 100 {
+            return total;
+        }
+        let material = evaluation.term(Term::Material);
+        material + (total - material) * weight / 100
+    }
+
+    fn make_move(&mut self, board: &Board, le_move: Move, ply: usize) -> Board {
+        let child = board.try_move(le_move);
+        self.update_accumulators(board, Some(le_move), ply);
+        child
+    }
+
+    fn make_null_move(&mut self, board: &Board, ply: usize) -> Board {
+        let child = board.try_null_move();
+        self.update_accumulators(board, None, ply);
+        child
+    }
+
+    #[cfg(feature = "nnue")]
+    fn refresh_accumulators(&mut self, board: &Board) {
+        if let Some(network) = &self.network {
+            self.accumulators.refresh(network, board);
+        }
+    }
+
+    #[cfg(feature = "nnue")]
+    fn update_accumulators(&mut self, board: &Board, le_move: Option<Move>, ply: usize) {
+        if let Some(network) = &self.network {
+            match le_move {
+                Some(le_move) => {
+                    let captured = board.captured_piece(le_move);
+                    self.accumulators
+                        .update(network, ply, board, le_move, captured);
+                }
+                None => self.accumulators.update_null(network, ply),
+            }
+        }
+    }
+
+    #[cfg(feature = "nnue")]
+    fn network_eval(&self, board: &Board, ply: usize) -> Option<i32> {
+        let network = self.network.as_ref()?;
+        Some(self.accumulators.evaluate(network, ply, board.turn))
+    }
+
+    #[cfg(not(feature = "nnue"))]
+    fn refresh_accumulators(&mut self, _board: &Board) {}
+
+    #[cfg(not(feature = "nnue"))]
+    fn update_accumulators(&mut self, _board: &Board, _le_move: Option<Move>, _ply: usize) {}
+
+    #[cfg(not(feature = "nnue"))]
+    fn network_eval(&self, _board: &Board, _ply: usize) -> Option<i32> {
+        None
+    }
+
+    /// A draw from white's point of view, worse than equal for the bot by its contempt.
+    fn draw_score(&self) -> i32 {
+        if self.color == Color::White {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    fn clear_pv(&mut self, ply: usize) {
+        if self.pv_table.len() <= ply + 1 {
+            self.pv_table.resize(ply + 2, Vec::new());
+        }
+        self.pv_table[ply].clear();
+    }
+
+    fn update_pv(&mut self, ply: usize, le_move: Move) {
+        let (line, rest) = self.pv_table.split_at_mut(ply + 1);
+        line[ply].clear();
+        line[ply].push(le_move);
+        line[ply].extend_from_slice(&rest[0]);
+    }
+
+    fn probe_tablebases(&mut self, board: &Board, ply: usize) -> Option<i32> {
+        let result = self.tablebases.as_ref()?.probe(board)?;
+        self.n_calculations += 1;
+        if result.wdl == Wdl::Draw {
+            return Some(self.draw_score());
+        }
+        Some(score_to_root(result.score(board.turn), ply))
+    }
+
+    fn get_moves_to_try(&self, board: &Board, in_check: bool) -> Vec<Move> {
+        if in_check {
+            return board.generate_evasions();
+        }
+        board.generate_moves()
+    }
+
+    fn get_quiescence_moves(&self, board: &Board, checks: bool) -> Vec<(i32, Move)> {
+        let (captures, quiets) = board.split_captures_and_quiets();
+        let mut moves: Vec<(i32, Move)> = captures
+            .into_iter()
+            .filter_map(|le_move| {
+                let exchange = board.static_exchange(le_move);
+                (exchange >= 0).then_some((exchange, le_move))
+            })
+            .collect();
+        moves.sort_by_key(|(exchange, _)| -exchange);
+        let mut moves: Vec<(i32, Move)> = moves
+            .into_iter()
+            .map(|(_, le_move)| (board.material_gain(le_move), le_move))
+            .collect();
+        if checks {
+            moves.extend(
+                quiets
+                    .into_iter()
+                    .filter(|le_move| {
+                        le_move.promotion.is_none()
+                            && board.gives_check(*le_move)
+                            && board.static_exchange(*le_move) >= 0
+                    })
+                    .map(|le_move| (0, le_move)),
+            );
+        }
+        moves
+    }
+
+    /// Table lookup for a node. Gives the score when the entry ends the search here, and the
+    /// stored best move to search first otherwise. Stored mates count from the position, see
+    /// `score_to_node`.
+    fn probe_table(
+        &mut self,
+        key: u64,
+        depth_left: u8,
+        ply: usize,
+        alpha: i32,
+        beta: i32,
+    ) -> Result<Option<Move>, i32> {
+        let entry = self.transposition_table.probe(key).map(|entry| TableEntry {
+            score: score_to_root(entry.score, ply),
+            ..entry
+        });
+        if let Some(score) = entry.and_then(|entry| entry.cutoff(depth_left, alpha, beta)) {
+            return Err(score);
+        }
+        Ok(entry.and_then(|entry| entry.best_move))
+    }
+
+    fn store_in_table(
+        &mut self,
+        key: u64,
+        depth_left: u8,
+        ply: usize,
+        score: i32,
+        (alpha, beta): (i32, i32),
+        best_move: Option<Move>,
+    ) {
+        // scores of an interrupted search are made up
+        if self.stopped {
+            return;
+        }
+        let bound = if score >= beta {
+            Bound::Lower
+        } else if score <= alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        self.transposition_table.store(
+            key,
+            depth_left,
+            score_to_node(score, ply),
+            bound,
+            best_move,
+        );
+    }
+
+    fn quiescence_max(
+        &mut self,
+        board: Board,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        checks: bool,
+    ) -> i32 {
+        if self.is_out_of_time() {
+            return alpha;
+        }
+        self.n_calculations += 1;
+        let in_check = board.is_king_in_check(&board.turn);
+        let mut stand_pat = i32::MIN;
+        let moves = if in_check {
+            let evasions = board.generate_evasions();
+            if evasions.is_empty() {
+                return mated_score(Color::White, ply);
+            }
+            evasions.into_iter().map(|le_move| (0, le_move)).collect()
+        } else {
+            stand_pat = self.evaluate_board(&board, ply);
+            if stand_pat >= beta {
+                return beta;
+            }
+            alpha = max(alpha, stand_pat);
+            self.get_quiescence_moves(&board, checks)
+        };
+
+        for (gain, move_to_try) in moves {
+            // delta pruning: even winning the piece doesn't get the score up to alpha
+            if !in_check && stand_pat.saturating_add(gain + DELTA_MARGIN) <= alpha {
+                continue;
+            }
+            let child = self.make_move(&board, move_to_try, ply);
+            let score = self.quiescence_min(child, ply + 1, alpha, beta, false);
+            if score >= beta {
+                return beta;
+            }
+            alpha = max(alpha, score);
+        }
+        alpha
+    }
+
+    fn quiescence_min(
+        &mut self,
+        board: Board,
+        ply: usize,
+        alpha: i32,
+        mut beta: i32,
+        checks: bool,
+    ) -> i32 {
+        if self.is_out_of_time() {
+            return beta;
+        }
+        self.n_calculations += 1;
+        let in_check = board.is_king_in_check(&board.turn);
+        let mut stand_pat = i32::MAX;
+        let moves = if in_check {
+            let evasions = board.generate_evasions();
+            if evasions.is_empty() {
+                return mated_score(Color::Black, ply);
+            }
+            evasions.into_iter().map(|le_move| (0, le_move)).collect()
+        } else {
+            stand_pat = self.evaluate_board(&board, ply);
+            if stand_pat <= alpha {
+                return alpha;
+            }
+            beta = min(beta, stand_pat);
+            self.get_quiescence_moves(&board, checks)
+        };
+
+        for (gain, move_to_try) in moves {
+            if !in_check && stand_pat.saturating_sub(gain + DELTA_MARGIN) >= beta {
+                continue;
+            }
+            let child = self.make_move(&board, move_to_try, ply);
+            let score = self.quiescence_max(child, ply + 1, alpha, beta, false);
+            if score <= alpha {
+                return alpha;
+            }
+            beta = min(beta, score);
         }
+        beta
+    }
+
+    fn is_quiet(board: &Board, le_move: Move) -> bool {
+        board.material_gain(le_move) == 0 && !board.gives_check(le_move)
+    }
+
+    fn static_eval(
+        &self,
+        board: &Board,
+        ply: usize,
+        depth_left: u8,
+        in_check: bool,
+    ) -> Option<i32> {
+        (!in_check && self.pruning.uses_static_eval(depth_left))
+            .then(|| self.evaluate_board(board, ply))
     }
 
-    fn alpha_beta_max(&mut self, board: Board, depth_left: u8, mut alpha: i32, beta: i32) -> i32 {
+    fn can_try_null_move(board: &Board, previous_move: Move) -> bool {
+        !is_null_move(previous_move) && board.has_non_pawn_material(board.turn)
+    }
+
+    fn alpha_beta_max(
+        &mut self,
+        board: Board,
+        depth_left: u8,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        previous_move: Move,
+    ) -> i32 {
+        self.clear_pv(ply);
+        if self.is_out_of_time() {
+            return alpha;
+        }
+        if let Some(score) = self.probe_tablebases(&board, ply) {
+            return score.clamp(alpha, beta);
+        }
         if depth_left == 0 {
-            self.n_calculations += 1;
-            return board.count_points();
+            return self.quiescence_max(board, ply, alpha, beta, self.quiescence_checks);
         }
-        let all_moves = board.get_all_moves_of_color(board.turn);
+        let key = zobrist_key(&board);
+        let hash_move = match self.probe_table(key, depth_left, ply, alpha, beta) {
+            Ok(hash_move) => hash_move,
+            Err(score) => return score,
+        };
+        let in_check = board.is_king_in_check(&board.turn);
+        let static_eval = self.static_eval(&board, ply, depth_left, in_check);
+        if beta != i32::MAX && !is_mate_score(beta) {
+            if let (Some(eval), Some(margin)) = (
+                static_eval,
+                self.pruning.reverse_futility_margin(depth_left),
+            ) {
+                if eval - margin >= beta {
+                    return beta;
+                }
+            }
+            if let Some(null_depth) = self.pruning.null_move_depth(depth_left) {
+                if static_eval.is_some_and(|eval| eval >= beta)
+                    && Self::can_try_null_move(&board, previous_move)
+                {
+                    let child = self.make_null_move(&board, ply);
+                    let score = self.alpha_beta_min(
+                        child,
+                        null_depth,
+                        ply + 1,
+                        beta - 1,
+                        beta,
+                        null_move(),
+                    );
+                    if score >= beta {
+                        return beta;
+                    }
+                }
+            }
+        }
+        let previous_move = (!is_null_move(previous_move)).then_some(previous_move);
+        let all_moves = self.move_ordering.order(
+            &board,
+            self.get_moves_to_try(&board, in_check),
+            hash_move,
+            ply,
+            previous_move,
+        );
         if all_moves.is_empty() {
             self.n_calculations += 1;
-            return board.count_points();
+            return if in_check {
+                mated_score(board.turn, ply)
+            } else {
+                self.draw_score()
+            };
         }
-        let mut score = std::i32::MIN;
+        let futile = !is_mate_score(alpha)
+            && static_eval
+                .zip(self.pruning.futility_margin(depth_left))
+                .is_some_and(|(eval, margin)| eval + margin <= alpha);
+        let window = (alpha, beta);
+        let mut score = i32::MIN;
+        let mut best_move = None;
 
-        for move_to_try in all_moves {
-            if depth_left == 1
-                && board.turn == self.color
-                && board.get_piece_from_position(&move_to_try.to).is_some()
-            {
+        for (index, move_to_try) in all_moves.into_iter().enumerate() {
+            let quiet = !in_check && index > 0 && Self::is_quiet(&board, move_to_try);
+            if quiet && futile {
                 continue;
             }
-            score = max(
-                score,
-                self.alpha_beta_min(board.try_move(move_to_try), depth_left - 1, alpha, beta),
+            let reduction = if quiet {
+                self.pruning.reduction(depth_left, index)
+            } else {
+                0
+            };
+            let child = self.make_move(&board, move_to_try, ply);
+            let mut child_score = self.alpha_beta_min(
+                child.clone(),
+                depth_left - 1 - reduction,
+                ply + 1,
+                alpha,
+                beta,
+                move_to_try,
             );
+            if reduction > 0 && child_score > alpha {
+                child_score =
+                    self.alpha_beta_min(child, depth_left - 1, ply + 1, alpha, beta, move_to_try);
+            }
+            score = max(score, child_score);
             if score >= beta {
+                self.move_ordering.record_cutoff(
+                    &board,
+                    move_to_try,
+                    ply,
+                    depth_left,
+                    previous_move,
+                );
+                self.store_in_table(key, depth_left, ply, beta, window, Some(move_to_try));
                 return beta;
             }
-            alpha = max(alpha, score);
+            if score > alpha {
+                alpha = score;
+                best_move = Some(move_to_try);
+                self.update_pv(ply, move_to_try);
+            }
         }
+        self.store_in_table(key, depth_left, ply, alpha, window, best_move);
         alpha
     }
 
-    fn alpha_beta_min(&mut self, board: Board, depth_left: u8, alpha: i32, mut beta: i32) -> i32 {
+    fn alpha_beta_min(
+        &mut self,
+        board: Board,
+        depth_left: u8,
+        ply: usize,
+        alpha: i32,
+        mut beta: i32,
+        previous_move: Move,
+    ) -> i32 {
+        self.clear_pv(ply);
+        if self.is_out_of_time() {
+            return beta;
+        }
+        if let Some(score) = self.probe_tablebases(&board, ply) {
+            return score.clamp(alpha, beta);
+        }
         if depth_left == 0 {
-            self.n_calculations += 1;
-            return board.count_points();
+            return self.quiescence_min(board, ply, alpha, beta, self.quiescence_checks);
+        }
+        let key = zobrist_key(&board);
+        let hash_move = match self.probe_table(key, depth_left, ply, alpha, beta) {
+            Ok(hash_move) => hash_move,
+            Err(score) => return score,
+        };
+        let in_check = board.is_king_in_check(&board.turn);
+        let static_eval = self.static_eval(&board, ply, depth_left, in_check);
+        if alpha != i32::MIN && !is_mate_score(alpha) {
+            if let (Some(eval), Some(margin)) = (
+                static_eval,
+                self.pruning.reverse_futility_margin(depth_left),
+            ) {
+                if eval + margin <= alpha {
+                    return alpha;
+                }
+            }
+            if let Some(null_depth) = self.pruning.null_move_depth(depth_left) {
+                if static_eval.is_some_and(|eval| eval <= alpha)
+                    && Self::can_try_null_move(&board, previous_move)
+                {
+                    let child = self.make_null_move(&board, ply);
+                    let score = self.alpha_beta_max(
+                        child,
+                        null_depth,
+                        ply + 1,
+                        alpha,
+                        alpha + 1,
+                        null_move(),
+                    );
+                    if score <= alpha {
+                        return alpha;
+                    }
+                }
+            }
         }
-        let all_moves = board.get_all_moves_of_color(board.turn);
+        let previous_move = (!is_null_move(previous_move)).then_some(previous_move);
+        let all_moves = self.move_ordering.order(
+            &board,
+            self.get_moves_to_try(&board, in_check),
+            hash_move,
+            ply,
+            previous_move,
+        );
         if all_moves.is_empty() {
             self.n_calculations += 1;
-            return board.count_points();
+            return if in_check {
+                mated_score(board.turn, ply)
+            } else {
+                self.draw_score()
+            };
         }
+        let futile = !is_mate_score(beta)
+            && static_eval
+                .zip(self.pruning.futility_margin(depth_left))
+                .is_some_and(|(eval, margin)| eval - margin >= beta);
+        let window = (alpha, beta);
+        let mut score = i32::MAX;
+        let mut best_move = None;
 
-        let mut score = std::i32::MAX;
-
-        for move_to_try in all_moves {
-            if depth_left == 1
-                && board.turn == self.color
-                && board.get_piece_from_position(&move_to_try.to).is_some()
-            {
+        for (index, move_to_try) in all_moves.into_iter().enumerate() {
+            let quiet = !in_check && index > 0 && Self::is_quiet(&board, move_to_try);
+            if quiet && futile {
                 continue;
             }
-            score = min(
-                score,
-                self.alpha_beta_max(board.try_move(move_to_try), depth_left - 1, alpha, beta),
+            let reduction = if quiet {
+                self.pruning.reduction(depth_left, index)
+            } else {
+                0
+            };
+            let child = self.make_move(&board, move_to_try, ply);
+            let mut child_score = self.alpha_beta_max(
+                child.clone(),
+                depth_left - 1 - reduction,
+                ply + 1,
+                alpha,
+                beta,
+                move_to_try,
             );
+            if reduction > 0 && child_score < beta {
+                child_score =
+                    self.alpha_beta_max(child, depth_left - 1, ply + 1, alpha, beta, move_to_try);
+            }
+            score = min(score, child_score);
             if score <= alpha {
+                self.move_ordering.record_cutoff(
+                    &board,
+                    move_to_try,
+                    ply,
+                    depth_left,
+                    previous_move,
+                );
+                self.store_in_table(key, depth_left, ply, alpha, window, Some(move_to_try));
                 return alpha;
             }
-            beta = min(beta, score);
+            if score < beta {
+                beta = score;
+                best_move = Some(move_to_try);
+                self.update_pv(ply, move_to_try);
+            }
         }
+        self.store_in_table(key, depth_left, ply, beta, window, best_move);
         beta
     }
 
-    fn alpha_beta_outer(&mut self, board: Board) -> AlphaBetaResult {
-        let mut rng = rand::thread_rng();
-        let mut score: i32;
-        let mut alpha = std::i32::MIN;
-        let mut beta = std::i32::MAX;
-        let mut best_move = AlphaBetaResult::default();
-        let mut all_moves = board.get_all_moves_of_color(board.turn);
-        all_moves.shuffle(&mut rng);
-
-        if board.turn == Color::White {
-            score = std::i32::MIN;
-            for move_to_try in all_moves {
-                score = max(
-                    score,
-                    self.alpha_beta_min(
-                        board.try_move(move_to_try),
-                        self.max_depth - 1,
-                        alpha,
-                        beta,
-                    ),
-                );
-                if score >= beta {
-                    break;
+    fn alpha_beta_outer(
+        &mut self,
+        board: Board,
+        depth: u8,
+        previous_best: Option<Move>,
+        (lower, upper): (i32, i32),
+    ) -> AlphaBetaResult {
+        let n_lines = self.multi_pv.max(1);
+        let is_white = board.turn == Color::White;
+        let mut lines: Vec<SearchLine> = Vec::new();
+        let all_moves =
+            self.move_ordering
+                .order(&board, board.generate_moves(), previous_best, 0, None);
+
+        for move_to_try in all_moves {
+            let bound = (lines.len() == n_lines).then(|| lines[n_lines - 1].score);
+            let child = self.make_move(&board, move_to_try, 0);
+            let score = if is_white {
+                self.alpha_beta_min(
+                    child,
+                    depth - 1,
+                    1,
+                    bound.map_or(lower, |bound| bound.max(lower)),
+                    upper,
+                    move_to_try,
+                )
+            } else {
+                self.alpha_beta_max(
+                    child,
+                    depth - 1,
+                    1,
+                    lower,
+                    bound.map_or(upper, |bound| bound.min(upper)),
+                    move_to_try,
+                )
+            };
+            let is_better = bound.is_none_or(|bound| {
+                if is_white {
+                    score > bound
+                } else {
+                    score < bound
                 }
-                if score > alpha {
-                    alpha = score;
-                    best_move = AlphaBetaResult::new(move_to_try, score);
+            });
+            if is_better {
+                let mut pv = vec![move_to_try];
+                pv.extend_from_slice(&self.pv_table[1]);
+                lines.push(SearchLine { score, pv });
+                if is_white {
+                    lines.sort_by_key(|line| Reverse(line.score));
+                } else {
+                    lines.sort_by_key(|line| line.score);
                 }
+                lines.truncate(n_lines);
             }
-        } else {
-            score = std::i32::MAX;
-            for move_to_try in all_moves {
-                score = min(
-                    score,
-                    self.alpha_beta_max(
-                        board.try_move(move_to_try),
-                        self.max_depth - 1,
-                        alpha,
-                        beta,
-                    ),
-                );
-                if score <= alpha {
-                    break;
+        }
+
+        AlphaBetaResult::from_lines(lines)
+    }
+
+    fn aspiration_search(
+        &mut self,
+        board: &Board,
+        depth: u8,
+        previous: Option<&AlphaBetaResult>,
+    ) -> AlphaBetaResult {
+        let previous_best = previous.map(|result| result.le_move);
+        let previous_score = previous.map(|result| result.score).filter(|score| {
+            self.pruning.aspiration_windows
+                && self.multi_pv <= 1
+                && depth >= ASPIRATION_MIN_DEPTH
+                && !is_mate_score(*score)
+        });
+        let Some(center) = previous_score else {
+            return self.alpha_beta_outer(
+                board.clone(),
+                depth,
+                previous_best,
+                (i32::MIN, i32::MAX),
+            );
+        };
+
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut lower, mut upper) = (center - delta, center + delta);
+        loop {
+            let result = self.alpha_beta_outer(board.clone(), depth, previous_best, (lower, upper));
+            let failed_low = lower != i32::MIN && result.score <= lower;
+            let failed_high = upper != i32::MAX && result.score >= upper;
+            if self.stopped || !(failed_low || failed_high) {
+                return result;
+            }
+            delta *= 4;
+            let too_wide = delta > MAX_ASPIRATION_WINDOW;
+            if failed_low {
+                lower = if too_wide { i32::MIN } else { center - delta };
+            }
+            if failed_high {
+                upper = if too_wide { i32::MAX } else { center + delta };
+            }
+        }
+    }
+
+    fn tablebase_lines(&self, board: &Board) -> Option<Vec<SearchLine>> {
+        let tablebases = self.tablebases.as_ref()?;
+        let moves = tablebases.rank_moves(board)?;
+        let lines = moves
+            .into_iter()
+            .take(self.multi_pv.max(1))
+            .map(|(le_move, result)| {
+                let mut pv = vec![le_move];
+                let mut position = board.try_move(le_move);
+                while pv.len() < self.max_depth as usize {
+                    match tablebases.best_move(&position) {
+                        Some((next, _)) => {
+                            pv.push(next);
+                            position = position.try_move(next);
+                        }
+                        None => break,
+                    }
                 }
-                if score < beta {
-                    beta = score;
-                    best_move = AlphaBetaResult::new(move_to_try, score);
+                SearchLine {
+                    score: result.score(board.turn),
+                    pv,
                 }
-            }
+            })
+            .collect();
+        Some(lines)
+    }
+
+    /// Searches the position to `max_depth`, or for `move_time` when it is set. The score is
+    /// from white's point of view.
+    pub fn search(&mut self, board: &Board) -> AlphaBetaResult {
+        let limits = SearchLimits {
+            depth: Some(self.max_depth),
+            move_time: self.move_time,
+            ..Default::default()
+        };
+        self.search_with_limits(board, &limits)
+    }
+
+    pub fn search_with_limits(&mut self, board: &Board, limits: &SearchLimits) -> AlphaBetaResult {
+        self.color = board.turn;
+        if let Some(lines) = self.tablebase_lines(board) {
+            self.n_calculations = 0;
+            return AlphaBetaResult::from_lines(lines);
+        }
+        self.transposition_table.new_search();
+        if self.threads <= 1 {
+            return self.iterative_deepening(board, limits, 0);
         }
+        if self
+            .thread_pool
+            .as_ref()
+            .is_none_or(|pool| pool.current_num_threads() != self.threads)
+        {
+            self.thread_pool = ThreadPoolBuilder::new()
+                .num_threads(self.threads)
+                .build()
+                .ok()
+                .map(Arc::new);
+        }
+        let pool = match self.thread_pool.clone() {
+            Some(pool) => pool,
+            None => return self.iterative_deepening(board, limits, 0),
+        };
+
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        let mut helpers: Vec<ChessBot> = (1..self.threads)
+            .map(|_| ChessBot {
+                quiescence_checks: self.quiescence_checks,
+                pruning: self.pruning,
+                skill: self.skill,
+                weights: self.weights,
+                contempt: self.contempt,
+                #[cfg(feature = "nnue")]
+                network: self.network.clone(),
+                tablebases: self.tablebases.clone(),
+                stop_signal: Arc::clone(&stop_signal),
+                ..ChessBot::with_table(
+                    self.color,
+                    self.max_depth,
+                    Arc::clone(&self.transposition_table),
+                )
+            })
+            .collect();
+        let helper_limits = SearchLimits {
+            depth: limits.depth,
+            ..Default::default()
+        };
 
+        let mut best_move = AlphaBetaResult::default();
+        pool.scope(|scope| {
+            for (index, helper) in helpers.iter_mut().enumerate() {
+                let helper_limits = &helper_limits;
+                scope.spawn(move |_| {
+                    helper.iterative_deepening(board, helper_limits, index + 1);
+                });
+            }
+            best_move = self.iterative_deepening(board, limits, 0);
+            stop_signal.store(true, Ordering::Relaxed);
+        });
+        self.n_calculations += helpers
+            .iter()
+            .map(|helper| helper.n_calculations)
+            .sum::<usize>();
         best_move
     }
 
-    pub fn get_bot_move(&mut self, board: &Board) -> Move {
-        self.color = board.turn;
+    fn iterative_deepening(
+        &mut self,
+        board: &Board,
+        limits: &SearchLimits,
+        helper_index: usize,
+    ) -> AlphaBetaResult {
         self.n_calculations = 0;
+        self.stopped = false;
+        self.time_manager = None;
+        self.node_limit = None;
+        self.move_ordering.new_search();
+        self.refresh_accumulators(board);
+
+        let mut time_manager = TimeManager::new(limits, board.turn);
+        let max_depth = limits
+            .depth
+            .unwrap_or(MAX_DEPTH)
+            .min(self.skill.max_depth())
+            .max(1);
+        let node_limit = match (limits.nodes, self.skill.max_nodes()) {
+            (Some(nodes), Some(skill_nodes)) => Some(nodes.min(skill_nodes)),
+            (nodes, skill_nodes) => nodes.or(skill_nodes),
+        };
+        let first_depth = (1 + (helper_index % 2) as u8).min(max_depth);
+        let mut best_move: Option<AlphaBetaResult> = None;
+
+        for depth in first_depth..=max_depth {
+            let previous_best = best_move.as_ref().map(|result| result.le_move);
+            let result = self.aspiration_search(board, depth, best_move.as_ref());
+            if self.stopped {
+                break;
+            }
+            let best_move_changed = previous_best != Some(result.le_move);
+            // every mate this close is seen, so none is sooner
+            let is_mate = mate_distance(result.score)
+                .is_some_and(|plies| plies.unsigned_abs() <= depth as u32);
+            best_move = Some(AlphaBetaResult { depth, ..result });
+
+            let out_of_nodes = node_limit.is_some_and(|nodes| self.n_calculations >= nodes);
+            if is_mate || out_of_nodes || time_manager.should_stop(best_move_changed) {
+                break;
+            }
+            self.time_manager = Some(time_manager);
+            self.node_limit = node_limit;
+        }
+        self.time_manager = None;
+        self.node_limit = None;
+
+        best_move.unwrap_or(AlphaBetaResult::default())
+    }
+
+    fn book_move(&self, board: &Board) -> Option<AlphaBetaResult> {
+        let le_move = self.book.as_ref()?.choose(board)?;
+        Some(AlphaBetaResult {
+            le_move,
+            pv: vec![le_move],
+            from_book: true,
+            ..AlphaBetaResult::default()
+        })
+    }
 
-        let start = Instant::now();
-        let best_move = self.alpha_beta_outer(board.clone());
-        let end = start.elapsed();
+    fn play_move(&mut self, board: &Board, limits: &SearchLimits) -> AlphaBetaResult {
+        let multi_pv = self.multi_pv;
+        self.multi_pv = multi_pv.max(self.skill.candidate_moves());
+        let mut best_move = self.search_with_limits(board, limits);
+        self.multi_pv = multi_pv;
 
-        println!("Highest point move: {}", best_move.score);
-        println!(
-            "Chosen move {} {}",
-            best_move.le_move.from.get_as_chess_string(),
-            best_move.le_move.to.get_as_chess_string()
+        let index = self.skill.choose_line(
+            &best_move.lines,
+            board.turn == Color::White,
+            &mut rand::thread_rng(),
         );
+        if let Some(line) = best_move.lines.get(index).filter(|_| index > 0) {
+            best_move.le_move = line.pv[0];
+            best_move.score = line.score;
+            best_move.pv = line.pv.clone();
+            best_move.chosen_line = index;
+        }
+        best_move
+    }
+
+    pub fn get_bot_move(&mut self, board: &Board) -> AlphaBetaResult {
+        if let Some(book_move) = self.book_move(board) {
+            return book_move;
+        }
+        let limits = SearchLimits {
+            depth: Some(self.max_depth),
+            move_time: self.move_time,
+            ..Default::default()
+        };
+        self.play_move(board, &limits)
+    }
+
+    pub fn get_bot_move_with_clock(&mut self, board: &Board, clock: &Clock) -> AlphaBetaResult {
+        if let Some(book_move) = self.book_move(board) {
+            return book_move;
+        }
+        let limits = SearchLimits {
+            depth: Some(self.max_depth),
+            ..SearchLimits::from_clock(clock, board.turn)
+        };
+        self.play_move(board, &limits)
+    }
+}
+
+#[cfg(test)]
+mod test_bot_moves {
+    use crate::board::Board;
+    use crate::bot::{
+        format_score, ChessBot, Pruning, SearchLimits, SkillLevel, Tablebases, TranspositionTable,
+        MATE_SCORE,
+    };
+    use crate::clock::{Clock, TimeControl};
+    use crate::helpers::{chess_move, Move};
+    use crate::pieces::Color;
+    use crate::polyglot::{encode_book_move, BookEntry, OpeningBook, PolyglotKeys};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_search_always_has_a_legal_move() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut bot = ChessBot::new(Color::White, 3);
 
-        println!("Calculation took {:?}", end);
+        // even without any time depth 1 is searched
+        let result = bot.search_with_limits(&board, &SearchLimits::move_time(Duration::ZERO));
+        assert!(board.generate_moves().contains(&result.le_move));
+        assert_eq!(result.depth, 1);
 
-        best_move.le_move
+        let clock = Clock::new(TimeControl::sudden_death(Duration::from_secs(600)));
+        let le_move = bot.get_bot_move_with_clock(&board, &clock).le_move;
+        assert_eq!(le_move.to, "a8".parse().unwrap());
+    }
+
+    #[test]
+    fn test_iterative_deepening_depths() {
+        let board = Board::new();
+        let mut bot = ChessBot::new(Color::White, 3);
+        let result = bot.search(&board);
+        assert_eq!(result.depth, 3);
+        assert!(board.generate_moves().contains(&result.le_move));
+
+        // mate is found at depth 1 and deeper iterations are skipped
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = bot.search_with_limits(&board, &SearchLimits::default());
+        assert_eq!(result.depth, 1);
+        assert_eq!(result.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn test_transposition_table_keeps_scores_and_saves_work() {
+        for (fen, saves_work) in [
+            (
+                "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+                true,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+                true,
+            ),
+            // too few transpositions at this depth to count on fewer nodes
+            ("4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 2", false),
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            // without the move ordering, which takes away some of what the table saves
+            let mut without_table = ChessBot::new(Color::White, 3);
+            without_table.transposition_table = Arc::new(TranspositionTable::new(0));
+            without_table.move_ordering.enabled = false;
+            let mut with_table = ChessBot::new(Color::White, 3);
+            with_table.move_ordering.enabled = false;
+
+            let expected = without_table.search(&board);
+            let result = with_table.search(&board);
+            assert_eq!(result.score, expected.score, "{}", fen);
+            if saves_work {
+                assert!(
+                    with_table.n_calculations < without_table.n_calculations,
+                    "{}",
+                    fen
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_quiescence_sees_recaptures() {
+        // at depth 1 the pawn on d5 looks free, but exd5 takes the queen back
+        let board = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut bot = ChessBot::new(Color::White, 1);
+        let result = bot.search(&board);
+        assert_ne!(
+            result.le_move,
+            Move::new("d1".parse().unwrap(), "d5".parse().unwrap())
+        );
+        assert!(result.score > 0);
+
+        // moving the king leaves the knight to the pawn after the horizon
+        let board = Board::from_fen("4k3/8/4n3/3P4/8/8/8/4K3 b - - 0 1").unwrap();
+        let mut bot = ChessBot::new(Color::Black, 1);
+        let result = bot.search(&board);
+        assert_eq!(result.le_move.from, "e6".parse().unwrap());
+        assert!(result.score < 0);
+    }
+
+    #[test]
+    fn test_one_thread_is_deterministic() {
+        let board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let mut bot = ChessBot::new(Color::White, 3);
+            let result = bot.search(&board);
+            runs.push((result.le_move, result.score, bot.n_calculations));
+        }
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[test]
+    fn test_helper_threads() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let mut bot = ChessBot::new(Color::White, 4);
+        bot.threads = 4;
+        let result = bot.search(&board);
+        assert_eq!(result.score, MATE_SCORE - 1);
+        assert_eq!(
+            result.le_move,
+            Move::new("d1".parse().unwrap(), "d8".parse().unwrap())
+        );
+
+        // the helpers stop with the main thread, also when it runs out of time
+        let mut bot = ChessBot::with_move_time(Color::White, Duration::from_millis(300));
+        bot.threads = 3;
+        let start = std::time::Instant::now();
+        let result = bot.search(&Board::new());
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(Board::new().generate_moves().contains(&result.le_move));
+    }
+
+    #[test]
+    fn test_thread_pool_is_kept_between_searches() {
+        let board = Board::new();
+        let mut bot = ChessBot::new(Color::White, 2);
+        bot.threads = 2;
+        bot.search(&board);
+        let pool = bot.thread_pool.clone().unwrap();
+        bot.search(&board);
+        assert!(Arc::ptr_eq(bot.thread_pool.as_ref().unwrap(), &pool));
+
+        bot.threads = 3;
+        bot.search(&board);
+        assert_eq!(bot.thread_pool.as_ref().unwrap().current_num_threads(), 3);
+
+        let helper = ChessBot::with_table(Color::White, 2, Arc::clone(&bot.transposition_table));
+        assert!(Arc::ptr_eq(
+            &helper.transposition_table,
+            &bot.transposition_table
+        ));
+    }
+
+    #[test]
+    fn test_tablebases_convert() {
+        let tablebases = Arc::new(Tablebases::generate(&["KRK"]).unwrap());
+        let mut board = Board::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        let dtm = tablebases.probe(&board).unwrap().dtm;
+        let mut bot = ChessBot::new(Color::White, 2);
+        bot.tablebases = Some(tablebases);
+        for _ in 0..dtm {
+            let result = bot.search(&board);
+            assert!(result.score.abs() > MATE_SCORE - 100);
+            board = board.try_move(result.le_move);
+        }
+        assert!(board.generate_moves().is_empty());
+        assert!(board.is_king_in_check(&board.turn));
+
+        // a search that runs into the tables
+        let board = Board::from_fen("8/8/8/4k3/8/8/3r4/4K2Q w - - 0 1").unwrap();
+        let mut bot = ChessBot::new(Color::White, 2);
+        bot.tablebases = Some(Arc::new(Tablebases::generate(&["KQK"]).unwrap()));
+        let result = bot.search(&board);
+        assert_eq!(
+            (result.le_move.from, result.le_move.to),
+            ("e1".parse().unwrap(), "d2".parse().unwrap())
+        );
+        assert!(result.score > MATE_SCORE - 100);
+    }
+
+    #[test]
+    fn test_principal_variation_and_multi_pv() {
+        let board =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        // the selective search depends on the windows, which differ with more lines
+        let mut bot = ChessBot::new(Color::White, 4);
+        bot.pruning = Pruning::none();
+        let best = bot.search(&board);
+        assert_eq!(best.pv[0], best.le_move);
+        assert!(best.pv.len() > 1);
+        let mut position = board.clone();
+        for le_move in &best.pv {
+            assert!(position.generate_moves().contains(le_move));
+            position = position.try_move(*le_move);
+        }
+
+        let mut bot = ChessBot::new(Color::White, 4);
+        bot.pruning = Pruning::none();
+        bot.multi_pv = 3;
+        let result = bot.search(&board);
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!((result.le_move, result.score), (best.le_move, best.score));
+        assert_eq!(result.pv, result.lines[0].pv);
+        assert!(result
+            .lines
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+        let first_moves: Vec<Move> = result.lines.iter().map(|line| line.pv[0]).collect();
+        for (i, le_move) in first_moves.iter().enumerate() {
+            assert!(!first_moves[i + 1..].contains(le_move));
+        }
+    }
+
+    #[test]
+    fn test_pruning_saves_nodes_and_keeps_mates() {
+        let board =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let mut nodes = Vec::new();
+        for pruning in [Pruning::none(), Pruning::default()] {
+            let mut bot = ChessBot::new(Color::White, 4);
+            bot.pruning = pruning;
+            bot.search(&board);
+            nodes.push(bot.n_calculations);
+        }
+        assert!(nodes[1] < nodes[0]);
+
+        // the mate is found with everything on
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/1Q3PPP/3R2K1 w - - 0 1").unwrap();
+        let mut bot = ChessBot::new(Color::White, 4);
+        let result = bot.search(&board);
+        assert_eq!(result.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn test_mates_count_plies() {
+        // 1. Re8+ Rxe8 2. Rxe8#
+        let board = Board::from_fen("r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1").unwrap();
+        let mut bot = ChessBot::new(Color::White, 4);
+        let result = bot.search(&board);
+        assert_eq!(result.score, MATE_SCORE - 3);
+        assert_eq!(format_score(result.score), "+mate in 2");
+        // the scores stored on the way still give the distance from the new root
+        let result = bot.search(&board);
+        assert_eq!(result.score, MATE_SCORE - 3);
+
+        let board = Board::from_fen("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1").unwrap();
+        let result = ChessBot::new(Color::Black, 3).search(&board);
+        assert_eq!(format_score(result.score), "-mate in 1");
+        assert_eq!(format_score(-35), "-0.35");
+    }
+
+    #[test]
+    fn test_skill_levels_limit_the_search() {
+        let board =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let mut bot = ChessBot::new(Color::White, 6);
+        bot.skill = SkillLevel::new(1);
+        assert_eq!(bot.search(&board).depth, 1);
+        bot.skill = SkillLevel::new(10);
+        assert!(bot.search(&board).depth <= 4);
+
+        // the node limit stops the search like the hard time limit, but not before depth 1
+        let mut bot = ChessBot::new(Color::White, 10);
+        let limits = SearchLimits {
+            nodes: Some(500),
+            ..Default::default()
+        };
+        let result = bot.search_with_limits(&board, &limits);
+        assert!(result.depth >= 1 && result.depth < 10);
+
+        let mut bot =
+            ChessBot::with_skill(Color::White, SkillLevel::new(3), Duration::from_secs(1));
+        for _ in 0..5 {
+            let result = bot.get_bot_move(&board);
+            assert!(board.generate_moves().contains(&result.le_move));
+            assert_eq!(result.lines[result.chosen_line].pv[0], result.le_move);
+        }
+    }
+
+    #[test]
+    fn test_book_moves_are_marked() {
+        let board = Board::new();
+        let e4 = chess_move("e2", "e4");
+        let entry = BookEntry {
+            key: PolyglotKeys::builtin().hash(&board),
+            le_move: encode_book_move(&board, e4),
+            weight: 1,
+            learn: 0,
+        };
+        let mut bot = ChessBot::new(Color::White, 2);
+        bot.book = Some(
+            OpeningBook::from_bytes(
+                &OpeningBook::write_entries(&[entry]),
+                PolyglotKeys::builtin(),
+            )
+            .unwrap(),
+        );
+        let result = bot.get_bot_move(&board);
+        assert!(result.from_book);
+        assert_eq!(result.le_move, e4);
+        assert!(!bot.get_bot_move(&board.try_move(e4)).from_book);
     }
 }
@@ -0,0 +1,484 @@
+use crate::board::Board;
+use crate::bot::{phase_weight, piece_square_values, taper, MAX_PHASE};
+use crate::helpers::Square;
+use crate::pieces::{Color, Piece, PieceKind};
+
+use std::fmt;
+
+/// Score of a mate on the board, far above anything the evaluation can reach. In the search a
+/// mate scores this minus the plies from the root to it, so sooner mates score higher.
+pub const MATE_SCORE: i32 = 100_000;
+
+/// Scores at least this far from zero are mates. Even with every piece on the board the
+/// evaluation stays far below it, so the two never mix.
+const MATE_BOUND: i32 = MATE_SCORE - 1000;
+
+/// Whether the score is a forced mate for either side rather than an evaluation.
+pub fn is_mate_score(score: i32) -> bool {
+    (MATE_BOUND as u32..=MATE_SCORE as u32).contains(&score.unsigned_abs())
+}
+
+/// Score from white's point of view of `color` being mated `ply` plies from the root.
+pub fn mated_score(color: Color, ply: usize) -> i32 {
+    let score = MATE_SCORE - ply as i32;
+    if color == Color::White {
+        -score
+    } else {
+        score
+    }
+}
+
+/// Plies from the root to the mate, positive when white mates.
+pub fn mate_distance(score: i32) -> Option<i32> {
+    is_mate_score(score).then(|| {
+        if score > 0 {
+            MATE_SCORE - score
+        } else {
+            -(MATE_SCORE + score)
+        }
+    })
+}
+
+/// Mate scores in the search count the plies from the root, stored ones (and tablebase ones)
+/// count them from the position, so they stay right wherever the position comes up again.
+/// This turns a score from the root at `ply` into one from the position.
+pub fn score_to_node(score: i32, ply: usize) -> i32 {
+    match mate_distance(score) {
+        Some(distance) if distance > 0 => score + ply as i32,
+        Some(_) => score - ply as i32,
+        None => score,
+    }
+}
+
+/// The other way around from `score_to_node`.
+pub fn score_to_root(score: i32, ply: usize) -> i32 {
+    match mate_distance(score) {
+        Some(distance) if distance > 0 => score - ply as i32,
+        Some(_) => score + ply as i32,
+        None => score,
+    }
+}
+
+const TEMPO: i32 = 10;
+
+/// Bonus for a passed pawn by how far it got, from its own side.
+const PASSED_PAWN_BONUS: [i32; 8] = [0, 5, 10, 20, 35, 60, 100, 0];
+const DOUBLED_PAWN_PENALTY: i32 = 12;
+const ISOLATED_PAWN_PENALTY: i32 = 12;
+const PAWN_SHIELD_BONUS: [i32; 2] = [12, 6];
+const OPEN_FILE_NEAR_KING_PENALTY: i32 = 15;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Term {
+    Material,
+    PieceSquare,
+    Mobility,
+    PawnStructure,
+    KingSafety,
+    Tempo,
+}
+
+impl Term {
+    pub const ALL: [Term; 6] = [
+        Term::Material,
+        Term::PieceSquare,
+        Term::Mobility,
+        Term::PawnStructure,
+        Term::KingSafety,
+        Term::Tempo,
+    ];
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Term::Material => "Material",
+            Term::PieceSquare => "Piece-square",
+            Term::Mobility => "Mobility",
+            Term::PawnStructure => "Pawn structure",
+            Term::KingSafety => "King safety",
+            Term::Tempo => "Tempo",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How a bot weighs the evaluation, the defaults give the plain evaluation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EvalWeights {
+    /// Centipawns, indexed by `PieceKind`.
+    piece_values: [i32; 6],
+    /// Percent of each term that counts, indexed by `Term`.
+    term_weights: [i32; 6],
+    /// Percent of the opponent's king safety that counts for the bot on top of the king safety
+    /// weight, how much it goes for the king.
+    pub aggression: i32,
+}
+
+impl Default for EvalWeights {
+    fn default() -> EvalWeights {
+        let mut piece_values = [0; 6];
+        for kind in [
+            PieceKind::P,
+            PieceKind::R,
+            PieceKind::N,
+            PieceKind::B,
+            PieceKind::K,
+            PieceKind::Q,
+        ] {
+            piece_values[kind as usize] =
+                Piece::new(Color::White, kind, Square::new(0)).points * 100;
+        }
+        EvalWeights {
+            piece_values,
+            term_weights: [100; 6],
+            aggression: 100,
+        }
+    }
+}
+
+impl EvalWeights {
+    pub fn piece_value(&self, kind: PieceKind) -> i32 {
+        self.piece_values[kind as usize]
+    }
+
+    pub fn set_piece_value(&mut self, kind: PieceKind, centipawns: i32) {
+        self.piece_values[kind as usize] = centipawns;
+    }
+
+    pub fn term_weight(&self, term: Term) -> i32 {
+        self.term_weights[term as usize]
+    }
+
+    pub fn set_term_weight(&mut self, term: Term, percent: i32) {
+        self.term_weights[term as usize] = percent;
+    }
+
+    /// The evaluation from white's point of view as the bot playing `color` weighs it.
+    pub fn total(&self, evaluation: &Evaluation, color: Color) -> i32 {
+        if self.term_weights == [100; 6] && self.aggression == 100 {
+            return evaluation.total();
+        }
+        let mut total = 0;
+        for term in Term::ALL {
+            for side in [Color::White, Color::Black] {
+                let mut weight = self.term_weight(term);
+                if term == Term::KingSafety && side != color {
+                    weight = weight * self.aggression / 100;
+                }
+                let score = evaluation.get(term, side) * weight / 100;
+                total += if side == Color::White { score } else { -score };
+            }
+        }
+        total
+    }
+}
+
+/// Centipawn scores of every term for each side, each side's own term being good for it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Evaluation {
+    terms: [[i32; 2]; 6],
+    /// Game phase the piece-square tables were blended with, `MAX_PHASE` in the opening.
+    pub phase: i32,
+}
+
+fn color_index(color: Color) -> usize {
+    (color == Color::Black) as usize
+}
+
+impl Evaluation {
+    pub fn get(&self, term: Term, color: Color) -> i32 {
+        self.terms[term as usize][color_index(color)]
+    }
+
+    /// The term from white's point of view.
+    pub fn term(&self, term: Term) -> i32 {
+        self.get(term, Color::White) - self.get(term, Color::Black)
+    }
+
+    pub fn side_total(&self, color: Color) -> i32 {
+        Term::ALL.iter().map(|term| self.get(*term, color)).sum()
+    }
+
+    /// The whole evaluation from white's point of view.
+    pub fn total(&self) -> i32 {
+        self.side_total(Color::White) - self.side_total(Color::Black)
+    }
+
+    fn add(&mut self, term: Term, color: Color, score: i32) {
+        self.terms[term as usize][color_index(color)] += score;
+    }
+}
+
+fn pawns(value: i32) -> String {
+    format!("{:.2}", value as f32 / 100.)
+}
+
+impl fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16}{:>8}{:>8}{:>8}",
+            "Term", "White", "Black", "Total"
+        )?;
+        for term in Term::ALL {
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>8}{:>8}",
+                term.to_string(),
+                pawns(self.get(term, Color::White)),
+                pawns(self.get(term, Color::Black)),
+                pawns(self.term(term))
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<16}{:>8}{:>8}{:>8}",
+            "Total",
+            pawns(self.side_total(Color::White)),
+            pawns(self.side_total(Color::Black)),
+            pawns(self.total())
+        )?;
+        write!(
+            f,
+            "{:<16}{:>8}",
+            "Phase",
+            format!("{}/{}", self.phase, MAX_PHASE)
+        )
+    }
+}
+
+fn mobility_weight(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::N => 4,
+        PieceKind::B => 5,
+        PieceKind::R => 2,
+        PieceKind::Q => 1,
+        PieceKind::P | PieceKind::K => 0,
+    }
+}
+
+/// Ranks counted from the color's own side, 0 is its first rank.
+fn relative_rank(square: Square, color: Color) -> u8 {
+    if color == Color::White {
+        square.rank()
+    } else {
+        7 - square.rank()
+    }
+}
+
+fn pawn_structure(own_pawns: &[&Piece], opponent_pawns: &[&Piece], color: Color) -> i32 {
+    let mut score = 0;
+    let mut pawns_on_file = [0; 8];
+    for pawn in own_pawns {
+        pawns_on_file[pawn.position.file() as usize] += 1;
+    }
+    for n_pawns in pawns_on_file {
+        if n_pawns > 1 {
+            score -= DOUBLED_PAWN_PENALTY * (n_pawns - 1);
+        }
+    }
+
+    for pawn in own_pawns {
+        let file = pawn.position.file() as i32;
+        let has_neighbour = [file - 1, file + 1]
+            .into_iter()
+            .any(|f| (0..8).contains(&f) && pawns_on_file[f as usize] > 0);
+        if !has_neighbour {
+            score -= ISOLATED_PAWN_PENALTY;
+        }
+
+        let rank = relative_rank(pawn.position, color);
+        let is_passed = !opponent_pawns.iter().any(|opponent| {
+            (opponent.position.file() as i32 - file).abs() <= 1
+                && relative_rank(opponent.position, color) > rank
+        });
+        if is_passed {
+            score += PASSED_PAWN_BONUS[rank as usize];
+        }
+    }
+    score
+}
+
+/// Pawns in front of the king, and open files next to it, only matter while the opponent has
+/// a queen to attack with.
+fn king_safety(board: &Board, color: Color, own_pawns: &[&Piece], opponent: &[Piece]) -> i32 {
+    if !opponent.iter().any(|piece| piece.kind == PieceKind::Q) {
+        return 0;
+    }
+    let king = board.king_positions[&color];
+    let forward = if color == Color::White { 1 } else { -1 };
+    let mut score = 0;
+    for file_step in -1..=1 {
+        for (distance, bonus) in PAWN_SHIELD_BONUS.iter().enumerate() {
+            if let Some(square) = king.offset(file_step, forward * (distance as i32 + 1)) {
+                if own_pawns.iter().any(|pawn| pawn.position == square) {
+                    score += bonus;
+                }
+            }
+        }
+        let file = king.file() as i32 + file_step;
+        if (0..8).contains(&file)
+            && !own_pawns
+                .iter()
+                .any(|pawn| pawn.position.file() as i32 == file)
+        {
+            score -= OPEN_FILE_NEAR_KING_PENALTY;
+        }
+    }
+    score
+}
+
+/// Breaks the evaluation of the position down into its terms. Mate and stalemate are left to
+/// the search, this only looks at the pieces.
+pub fn evaluate(board: &Board) -> Evaluation {
+    evaluate_with(board, &EvalWeights::default())
+}
+
+/// Like `evaluate`, with the piece values of the weights.
+pub fn evaluate_with(board: &Board, weights: &EvalWeights) -> Evaluation {
+    let all_pieces = board.get_pieces();
+    let all_positions = board.get_all_positions();
+    let mut evaluation = Evaluation {
+        phase: all_pieces
+            .iter()
+            .flatten()
+            .map(|piece| phase_weight(piece.kind))
+            .sum::<i32>()
+            .min(MAX_PHASE),
+        ..Default::default()
+    };
+
+    for color in [Color::White, Color::Black] {
+        let index = color_index(color);
+        let own = &all_pieces[index];
+        let opponent = &all_pieces[1 - index];
+        let own_pawns: Vec<&Piece> = own.iter().filter(|p| p.kind == PieceKind::P).collect();
+        let opponent_pawns: Vec<&Piece> =
+            opponent.iter().filter(|p| p.kind == PieceKind::P).collect();
+
+        for piece in own {
+            evaluation.add(Term::Material, color, weights.piece_value(piece.kind));
+            let (middlegame, endgame) = piece_square_values(piece);
+            evaluation.add(
+                Term::PieceSquare,
+                color,
+                taper(middlegame, endgame, evaluation.phase),
+            );
+            let weight = mobility_weight(piece.kind);
+            if weight > 0 {
+                let n_moves = piece
+                    .get_pseudo_legal_moves(&all_positions[index], &all_positions[1 - index], board)
+                    .len() as i32;
+                evaluation.add(Term::Mobility, color, weight * n_moves);
+            }
+        }
+        evaluation.add(
+            Term::PawnStructure,
+            color,
+            pawn_structure(&own_pawns, &opponent_pawns, color),
+        );
+        evaluation.add(
+            Term::KingSafety,
+            color,
+            king_safety(board, color, &own_pawns, opponent),
+        );
+    }
+    evaluation.add(Term::Tempo, board.turn, TEMPO);
+
+    evaluation
+}
+
+#[cfg(test)]
+mod test_evaluation {
+    use crate::board::Board;
+    use crate::bot::{
+        evaluate, is_mate_score, mate_distance, mated_score, score_to_node, score_to_root, Term,
+        MATE_SCORE,
+    };
+    use crate::pieces::Color;
+
+    #[test]
+    fn test_starting_position_is_balanced() {
+        let evaluation = evaluate(&Board::new());
+        for term in Term::ALL {
+            if term != Term::Tempo {
+                assert_eq!(evaluation.term(term), 0, "{}", term);
+            }
+        }
+        assert_eq!(evaluation.get(Term::Material, Color::White), 3900);
+        assert_eq!(evaluation.total(), evaluation.term(Term::Tempo));
+        assert!(evaluation.get(Term::Tempo, Color::White) > 0);
+    }
+
+    #[test]
+    fn test_mirrored_positions_mirror_the_terms() {
+        let white = evaluate(
+            &Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap(),
+        );
+        let black = evaluate(
+            &Board::from_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3")
+                .unwrap(),
+        );
+        for term in Term::ALL {
+            assert_eq!(white.term(term), -black.term(term), "{}", term);
+        }
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        // white has doubled, isolated pawns, black a passed pawn on its sixth rank
+        let evaluation = evaluate(&Board::from_fen("4k3/8/8/8/8/p3P3/4P3/4K3 w - - 0 1").unwrap());
+        assert!(evaluation.get(Term::PawnStructure, Color::White) < 0);
+        assert!(evaluation.get(Term::PawnStructure, Color::Black) > 0);
+    }
+
+    #[test]
+    fn test_king_safety() {
+        let sheltered =
+            evaluate(&Board::from_fen("q5k1/5ppp/8/8/8/8/5PPP/Q5K1 w - - 0 1").unwrap());
+        let exposed = evaluate(&Board::from_fen("q5k1/5ppp/8/8/8/8/8/Q5K1 w - - 0 1").unwrap());
+        assert_eq!(sheltered.term(Term::KingSafety), 0);
+        assert!(exposed.term(Term::KingSafety) < 0);
+        assert!(
+            evaluate(&Board::from_fen("6k1/8/8/8/8/8/8/6K1 w - - 0 1").unwrap())
+                .get(Term::KingSafety, Color::White)
+                == 0
+        );
+    }
+
+    #[test]
+    fn test_king_belongs_in_the_center_only_in_the_endgame() {
+        let piece_square = |fen: &str| {
+            let evaluation = evaluate(&Board::from_fen(fen).unwrap());
+            (evaluation.phase, evaluation.term(Term::PieceSquare))
+        };
+        let (phase, castled) =
+            piece_square("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPBPPP/RNBQ1RK1 w kq - 0 1");
+        assert_eq!(phase, 24);
+        let (_, centralized) =
+            piece_square("rnbqkbnr/pppppppp/8/8/4K3/5N2/PPPPBPPP/RNBQ1R2 w kq - 0 1");
+        assert!(castled > centralized);
+
+        let (phase, castled) = piece_square("4k3/pppp4/8/8/8/8/PPPP4/6K1 w - - 0 1");
+        assert_eq!(phase, 0);
+        let (_, centralized) = piece_square("4k3/pppp4/8/8/4K3/8/PPPP4/8 w - - 0 1");
+        assert!(centralized > castled);
+    }
+
+    #[test]
+    fn test_mate_scores() {
+        assert_eq!(mated_score(Color::Black, 3), MATE_SCORE - 3);
+        assert_eq!(mate_distance(mated_score(Color::Black, 3)), Some(3));
+        assert_eq!(mate_distance(mated_score(Color::White, 4)), Some(-4));
+        assert_eq!(mate_distance(2500), None);
+        assert!(!is_mate_score(i32::MAX) && !is_mate_score(i32::MIN));
+
+        // mated 5 plies from the root is mated 2 plies from a position at ply 3
+        let score = mated_score(Color::White, 5);
+        assert_eq!(score_to_node(score, 3), mated_score(Color::White, 2));
+        assert_eq!(score_to_root(score_to_node(score, 3), 3), score);
+        assert_eq!(score_to_node(120, 3), 120);
+    }
+}
@@ -1,3 +1,23 @@
 mod bot_moves;
+mod evaluation;
+mod move_ordering;
+#[cfg(feature = "nnue")]
+mod nnue;
+mod piece_square_tables;
+mod pruning;
+mod skill;
+mod tablebase;
+mod time_management;
+mod transposition;
 
 pub use bot_moves::*;
+pub use evaluation::*;
+pub use move_ordering::*;
+#[cfg(feature = "nnue")]
+pub use nnue::*;
+pub use piece_square_tables::*;
+pub use pruning::*;
+pub use skill::*;
+pub use tablebase::*;
+pub use time_management::*;
+pub use transposition::*;
@@ -0,0 +1,214 @@
+use crate::board::Board;
+use crate::bot::MAX_DEPTH;
+use crate::helpers::Move;
+use crate::pieces::{Color, PieceKind};
+
+const GOOD_CAPTURE_SCORE: i32 = 1_000_000;
+const KILLER_SCORES: [i32; 2] = [900_000, 890_000];
+const COUNTER_MOVE_SCORE: i32 = 800_000;
+const BAD_CAPTURE_SCORE: i32 = -1_000_000;
+
+/// History scores are halved once one of them gets here, so they stay below the counter move
+/// and recent cutoffs weigh more than old ones.
+const HISTORY_MAX: i32 = 100_000;
+
+/// Most valuable victim, least valuable attacker: taking the queen with a pawn comes first.
+fn mvv_lva(board: &Board, le_move: Move) -> i32 {
+    let attacker = board.get_piece_from_position(&le_move.from).unwrap();
+    let victim_points = match board.get_piece_from_position(&le_move.to) {
+        Some(victim) => victim.points,
+        // en passant, or a promotion without a capture
+        None => (attacker.kind == PieceKind::P) as i32,
+    };
+    let attacker_points = if attacker.kind == PieceKind::K {
+        10
+    } else {
+        attacker.points
+    };
+    victim_points * 10 - attacker_points
+}
+
+fn history_index(color: Color, le_move: Move) -> usize {
+    (color == Color::Black) as usize * 64 * 64 + le_move.from.index() * 64 + le_move.to.index()
+}
+
+/// Decides the order moves are searched in, the sooner the best move comes the more of the
+/// others are cut off. The order is: the move from the transposition table, captures that
+/// don't lose material by MVV-LVA, the two killer moves of the ply, the move that refuted the
+/// previous move last time, the other quiet moves by history score and the losing captures.
+/// Killers, counter moves and history are learned from the cutoffs of quiet moves.
+#[derive(Clone, Debug)]
+pub struct MoveOrdering {
+    /// Without it only the move from the transposition table is moved to the front, for
+    /// comparing node counts.
+    pub enabled: bool,
+    killers: Vec<[Option<Move>; 2]>,
+    /// Indexed by the from and to square of the previous move.
+    counter_moves: Vec<Option<Move>>,
+    /// Indexed by the color, from and to square of the move.
+    history: Vec<i32>,
+}
+
+impl MoveOrdering {
+    pub fn new() -> MoveOrdering {
+        MoveOrdering {
+            enabled: true,
+            killers: vec![[None; 2]; MAX_DEPTH as usize + 1],
+            counter_moves: vec![None; 64 * 64],
+            history: vec![0; 2 * 64 * 64],
+        }
+    }
+
+    /// Killers only make sense for the position they were found in, the history is kept but
+    /// counts less.
+    pub fn new_search(&mut self) {
+        self.killers.fill([None; 2]);
+        self.history.iter_mut().for_each(|score| *score /= 2);
+    }
+
+    fn score(&self, board: &Board, le_move: Move, ply: usize, counter_move: Option<Move>) -> i32 {
+        if board.material_gain(le_move) > 0 {
+            let attacker_points = board.get_piece_from_position(&le_move.from).unwrap().points;
+            // a capture of a piece worth at least as much can't lose material, no need for SEE
+            let is_good = board.material_gain(le_move) >= attacker_points * 100
+                || board.static_exchange(le_move) >= 0;
+            let base = if is_good {
+                GOOD_CAPTURE_SCORE
+            } else {
+                BAD_CAPTURE_SCORE
+            };
+            return base + mvv_lva(board, le_move);
+        }
+        if let Some(index) = self.killers[ply]
+            .iter()
+            .position(|killer| *killer == Some(le_move))
+        {
+            return KILLER_SCORES[index];
+        }
+        if counter_move == Some(le_move) {
+            return COUNTER_MOVE_SCORE;
+        }
+        self.history[history_index(board.turn, le_move)]
+    }
+
+    /// The moves of the position in the order to search them. `ply` is the distance from the
+    /// root and `previous_move` the move that led to the position.
+    pub fn order(
+        &self,
+        board: &Board,
+        mut moves: Vec<Move>,
+        hash_move: Option<Move>,
+        ply: usize,
+        previous_move: Option<Move>,
+    ) -> Vec<Move> {
+        if self.enabled {
+            let ply = ply.min(self.killers.len() - 1);
+            let counter_move = previous_move.and_then(|previous| {
+                self.counter_moves[previous.from.index() * 64 + previous.to.index()]
+            });
+            let mut scored: Vec<(i32, Move)> = moves
+                .into_iter()
+                .map(|le_move| (self.score(board, le_move, ply, counter_move), le_move))
+                .collect();
+            scored.sort_by_key(|(score, _)| -score);
+            moves = scored.into_iter().map(|(_, le_move)| le_move).collect();
+        }
+        if let Some(index) = moves.iter().position(|m| Some(*m) == hash_move) {
+            moves[..=index].rotate_right(1);
+        }
+        moves
+    }
+
+    /// Learns from a move that caused a beta cutoff `depth_left` plies above the horizon.
+    /// Captures are ordered well enough by what they take.
+    pub fn record_cutoff(
+        &mut self,
+        board: &Board,
+        le_move: Move,
+        ply: usize,
+        depth_left: u8,
+        previous_move: Option<Move>,
+    ) {
+        if !self.enabled || board.material_gain(le_move) > 0 {
+            return;
+        }
+        let ply = ply.min(self.killers.len() - 1);
+        if self.killers[ply][0] != Some(le_move) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(le_move);
+        }
+        if let Some(previous) = previous_move {
+            self.counter_moves[previous.from.index() * 64 + previous.to.index()] = Some(le_move);
+        }
+
+        let index = history_index(board.turn, le_move);
+        self.history[index] += depth_left as i32 * depth_left as i32;
+        if self.history[index] >= HISTORY_MAX {
+            self.history.iter_mut().for_each(|score| *score /= 2);
+        }
+    }
+}
+
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering::new()
+    }
+}
+
+#[cfg(test)]
+mod test_move_ordering {
+    use crate::board::Board;
+    use crate::bot::{ChessBot, MoveOrdering};
+    use crate::helpers::chess_move;
+    use crate::pieces::Color;
+
+    #[test]
+    fn test_order() {
+        // pawn takes queen, knight takes rook, queen takes a pawn the black queen defends
+        let board = Board::from_fen("4k3/8/8/3q4/4P2r/3p1N2/8/3QK3 w - - 0 1").unwrap();
+        let mut ordering = MoveOrdering::new();
+        let moves = ordering.order(&board, board.generate_moves(), None, 2, None);
+        assert_eq!(moves[..2], [chess_move("e4", "d5"), chess_move("f3", "h4")]);
+        assert_eq!(moves[moves.len() - 1], chess_move("d1", "d3"));
+
+        // a quiet cutoff makes a killer at its ply and a counter move elsewhere
+        let previous_move = Some(chess_move("c6", "d5"));
+        let killer = chess_move("e1", "f1");
+        ordering.record_cutoff(&board, killer, 2, 3, previous_move);
+        let moves = ordering.order(&board, board.generate_moves(), None, 2, None);
+        assert_eq!(moves[2], killer);
+        let moves = ordering.order(&board, board.generate_moves(), None, 5, previous_move);
+        assert_eq!(moves[2], killer);
+        // and counts in the history of its color
+        let moves = ordering.order(&board, board.generate_moves(), None, 5, None);
+        assert_eq!(moves[2], killer);
+
+        // the hash move goes before everything
+        let hash_move = chess_move("d1", "d3");
+        let moves = ordering.order(&board, board.generate_moves(), Some(hash_move), 2, None);
+        assert_eq!(moves[0], hash_move);
+
+        ordering.enabled = false;
+        assert_eq!(
+            ordering.order(&board, board.generate_moves(), None, 2, None),
+            board.generate_moves()
+        );
+    }
+
+    #[test]
+    fn test_ordering_saves_nodes() {
+        for fen in [
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let mut unordered = ChessBot::new(Color::White, 3);
+            unordered.move_ordering.enabled = false;
+            let mut ordered = ChessBot::new(Color::White, 3);
+
+            let expected = unordered.search(&board);
+            assert_eq!(ordered.search(&board).score, expected.score, "{}", fen);
+            assert!(ordered.n_calculations < unordered.n_calculations, "{}", fen);
+        }
+    }
+}
@@ -0,0 +1,556 @@
+//! An efficiently updatable neural network evaluation (NNUE), built with the `nnue` feature.
+//!
+//! The network is `768 -> N x 2 -> 1`: one input per piece kind, color and square, seen from
+//! each side, a hidden layer of `N` the search keeps up to date move by move (the accumulator),
+//! clipped ReLU, and one output from the side to move's and the other side's halves.
+//!
+//! Weights file, little endian:
+//!
+//! | bytes                 | content                                                      |
+//! |-----------------------|--------------------------------------------------------------|
+//! | 4                     | `NNUE_MAGIC`                                                 |
+//! | 2                     | version, `NNUE_VERSION`                                      |
+//! | 1                     | bits of the weights, 16 or 8                                 |
+//! | 1                     | 0                                                            |
+//! | 4                     | `N`                                                          |
+//! | 2, 2, 2               | `QA`, `QB`, centipawn scale                                  |
+//! | 768 × N weights       | hidden layer weights, input by input, quantized by `QA`      |
+//! | N × i16               | hidden layer biases, quantized by `QA`                       |
+//! | 2 × N weights         | output weights, the side to move's half first, by `QB`       |
+//! | i32                   | output bias, quantized by `QA × QB`                          |
+//!
+//! An input is `side × 384 + kind × 64 + square` from each side's point of view: `side` is 0
+//! for its own pieces, `kind` goes pawn, knight, bishop, rook, queen, king, and squares go a1,
+//! b1, ... h8, mirrored vertically for black. The evaluation is
+//! `(Σ crelu(us) · w_us + Σ crelu(them) · w_them + bias) × scale / (QA × QB)`, crelu clipping
+//! to `0..=QA`.
+
+use crate::board::Board;
+use crate::helpers::Move;
+use crate::moves::get_rook_old_and_new_castling_positions;
+use crate::pieces::{Color, Piece, PieceKind};
+
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+pub const NNUE_MAGIC: &[u8; 4] = b"RCNN";
+pub const NNUE_VERSION: u16 = 1;
+pub const NNUE_INPUTS: usize = 768;
+/// Bigger hidden layers are taken for a broken file rather than allocated.
+const MAX_HIDDEN_SIZE: usize = 4096;
+const HEADER_SIZE: usize = 18;
+/// Network scores are kept clear of the mate scores.
+const MAX_NETWORK_SCORE: i32 = 20_000;
+
+/// How the weights are stored. Biases and the accumulator are 16 bits either way.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Quantization {
+    Int16,
+    Int8,
+}
+
+impl Quantization {
+    fn bits(&self) -> u8 {
+        match self {
+            Quantization::Int16 => 16,
+            Quantization::Int8 => 8,
+        }
+    }
+
+    fn weight_size(&self) -> usize {
+        self.bits() as usize / 8
+    }
+}
+
+fn invalid_network(message: impl std::fmt::Display) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid network: {}", message),
+    )
+}
+
+fn kind_index(kind: PieceKind) -> usize {
+    match kind {
+        PieceKind::P => 0,
+        PieceKind::N => 1,
+        PieceKind::B => 2,
+        PieceKind::R => 3,
+        PieceKind::Q => 4,
+        PieceKind::K => 5,
+    }
+}
+
+/// The input of a piece from `perspective`'s side.
+fn input_index(perspective: Color, color: Color, kind: PieceKind, square: usize) -> usize {
+    let (side, square) = if perspective == Color::White {
+        (color != Color::White, square)
+    } else {
+        (color != Color::Black, square ^ 56)
+    };
+    side as usize * 384 + kind_index(kind) * 64 + square
+}
+
+/// Reads the file front to back.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < n {
+            return Err(invalid_network("file too short"));
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes([self.take(1)?[0], self.take(1)?[0]]))
+    }
+
+    fn weights(&mut self, n: usize, quantization: Quantization) -> Result<Vec<i16>, Error> {
+        let bytes = self.take(n * quantization.weight_size())?;
+        Ok(match quantization {
+            Quantization::Int16 => bytes
+                .chunks_exact(2)
+                .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+                .collect(),
+            Quantization::Int8 => bytes.iter().map(|byte| *byte as i8 as i16).collect(),
+        })
+    }
+}
+
+/// A loaded network, see the module for the format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    hidden_size: usize,
+    quantization: Quantization,
+    /// `NNUE_INPUTS` rows of `hidden_size`.
+    hidden_weights: Vec<i16>,
+    hidden_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+    qa: i32,
+    qb: i32,
+    scale: i32,
+}
+
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, Error> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != NNUE_MAGIC {
+            return Err(invalid_network("not a network file"));
+        }
+        let version = reader.u16()?;
+        if version != NNUE_VERSION {
+            return Err(invalid_network(format!("unknown version {}", version)));
+        }
+        let quantization = match reader.take(2)? {
+            [16, 0] => Quantization::Int16,
+            [8, 0] => Quantization::Int8,
+            [bits, _] => return Err(invalid_network(format!("{}-bit weights", bits))),
+            _ => unreachable!(),
+        };
+        let size = reader.take(4)?;
+        let hidden_size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+        if hidden_size == 0 || hidden_size > MAX_HIDDEN_SIZE {
+            return Err(invalid_network(format!("hidden layer of {}", hidden_size)));
+        }
+        let (qa, qb, scale) = (reader.u16()?, reader.u16()?, reader.u16()?);
+        if qa == 0 || qb == 0 {
+            return Err(invalid_network("zero quantization factor"));
+        }
+
+        let hidden_weights = reader.weights(NNUE_INPUTS * hidden_size, quantization)?;
+        let hidden_biases = reader.weights(hidden_size, Quantization::Int16)?;
+        let output_weights = reader.weights(2 * hidden_size, quantization)?;
+        let bias = reader.take(4)?;
+        let output_bias = i32::from_le_bytes([bias[0], bias[1], bias[2], bias[3]]);
+        if !reader.bytes.is_empty() {
+            return Err(invalid_network("trailing bytes"));
+        }
+        Ok(Network {
+            hidden_size,
+            quantization,
+            hidden_weights,
+            hidden_biases,
+            output_weights,
+            output_bias,
+            qa: qa as i32,
+            qb: qb as i32,
+            scale: scale as i32,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Network, Error> {
+        Network::from_bytes(&fs::read(path)?)
+    }
+
+    /// The network at `CHESS_NNUE`, `None` when it isn't set.
+    pub fn from_env() -> Result<Option<Network>, Error> {
+        match env::var("CHESS_NNUE") {
+            Ok(path) => Network::load(path).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// The file for the network, with its weights stored as `quantization`. Storing 16-bit
+    /// weights in 8 bits fails if any of them doesn't fit.
+    pub fn to_bytes(&self, quantization: Quantization) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::with_capacity(
+            HEADER_SIZE
+                + (NNUE_INPUTS + 2) * self.hidden_size * quantization.weight_size()
+                + 2 * self.hidden_size
+                + 4,
+        );
+        bytes.extend_from_slice(NNUE_MAGIC);
+        bytes.extend_from_slice(&NNUE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[quantization.bits(), 0]);
+        bytes.extend_from_slice(&(self.hidden_size as u32).to_le_bytes());
+        for factor in [self.qa, self.qb, self.scale] {
+            bytes.extend_from_slice(&(factor as u16).to_le_bytes());
+        }
+        let write_weights = |bytes: &mut Vec<u8>, weights: &[i16], quantization| {
+            for weight in weights {
+                match quantization {
+                    Quantization::Int16 => bytes.extend_from_slice(&weight.to_le_bytes()),
+                    Quantization::Int8 => {
+                        let weight = i8::try_from(*weight).map_err(|_| {
+                            invalid_network(format!("weight {} doesn't fit 8 bits", weight))
+                        })?;
+                        bytes.push(weight as u8);
+                    }
+                }
+            }
+            Ok::<(), Error>(())
+        };
+        write_weights(&mut bytes, &self.hidden_weights, quantization)?;
+        write_weights(&mut bytes, &self.hidden_biases, Quantization::Int16)?;
+        write_weights(&mut bytes, &self.output_weights, quantization)?;
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        Ok(bytes)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, quantization: Quantization) -> Result<(), Error> {
+        fs::write(path, self.to_bytes(quantization)?)
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden_size
+    }
+
+    /// How the weights were stored in the file.
+    pub fn quantization(&self) -> Quantization {
+        self.quantization
+    }
+
+    /// Evaluates from scratch, in centipawns from white's point of view.
+    pub fn evaluate(&self, board: &Board) -> i32 {
+        let mut accumulator = Accumulator::new(self);
+        accumulator.refresh(self, board);
+        accumulator.evaluate(self, board.turn)
+    }
+
+    fn weights_of(&self, input: usize) -> &[i16] {
+        &self.hidden_weights[input * self.hidden_size..(input + 1) * self.hidden_size]
+    }
+
+    /// The output for the side to move.
+    fn output(&self, us: &[i16], them: &[i16]) -> i32 {
+        let (us_weights, them_weights) = self.output_weights.split_at(self.hidden_size);
+        let crelu = |value: i16| (value as i32).clamp(0, self.qa) as i64;
+        let sum: i64 = us
+            .iter()
+            .zip(us_weights)
+            .chain(them.iter().zip(them_weights))
+            .map(|(value, weight)| crelu(*value) * *weight as i64)
+            .sum();
+        let score =
+            (sum + self.output_bias as i64) * self.scale as i64 / (self.qa as i64 * self.qb as i64);
+        score.clamp(-MAX_NETWORK_SCORE as i64, MAX_NETWORK_SCORE as i64) as i32
+    }
+}
+
+/// The hidden layer from each side's point of view, indexed by `Color`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator {
+    values: [Vec<i16>; 2],
+}
+
+impl Accumulator {
+    fn new(network: &Network) -> Accumulator {
+        Accumulator {
+            values: [network.hidden_biases.clone(), network.hidden_biases.clone()],
+        }
+    }
+
+    fn refresh(&mut self, network: &Network, board: &Board) {
+        for values in self.values.iter_mut() {
+            values.copy_from_slice(&network.hidden_biases);
+        }
+        for (square, piece) in board.board.iter().enumerate() {
+            if let Some(piece) = piece {
+                self.update_piece(network, piece.color, piece.kind, square, true);
+            }
+        }
+    }
+
+    fn update_piece(
+        &mut self,
+        network: &Network,
+        color: Color,
+        kind: PieceKind,
+        square: usize,
+        add: bool,
+    ) {
+        for perspective in [Color::White, Color::Black] {
+            let weights = network.weights_of(input_index(perspective, color, kind, square));
+            let values = &mut self.values[perspective as usize];
+            for (value, weight) in values.iter_mut().zip(weights) {
+                *value = if add {
+                    value.wrapping_add(*weight)
+                } else {
+                    value.wrapping_sub(*weight)
+                };
+            }
+        }
+    }
+
+    /// From white's point of view.
+    fn evaluate(&self, network: &Network, turn: Color) -> i32 {
+        let them = if turn == Color::White {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let score = network.output(&self.values[turn as usize], &self.values[them as usize]);
+        if turn == Color::White {
+            score
+        } else {
+            -score
+        }
+    }
+}
+
+/// An accumulator for each ply of the line being searched. Making a move updates the child's
+/// from the parent's with only the squares the move changed, taking it back is going back to
+/// the parent's ply.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Accumulators {
+    stack: Vec<Accumulator>,
+}
+
+impl Accumulators {
+    /// Starts over at the root.
+    pub fn refresh(&mut self, network: &Network, board: &Board) {
+        self.stack.clear();
+        let mut root = Accumulator::new(network);
+        root.refresh(network, board);
+        self.stack.push(root);
+    }
+
+    /// The accumulator of `ply + 1`, starting out as a copy of the one at `ply`.
+    fn child(&mut self, network: &Network, ply: usize) -> &mut Accumulator {
+        while self.stack.len() <= ply + 1 {
+            self.stack.push(Accumulator::new(network));
+        }
+        let (parents, children) = self.stack.split_at_mut(ply + 1);
+        let accumulator = &mut children[0];
+        for (values, parent_values) in accumulator.values.iter_mut().zip(&parents[ply].values) {
+            values.copy_from_slice(parent_values);
+        }
+        accumulator
+    }
+
+    /// Sets the accumulator of `ply + 1` for `le_move` played in `parent` at `ply`, taking
+    /// `captured`. Only the squares the move changes are touched: the from and to squares, the
+    /// captured piece's, which is elsewhere for en passant, and the castling rook's.
+    pub fn update(
+        &mut self,
+        network: &Network,
+        ply: usize,
+        parent: &Board,
+        le_move: Move,
+        captured: Option<Piece>,
+    ) {
+        let piece = parent.get_piece_from_position(&le_move.from).unwrap();
+        let accumulator = self.child(network, ply);
+        accumulator.update_piece(
+            network,
+            piece.color,
+            piece.kind,
+            le_move.from.index(),
+            false,
+        );
+        if let Some(captured) = captured {
+            accumulator.update_piece(
+                network,
+                captured.color,
+                captured.kind,
+                captured.position.index(),
+                false,
+            );
+        }
+        let is_promotion =
+            piece.kind == PieceKind::P && (le_move.to.rank() == 0 || le_move.to.rank() == 7);
+        let kind = if is_promotion {
+            le_move.promotion.unwrap_or(PieceKind::Q)
+        } else {
+            piece.kind
+        };
+        accumulator.update_piece(network, piece.color, kind, le_move.to.index(), true);
+        if piece.kind == PieceKind::K && le_move.from.file().abs_diff(le_move.to.file()) == 2 {
+            let (old_rook, new_rook) = get_rook_old_and_new_castling_positions(&le_move.to);
+            accumulator.update_piece(network, piece.color, PieceKind::R, old_rook.index(), false);
+            accumulator.update_piece(network, piece.color, PieceKind::R, new_rook.index(), true);
+        }
+    }
+
+    /// Sets the accumulator of `ply + 1` for a null move, nothing changes.
+    pub fn update_null(&mut self, network: &Network, ply: usize) {
+        self.child(network, ply);
+    }
+
+    /// The evaluation at `ply` from white's point of view.
+    pub fn evaluate(&self, network: &Network, ply: usize, turn: Color) -> i32 {
+        self.stack[ply].evaluate(network, turn)
+    }
+
+    pub fn get(&self, ply: usize) -> Option<&Accumulator> {
+        self.stack.get(ply)
+    }
+}
+
+#[cfg(test)]
+mod test_nnue {
+    use crate::board::Board;
+    use crate::bot::{Accumulator, Accumulators, ChessBot, Network, Quantization, NNUE_INPUTS};
+    use crate::notation::san_to_move;
+    use crate::pieces::Color;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::Arc;
+
+    const HIDDEN_SIZE: usize = 16;
+
+    /// A random network file with 8-bit sized weights, so it can be stored either way.
+    fn random_network_bytes(seed: u64) -> Vec<u8> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut bytes = b"RCNN".to_vec();
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&[16, 0]);
+        bytes.extend_from_slice(&(HIDDEN_SIZE as u32).to_le_bytes());
+        for factor in [255u16, 64, 400] {
+            bytes.extend_from_slice(&factor.to_le_bytes());
+        }
+        for _ in 0..(NNUE_INPUTS + 1 + 2) * HIDDEN_SIZE {
+            bytes.extend_from_slice(&rng.gen_range(-100i16..=100).to_le_bytes());
+        }
+        bytes.extend_from_slice(&rng.gen_range(-1000i32..=1000).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_load_and_quantization() {
+        let bytes = random_network_bytes(1);
+        let network = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(network.hidden_size(), HIDDEN_SIZE);
+        assert_eq!(network.to_bytes(Quantization::Int16).unwrap(), bytes);
+
+        let int8 = network.to_bytes(Quantization::Int8).unwrap();
+        assert!(int8.len() < bytes.len());
+        let network8 = Network::from_bytes(&int8).unwrap();
+        assert_eq!(network8.quantization(), Quantization::Int8);
+        let board = Board::new();
+        assert_eq!(network8.evaluate(&board), network.evaluate(&board));
+
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Network::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(Network::from_bytes(b"RCNX").is_err());
+    }
+
+    #[test]
+    fn test_evaluation_is_symmetric() {
+        let network = Network::from_bytes(&random_network_bytes(2)).unwrap();
+        let board =
+            Board::from_fen("r3k2r/pp3ppp/2n5/3q4/8/2N5/PP3PPP/R2QK2R w KQkq - 0 1").unwrap();
+        let mirrored =
+            Board::from_fen("r2qk2r/pp3ppp/2n5/8/3Q4/2N5/PP3PPP/R3K2R b KQkq - 0 1").unwrap();
+        assert_eq!(network.evaluate(&board), -network.evaluate(&mirrored));
+    }
+
+    /// The accumulator of `child` from `parent`'s by every square that differs between them.
+    fn update_by_diff(
+        network: &Network,
+        accumulator: &Accumulator,
+        parent: &Board,
+        child: &Board,
+    ) -> Accumulator {
+        let mut accumulator = accumulator.clone();
+        for (square, (before, after)) in parent.board.iter().zip(&child.board).enumerate() {
+            let before = before.map(|piece| (piece.color, piece.kind));
+            let after = after.map(|piece| (piece.color, piece.kind));
+            if before == after {
+                continue;
+            }
+            if let Some((color, kind)) = before {
+                accumulator.update_piece(network, color, kind, square, false);
+            }
+            if let Some((color, kind)) = after {
+                accumulator.update_piece(network, color, kind, square, true);
+            }
+        }
+        accumulator
+    }
+
+    #[test]
+    fn test_incremental_updates_match_a_refresh() {
+        let network = Network::from_bytes(&random_network_bytes(3)).unwrap();
+        // castling, en passant and an underpromotion with capture
+        let mut board =
+            Board::from_fen("r3k2r/1P3ppp/8/3pP3/8/8/5PPP/R3K2R w KQkq d6 0 1").unwrap();
+        let mut accumulators = Accumulators::default();
+        accumulators.refresh(&network, &board);
+        for (ply, san) in ["exd6", "O-O", "O-O-O", "h6", "bxa8=N"].iter().enumerate() {
+            let le_move = san_to_move(&board, san).unwrap();
+            let child = board.try_move(le_move);
+            let captured = board.captured_piece(le_move);
+            accumulators.update(&network, ply, &board, le_move, captured);
+            debug_assert_eq!(
+                accumulators.get(ply + 1),
+                Some(&update_by_diff(
+                    &network,
+                    accumulators.get(ply).unwrap(),
+                    &board,
+                    &child
+                )),
+                "after {}",
+                san
+            );
+            board = child;
+            let mut fresh = Accumulators::default();
+            fresh.refresh(&network, &board);
+            assert_eq!(accumulators.get(ply + 1), fresh.get(0), "after {}", san);
+            assert_eq!(
+                accumulators.evaluate(&network, ply + 1, board.turn),
+                network.evaluate(&board)
+            );
+        }
+        accumulators.update_null(&network, 5);
+        assert_eq!(accumulators.get(6), accumulators.get(5));
+    }
+
+    #[test]
+    fn test_bot_searches_with_the_network() {
+        let network = Arc::new(Network::from_bytes(&random_network_bytes(4)).unwrap());
+        let board = Board::new();
+        let mut bot = ChessBot::new(Color::White, 3);
+        bot.network = Some(network);
+        let result = bot.search(&board);
+        assert!(board.generate_moves().contains(&result.le_move));
+        assert!(result.score.abs() <= 20_000);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::pieces::{Color, Piece, PieceKind};
+
+/// Phase of the starting position, every piece but the pawns and kings counts towards it.
+pub const MAX_PHASE: i32 = 24;
+
+/// How much a piece adds to the game phase.
+pub fn phase_weight(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::N | PieceKind::B => 1,
+        PieceKind::R => 2,
+        PieceKind::Q => 4,
+        PieceKind::P | PieceKind::K => 0,
+    }
+}
+
+// Piece-square tables in centipawns from white's point of view, written with the eighth rank
+// first so they look like the board. Every piece kind has one for the middlegame and one for
+// the endgame, the evaluation blends the two by the game phase.
+
+#[rustfmt::skip]
+const PAWN_MIDDLEGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const PAWN_ENDGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    15, 15, 15, 15, 15, 15, 15, 15,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_MIDDLEGAME: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const KNIGHT_ENDGAME: [i32; 64] = [
+    -40,-30,-20,-20,-20,-20,-30,-40,
+    -30,-15,  0,  0,  0,  0,-15,-30,
+    -20,  0, 10, 15, 15, 10,  0,-20,
+    -20,  0, 15, 20, 20, 15,  0,-20,
+    -20,  0, 15, 20, 20, 15,  0,-20,
+    -20,  0, 10, 15, 15, 10,  0,-20,
+    -30,-15,  0,  0,  0,  0,-15,-30,
+    -40,-30,-20,-20,-20,-20,-30,-40,
+];
+
+#[rustfmt::skip]
+const BISHOP_MIDDLEGAME: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const BISHOP_ENDGAME: [i32; 64] = [
+    -15,-10,-10,-10,-10,-10,-10,-15,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -15,-10,-10,-10,-10,-10,-10,-15,
+];
+
+#[rustfmt::skip]
+const ROOK_MIDDLEGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const ROOK_ENDGAME: [i32; 64] = [
+     5,  5,  5,  5,  5,  5,  5,  5,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_MIDDLEGAME: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const QUEEN_ENDGAME: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -10,  5, 10, 10, 10, 10,  5,-10,
+     -5,  5, 10, 15, 15, 10,  5, -5,
+     -5,  5, 10, 15, 15, 10,  5, -5,
+    -10,  5, 10, 10, 10, 10,  5,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+fn tables(kind: PieceKind) -> (&'static [i32; 64], &'static [i32; 64]) {
+    match kind {
+        PieceKind::P => (&PAWN_MIDDLEGAME, &PAWN_ENDGAME),
+        PieceKind::N => (&KNIGHT_MIDDLEGAME, &KNIGHT_ENDGAME),
+        PieceKind::B => (&BISHOP_MIDDLEGAME, &BISHOP_ENDGAME),
+        PieceKind::R => (&ROOK_MIDDLEGAME, &ROOK_ENDGAME),
+        PieceKind::Q => (&QUEEN_MIDDLEGAME, &QUEEN_ENDGAME),
+        PieceKind::K => (&KING_MIDDLEGAME, &KING_ENDGAME),
+    }
+}
+
+/// Middlegame and endgame value of the piece on its square.
+pub fn piece_square_values(piece: &Piece) -> (i32, i32) {
+    let rank = if piece.color == Color::White {
+        7 - piece.position.rank()
+    } else {
+        piece.position.rank()
+    };
+    let index = rank as usize * 8 + piece.position.file() as usize;
+    let (middlegame, endgame) = tables(piece.kind);
+    (middlegame[index], endgame[index])
+}
+
+/// Blends a middlegame and an endgame score, `phase` going from `MAX_PHASE` at the start down to
+/// 0 with only kings and pawns left.
+pub fn taper(middlegame: i32, endgame: i32, phase: i32) -> i32 {
+    let phase = phase.clamp(0, MAX_PHASE);
+    (middlegame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+#[cfg(test)]
+mod test_piece_square_tables {
+    use crate::bot::{piece_square_values, taper, MAX_PHASE};
+    use crate::helpers::Square;
+    use crate::pieces::{Color, Piece, PieceKind};
+
+    #[test]
+    fn test_tables_are_mirrored_for_black() {
+        for kind in [
+            PieceKind::P,
+            PieceKind::N,
+            PieceKind::B,
+            PieceKind::R,
+            PieceKind::Q,
+            PieceKind::K,
+        ] {
+            for square in ["a2", "c3", "e4", "g7", "h8"] {
+                let square: Square = square.parse().unwrap();
+                let mirrored = Square::from_file_rank(square.file(), 7 - square.rank());
+                assert_eq!(
+                    piece_square_values(&Piece::new(Color::White, kind, square)),
+                    piece_square_values(&Piece::new(Color::Black, kind, mirrored))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_taper() {
+        assert_eq!(taper(40, -20, MAX_PHASE), 40);
+        assert_eq!(taper(40, -20, 0), -20);
+        assert_eq!(taper(40, -20, MAX_PHASE / 2), 10);
+        // promoted pieces can push the phase over the maximum
+        assert_eq!(taper(40, -20, MAX_PHASE + 4), 40);
+    }
+}
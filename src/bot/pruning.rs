@@ -0,0 +1,150 @@
+use crate::helpers::{Move, Square};
+
+pub const NULL_MOVE_REDUCTION: u8 = 2;
+const DEEP_NULL_MOVE_DEPTH: u8 = 6;
+
+const LMR_MIN_DEPTH: u8 = 3;
+const LMR_MIN_MOVES: usize = 3;
+const DEEP_REDUCTION_MOVES: usize = 8;
+const DEEP_REDUCTION_DEPTH: u8 = 6;
+
+const FUTILITY_MARGINS: [i32; 3] = [0, 200, 350];
+
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+const REVERSE_FUTILITY_DEPTH: u8 = 3;
+
+pub const ASPIRATION_WINDOW: i32 = 50;
+pub const ASPIRATION_MIN_DEPTH: u8 = 4;
+pub const MAX_ASPIRATION_WINDOW: i32 = 1000;
+
+/// Stands for passing in the null-move search. No real move goes to its own square.
+pub fn null_move() -> Move {
+    Move::new(Square::new(0), Square::new(0))
+}
+
+pub fn is_null_move(le_move: Move) -> bool {
+    le_move.from == le_move.to
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Pruning {
+    pub null_move: bool,
+    pub late_move_reductions: bool,
+    pub futility: bool,
+    pub reverse_futility: bool,
+    pub aspiration_windows: bool,
+}
+
+impl Default for Pruning {
+    fn default() -> Pruning {
+        Pruning {
+            null_move: true,
+            late_move_reductions: true,
+            futility: true,
+            reverse_futility: true,
+            aspiration_windows: true,
+        }
+    }
+}
+
+impl Pruning {
+    pub const NAMES: [&'static str; 5] = [
+        "null-move",
+        "lmr",
+        "futility",
+        "reverse-futility",
+        "aspiration",
+    ];
+
+    pub fn none() -> Pruning {
+        Pruning {
+            null_move: false,
+            late_move_reductions: false,
+            futility: false,
+            reverse_futility: false,
+            aspiration_windows: false,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, enabled: bool) -> bool {
+        let technique = match name {
+            "null-move" => &mut self.null_move,
+            "lmr" => &mut self.late_move_reductions,
+            "futility" => &mut self.futility,
+            "reverse-futility" => &mut self.reverse_futility,
+            "aspiration" => &mut self.aspiration_windows,
+            _ => return false,
+        };
+        *technique = enabled;
+        true
+    }
+
+    pub fn null_move_depth(&self, depth_left: u8) -> Option<u8> {
+        if !self.null_move || depth_left < NULL_MOVE_REDUCTION {
+            return None;
+        }
+        let reduction = NULL_MOVE_REDUCTION + (depth_left >= DEEP_NULL_MOVE_DEPTH) as u8;
+        Some(depth_left.saturating_sub(1 + reduction))
+    }
+
+    pub fn reduction(&self, depth_left: u8, index: usize) -> u8 {
+        if !self.late_move_reductions || depth_left < LMR_MIN_DEPTH || index < LMR_MIN_MOVES {
+            return 0;
+        }
+        if index >= DEEP_REDUCTION_MOVES && depth_left >= DEEP_REDUCTION_DEPTH {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn futility_margin(&self, depth_left: u8) -> Option<i32> {
+        if !self.futility {
+            return None;
+        }
+        FUTILITY_MARGINS
+            .get(depth_left as usize)
+            .copied()
+            .filter(|_| depth_left > 0)
+    }
+
+    pub fn reverse_futility_margin(&self, depth_left: u8) -> Option<i32> {
+        (self.reverse_futility && depth_left <= REVERSE_FUTILITY_DEPTH)
+            .then_some(REVERSE_FUTILITY_MARGIN * depth_left as i32)
+    }
+
+    pub fn uses_static_eval(&self, depth_left: u8) -> bool {
+        self.null_move
+            || self.futility_margin(depth_left).is_some()
+            || self.reverse_futility_margin(depth_left).is_some()
+    }
+}
+
+#[cfg(test)]
+mod test_pruning {
+    use crate::bot::Pruning;
+
+    #[test]
+    fn test_switches_and_depths() {
+        let mut pruning = Pruning::none();
+        for name in Pruning::NAMES {
+            assert!(pruning.set(name, true));
+        }
+        assert_eq!(pruning, Pruning::default());
+        assert!(!pruning.set("razoring", true));
+
+        assert_eq!(pruning.null_move_depth(1), None);
+        assert_eq!(pruning.null_move_depth(3), Some(0));
+        assert_eq!(pruning.null_move_depth(8), Some(4));
+        assert_eq!(pruning.reduction(5, 2), 0);
+        assert_eq!(pruning.reduction(5, 3), 1);
+        assert_eq!(pruning.reduction(6, 10), 2);
+        assert_eq!(pruning.futility_margin(0), None);
+        assert_eq!(pruning.futility_margin(3), None);
+        assert_eq!(pruning.reverse_futility_margin(4), None);
+
+        let pruning = Pruning::none();
+        assert_eq!(pruning.reduction(6, 10), 0);
+        assert!(!pruning.uses_static_eval(1));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::bot::{SearchLine, MAX_DEPTH};
+
+use rand::Rng;
+use std::fmt;
+
+pub const MIN_SKILL_LEVEL: u8 = 1;
+/// Full strength, nothing is held back.
+pub const MAX_SKILL_LEVEL: u8 = 20;
+
+/// Depth limit by level, the last level searches as deep as time allows.
+const SKILL_DEPTHS: [u8; 20] = [
+    1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4, 5, 5, 5, 6, 6, 8, 10, MAX_DEPTH,
+];
+
+/// Rough strength of level 1 and what each level adds, only to give the levels a familiar scale.
+const BASE_ELO: u32 = 600;
+const ELO_PER_LEVEL: u32 = 100;
+
+/// Root moves the weaker levels choose among.
+const CANDIDATE_MOVES: usize = 4;
+
+/// How strongly the bot plays, from `MIN_SKILL_LEVEL` to `MAX_SKILL_LEVEL`. Lower levels search
+/// less deep and fewer nodes, see less than the whole evaluation, and often play a worse move
+/// than the best one, the more likely the less it loses.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct SkillLevel(u8);
+
+impl Default for SkillLevel {
+    fn default() -> SkillLevel {
+        SkillLevel(MAX_SKILL_LEVEL)
+    }
+}
+
+impl fmt::Display for SkillLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Level {} (~{} Elo)", self.0, self.elo())
+    }
+}
+
+impl SkillLevel {
+    /// Levels out of range are clamped.
+    pub fn new(level: u8) -> SkillLevel {
+        SkillLevel(level.clamp(MIN_SKILL_LEVEL, MAX_SKILL_LEVEL))
+    }
+
+    /// The level closest to the Elo rating.
+    pub fn from_elo(elo: u32) -> SkillLevel {
+        let steps = (elo.saturating_sub(BASE_ELO) + ELO_PER_LEVEL / 2) / ELO_PER_LEVEL;
+        SkillLevel::new(MIN_SKILL_LEVEL.saturating_add(steps.min(u8::MAX as u32) as u8))
+    }
+
+    pub fn level(&self) -> u8 {
+        self.0
+    }
+
+    pub fn elo(&self) -> u32 {
+        BASE_ELO + (self.0 - MIN_SKILL_LEVEL) as u32 * ELO_PER_LEVEL
+    }
+
+    pub fn is_full_strength(&self) -> bool {
+        self.0 == MAX_SKILL_LEVEL
+    }
+
+    pub fn max_depth(&self) -> u8 {
+        SKILL_DEPTHS[(self.0 - 1) as usize]
+    }
+
+    pub fn max_nodes(&self) -> Option<usize> {
+        (!self.is_full_strength()).then(|| 100 << (self.0 / 2))
+    }
+
+    /// Share of everything besides material the evaluation counts, in percent.
+    pub fn positional_weight(&self) -> i32 {
+        if self.is_full_strength() {
+            100
+        } else {
+            40 + 3 * self.0 as i32
+        }
+    }
+
+    /// Root moves the bot chooses among, searched with exact scores.
+    pub fn candidate_moves(&self) -> usize {
+        if self.is_full_strength() {
+            1
+        } else {
+            CANDIDATE_MOVES
+        }
+    }
+
+    /// Centipawns a worse move may lose and still be played a third as often as the best one.
+    fn temperature(&self) -> f64 {
+        12. * (MAX_SKILL_LEVEL - self.0) as f64
+    }
+
+    /// Picks one of the lines, best first, from the side to move's point of view: the best one
+    /// at full strength, otherwise each with a weight that falls off with what it loses on the
+    /// best one. Returns the index of the line.
+    pub fn choose_line(
+        &self,
+        lines: &[SearchLine],
+        white_to_move: bool,
+        rng: &mut impl Rng,
+    ) -> usize {
+        let temperature = self.temperature();
+        let Some(best) = lines.first() else {
+            return 0;
+        };
+        if temperature == 0. {
+            return 0;
+        }
+        let weights: Vec<f64> = lines
+            .iter()
+            .map(|line| {
+                let loss = if white_to_move {
+                    best.score as f64 - line.score as f64
+                } else {
+                    line.score as f64 - best.score as f64
+                };
+                (-loss.max(0.) / temperature).exp()
+            })
+            .collect();
+        let mut pick = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (index, weight) in weights.iter().enumerate() {
+            if pick < *weight {
+                return index;
+            }
+            pick -= weight;
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod test_skill {
+    use crate::bot::{SearchLine, SkillLevel, MAX_DEPTH, MAX_SKILL_LEVEL};
+    use crate::helpers::{Move, Square};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn line(score: i32) -> SearchLine {
+        SearchLine {
+            score,
+            pv: vec![Move::new(Square::new(8), Square::new(16))],
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(SkillLevel::new(0), SkillLevel::new(1));
+        assert_eq!(SkillLevel::new(30), SkillLevel::default());
+        assert_eq!(SkillLevel::from_elo(1240).level(), 7);
+        assert_eq!(SkillLevel::from_elo(100).level(), 1);
+        assert_eq!(SkillLevel::from_elo(4000).level(), MAX_SKILL_LEVEL);
+        assert_eq!(SkillLevel::new(7).elo(), 1200);
+
+        let full = SkillLevel::default();
+        assert_eq!(full.max_depth(), MAX_DEPTH);
+        assert_eq!(full.max_nodes(), None);
+        assert_eq!(full.positional_weight(), 100);
+        let weak = SkillLevel::new(1);
+        assert_eq!(weak.max_depth(), 1);
+        assert!(weak.max_nodes().unwrap() < SkillLevel::new(10).max_nodes().unwrap());
+        assert!(weak.positional_weight() < 50);
+    }
+
+    #[test]
+    fn test_choose_line() {
+        let mut rng = StdRng::seed_from_u64(7);
+        // black to move, lower is better for it
+        let lines = [line(-50), line(-20), line(900)];
+        let full = SkillLevel::default();
+        assert!((0..20).all(|_| full.choose_line(&lines, false, &mut rng) == 0));
+
+        let mut counts = [0; 3];
+        for _ in 0..1000 {
+            counts[SkillLevel::new(1).choose_line(&lines, false, &mut rng)] += 1;
+        }
+        assert!(counts[0] > counts[1] && counts[1] > counts[2]);
+        assert!(counts[1] > 0);
+        // a blunder of nine pawns is next to never played
+        assert!(counts[2] < 10);
+    }
+}
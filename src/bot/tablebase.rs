@@ -0,0 +1,832 @@
+use crate::board::Board;
+use crate::bot::MATE_SCORE;
+use crate::helpers::{Move, Square};
+use crate::moves::get_attackers;
+use crate::pieces::{Color, Piece, PieceKind};
+
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::mem::take;
+use std::path::{Path, PathBuf};
+
+pub const ENDGAMES: [&str; 4] = ["KQK", "KRK", "KPK", "KBNK"];
+
+pub const MAX_TABLEBASE_MEN: usize = 4;
+
+const FILE_HEADER: &str = "rust-chess tablebase";
+
+const NAME_ORDER: [PieceKind; 5] = [
+    PieceKind::Q,
+    PieceKind::R,
+    PieceKind::B,
+    PieceKind::N,
+    PieceKind::P,
+];
+
+/// Move counter of a position where the lone king can always take a piece or is stalemated,
+/// so it can never be lost.
+const ESCAPE: u8 = u8::MAX;
+
+const KING_STEPS: [(i32, i32); 8] = [
+    (0, 1),
+    (0, -1),
+    (1, 0),
+    (-1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Wdl {
+    Win,
+    Draw,
+    Loss,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TablebaseResult {
+    pub wdl: Wdl,
+    /// Plies to mate with best play from both sides, 0 for a draw.
+    pub dtm: u16,
+}
+
+impl TablebaseResult {
+    const DRAW: TablebaseResult = TablebaseResult {
+        wdl: Wdl::Draw,
+        dtm: 0,
+    };
+
+    pub fn previous_ply(&self) -> TablebaseResult {
+        match self.wdl {
+            Wdl::Win => TablebaseResult {
+                wdl: Wdl::Loss,
+                dtm: self.dtm + 1,
+            },
+            Wdl::Draw => TablebaseResult::DRAW,
+            Wdl::Loss => TablebaseResult {
+                wdl: Wdl::Win,
+                dtm: self.dtm + 1,
+            },
+        }
+    }
+
+    /// Search score from white's point of view, the mate `dtm` plies from this position.
+    pub fn score(&self, turn: Color) -> i32 {
+        let score = match self.wdl {
+            Wdl::Win => MATE_SCORE - self.dtm as i32,
+            Wdl::Draw => 0,
+            Wdl::Loss => -(MATE_SCORE - self.dtm as i32),
+        };
+        if turn == Color::White {
+            score
+        } else {
+            -score
+        }
+    }
+
+    fn preference(&self) -> i32 {
+        match self.wdl {
+            Wdl::Win => 1000 - self.dtm as i32,
+            Wdl::Draw => 0,
+            Wdl::Loss => self.dtm as i32 - 1000,
+        }
+    }
+}
+
+fn invalid_endgame(name: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("No tablebase for {}, only a king and one or two pieces against a lone king where taking any of them draws", name),
+    )
+}
+
+/// The pieces besides the king of the stronger side, e.g. bishop and knight for "KBNK". The lone
+/// king taking any of them has to leave a draw, so that the tables don't need each other except
+/// for promotions.
+fn parse_endgame(name: &str) -> Result<Vec<PieceKind>, Error> {
+    let inner = name
+        .strip_prefix('K')
+        .and_then(|rest| rest.strip_suffix('K'))
+        .ok_or_else(|| invalid_endgame(name))?;
+    let pieces: Vec<PieceKind> = inner
+        .chars()
+        .map(|c| match c {
+            'Q' => Ok(PieceKind::Q),
+            'R' => Ok(PieceKind::R),
+            'B' => Ok(PieceKind::B),
+            'N' => Ok(PieceKind::N),
+            'P' => Ok(PieceKind::P),
+            _ => Err(invalid_endgame(name)),
+        })
+        .collect::<Result<_, _>>()?;
+    let is_minor = |kind: &PieceKind| [PieceKind::B, PieceKind::N].contains(kind);
+    let is_supported = match pieces[..] {
+        [_] => true,
+        [first, second] => is_minor(&first) && is_minor(&second),
+        _ => false,
+    };
+    if !is_supported || endgame_name(&pieces) != name {
+        return Err(invalid_endgame(name));
+    }
+    Ok(pieces)
+}
+
+fn endgame_name(pieces: &[PieceKind]) -> String {
+    let mut pieces = pieces.to_vec();
+    pieces.sort_by_key(|kind| NAME_ORDER.iter().position(|k| k == kind));
+    let chars: String = pieces
+        .iter()
+        .map(|kind| Piece::new(Color::White, *kind, Square::new(0)).get_piece_kind_as_char())
+        .collect();
+    format!("K{}K", chars)
+}
+
+fn king_neighbours(square: usize) -> impl Iterator<Item = usize> {
+    let square = Square::new(square as u8);
+    KING_STEPS
+        .into_iter()
+        .filter_map(move |(x, y)| square.offset(x, y))
+        .map(|square| square.index())
+}
+
+fn are_adjacent(a: usize, b: usize) -> bool {
+    (a % 8).abs_diff(b % 8) <= 1 && (a / 8).abs_diff(b / 8) <= 1
+}
+
+/// How positions are numbered: the side to move, the white king, the black king and the white
+/// pieces, white always being the side with the pieces. Mirroring the board doesn't change the
+/// result, so the white king is kept on the a to d files, and without pawns on the first four
+/// ranks too.
+#[derive(Clone, Copy, Debug)]
+struct Layout {
+    n_pieces: usize,
+    has_pawns: bool,
+}
+
+impl Layout {
+    fn new(pieces: &[PieceKind]) -> Layout {
+        Layout {
+            n_pieces: pieces.len(),
+            has_pawns: pieces.contains(&PieceKind::P),
+        }
+    }
+
+    fn king_slots(&self) -> usize {
+        if self.has_pawns {
+            32
+        } else {
+            16
+        }
+    }
+
+    fn n_configs(&self) -> usize {
+        self.king_slots() * 64usize.pow(self.n_pieces as u32)
+    }
+
+    fn size(&self) -> usize {
+        2 * 64 * self.n_configs()
+    }
+
+    fn squares<'a>(&self, squares: &'a [usize; MAX_TABLEBASE_MEN]) -> &'a [usize] {
+        &squares[..2 + self.n_pieces]
+    }
+
+    fn canonical(&self, squares: &mut [usize; MAX_TABLEBASE_MEN]) {
+        let mut flip = 0;
+        if squares[0] % 8 > 3 {
+            flip ^= 7;
+        }
+        if !self.has_pawns && squares[0] / 8 > 3 {
+            flip ^= 56;
+        }
+        for square in squares[..2 + self.n_pieces].iter_mut() {
+            *square ^= flip;
+        }
+    }
+
+    fn king_slot(&self, square: usize) -> usize {
+        square / 8 * 4 + square % 8
+    }
+
+    fn config_index(&self, squares: &[usize; MAX_TABLEBASE_MEN]) -> usize {
+        let mut index = self.king_slot(squares[0]);
+        for square in &squares[2..2 + self.n_pieces] {
+            index = index * 64 + square;
+        }
+        index
+    }
+
+    fn index(&self, black_to_move: bool, squares: &[usize; MAX_TABLEBASE_MEN]) -> usize {
+        let config = self.config_index(squares);
+        let pieces_size = 64usize.pow(self.n_pieces as u32);
+        let (king_slot, pieces) = (config / pieces_size, config % pieces_size);
+        ((black_to_move as usize * self.king_slots() + king_slot) * 64 + squares[1]) * pieces_size
+            + pieces
+    }
+
+    fn decode(&self, mut index: usize) -> (bool, [usize; MAX_TABLEBASE_MEN]) {
+        let mut squares = [0; MAX_TABLEBASE_MEN];
+        for i in (2..2 + self.n_pieces).rev() {
+            squares[i] = index % 64;
+            index /= 64;
+        }
+        squares[1] = index % 64;
+        index /= 64;
+        let king_slot = index % self.king_slots();
+        squares[0] = king_slot / 4 * 8 + king_slot % 4;
+        (index >= self.king_slots(), squares)
+    }
+
+    fn decode_config(&self, mut config: usize) -> [usize; MAX_TABLEBASE_MEN] {
+        let mut squares = [0; MAX_TABLEBASE_MEN];
+        for i in (2..2 + self.n_pieces).rev() {
+            squares[i] = config % 64;
+            config /= 64;
+        }
+        squares[0] = config / 4 * 8 + config % 4;
+        squares
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tablebase {
+    name: String,
+    pieces: Vec<PieceKind>,
+    /// Plies to mate plus one by position index, 0 for draws and impossible positions.
+    values: Vec<u8>,
+}
+
+struct Generator<'a> {
+    layout: Layout,
+    pieces: &'a [PieceKind],
+    attacks: Vec<u64>,
+    empty_board: Board,
+}
+
+impl<'a> Generator<'a> {
+    fn new(pieces: &'a [PieceKind]) -> Generator<'a> {
+        let layout = Layout::new(pieces);
+        let mut generator = Generator {
+            layout,
+            pieces,
+            attacks: vec![0; layout.n_configs()],
+            empty_board: Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap(),
+        };
+        for config in 0..layout.n_configs() {
+            let squares = layout.decode_config(config);
+            if generator.is_placeable(&squares) {
+                generator.attacks[config] = generator.attack_map(&squares);
+            }
+        }
+        generator
+    }
+
+    fn white_men(&self, squares: &[usize; MAX_TABLEBASE_MEN]) -> Vec<Piece> {
+        let mut men = vec![Piece::new(
+            Color::White,
+            PieceKind::K,
+            Square::new(squares[0] as u8),
+        )];
+        for (kind, square) in self.pieces.iter().zip(&squares[2..]) {
+            men.push(Piece::new(Color::White, *kind, Square::new(*square as u8)));
+        }
+        men
+    }
+
+    fn attack_map(&self, squares: &[usize; MAX_TABLEBASE_MEN]) -> u64 {
+        let men = self.white_men(squares);
+        let piece_at = |square: Square| men.iter().find(|man| man.position == square).copied();
+        Square::all()
+            .filter(|target| !get_attackers(*target, Color::White, piece_at).is_empty())
+            .fold(0, |attacks, target| attacks | 1 << target.index())
+    }
+
+    fn is_placeable(&self, squares: &[usize; MAX_TABLEBASE_MEN]) -> bool {
+        let white: Vec<usize> = [squares[0]]
+            .into_iter()
+            .chain(squares[2..2 + self.layout.n_pieces].iter().copied())
+            .collect();
+        let distinct = (1..white.len()).all(|i| !white[..i].contains(&white[i]));
+        let pawns_ok = self
+            .pieces
+            .iter()
+            .zip(&squares[2..])
+            .all(|(kind, square)| *kind != PieceKind::P || (1..7).contains(&(square / 8)));
+        distinct && pawns_ok
+    }
+
+    fn attacked(&self, squares: &[usize; MAX_TABLEBASE_MEN]) -> u64 {
+        self.attacks[self.layout.config_index(squares)]
+    }
+
+    fn is_legal(&self, black_to_move: bool, squares: &[usize; MAX_TABLEBASE_MEN]) -> bool {
+        let n = 2 + self.layout.n_pieces;
+        self.is_placeable(squares)
+            && !squares[2..n].contains(&squares[1])
+            && !are_adjacent(squares[0], squares[1])
+            && (black_to_move || self.attacked(squares) & 1 << squares[1] == 0)
+    }
+
+    fn is_empty(&self, squares: &[usize; MAX_TABLEBASE_MEN], square: usize) -> bool {
+        !self.layout.squares(squares).contains(&square)
+    }
+
+    fn white_unmoves(&self, squares: &[usize; MAX_TABLEBASE_MEN]) -> Vec<(usize, usize)> {
+        let men = self.white_men(squares);
+        let friendly: Vec<Square> = men.iter().map(|man| man.position).collect();
+        let black_king = [Square::new(squares[1] as u8)];
+        let mut unmoves = Vec::new();
+        for (i, man) in men.iter().enumerate() {
+            let slot = if i == 0 { 0 } else { i + 1 };
+            let from = man.position.index();
+            if man.kind == PieceKind::P {
+                let rank = from / 8;
+                if rank >= 2 && self.is_empty(squares, from - 8) {
+                    unmoves.push((slot, from - 8));
+                    if rank == 3 && self.is_empty(squares, from - 16) {
+                        unmoves.push((slot, from - 16));
+                    }
+                }
+                continue;
+            }
+            for origin in man.get_pseudo_legal_moves(&friendly, &black_king, &self.empty_board) {
+                if origin != black_king[0] {
+                    unmoves.push((slot, origin.index()));
+                }
+            }
+        }
+        unmoves
+    }
+
+    fn board(&self, black_to_move: bool, squares: &[usize; MAX_TABLEBASE_MEN]) -> Board {
+        let mut placement = ['1'; 64];
+        placement[squares[0]] = 'K';
+        placement[squares[1]] = 'k';
+        for man in &self.white_men(squares)[1..] {
+            placement[man.position.index()] = man.get_piece_kind_as_char();
+        }
+        let ranks: Vec<String> = placement
+            .chunks(8)
+            .rev()
+            .map(|rank| rank.iter().collect())
+            .collect();
+        let turn = if black_to_move { 'b' } else { 'w' };
+        Board::from_fen(&format!("{} {} - - 0 1", ranks.join("/"), turn)).unwrap()
+    }
+}
+
+impl Tablebase {
+    pub fn generate(name: &str, tablebases: &Tablebases) -> Result<Tablebase, Error> {
+        let pieces = parse_endgame(name)?;
+        let generator = Generator::new(&pieces);
+        let layout = generator.layout;
+        let half = layout.size() / 2;
+        let mut values = vec![0u8; layout.size()];
+        // moves of the black positions that aren't known to lose yet
+        let mut counts = vec![ESCAPE; half];
+        let mut layers: Vec<Vec<usize>> = vec![Vec::new()];
+
+        for (index, count) in counts.iter_mut().enumerate() {
+            let (_, squares) = layout.decode(half + index);
+            if !generator.is_legal(true, &squares) {
+                continue;
+            }
+            let attacked = generator.attacked(&squares);
+            let mut n_moves = 0;
+            for to in king_neighbours(squares[1]) {
+                if attacked & 1 << to != 0 {
+                    continue;
+                }
+                if !generator.is_empty(&squares, to) {
+                    // taking a piece leaves a draw
+                    n_moves = ESCAPE;
+                    break;
+                }
+                n_moves += 1;
+            }
+            if n_moves == 0 && attacked & 1 << squares[1] != 0 {
+                values[half + index] = 1;
+                layers[0].push(half + index);
+            }
+            if n_moves > 0 {
+                *count = n_moves;
+            }
+        }
+
+        // promotions lead into other tables, and win in however many plies those say
+        let mut seeds: Vec<Vec<usize>> = Vec::new();
+        if layout.has_pawns {
+            for index in 0..half {
+                let (_, squares) = layout.decode(index);
+                let on_seventh = pieces
+                    .iter()
+                    .zip(&squares[2..])
+                    .any(|(kind, square)| *kind == PieceKind::P && square / 8 == 6);
+                if !on_seventh || !generator.is_legal(false, &squares) {
+                    continue;
+                }
+                let board = generator.board(false, &squares);
+                let mut fastest = None;
+                for le_move in board.generate_moves() {
+                    if le_move.promotion.is_none() {
+                        continue;
+                    }
+                    let result = tablebases
+                        .probe(&board.try_move(le_move))
+                        .ok_or_else(|| {
+                            Error::new(
+                                ErrorKind::NotFound,
+                                format!("{} needs the tables it promotes into", name),
+                            )
+                        })?
+                        .previous_ply();
+                    if result.wdl == Wdl::Win {
+                        fastest = Some(fastest.unwrap_or(u16::MAX).min(result.dtm));
+                    }
+                }
+                if let Some(dtm) = fastest {
+                    let dtm = dtm as usize;
+                    if seeds.len() <= dtm {
+                        seeds.resize(dtm + 1, Vec::new());
+                    }
+                    seeds[dtm].push(index);
+                }
+            }
+        }
+
+        let mut dtm = 0;
+        while dtm < layers.len() || dtm < seeds.len() {
+            if dtm + 2 > u8::MAX as usize {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Mates too long to store",
+                ));
+            }
+            let mut layer = take(layers.get_mut(dtm).unwrap_or(&mut Vec::new()));
+            for index in seeds.get(dtm).into_iter().flatten() {
+                if values[*index] == 0 {
+                    values[*index] = dtm as u8 + 1;
+                    layer.push(*index);
+                }
+            }
+
+            let mut next = Vec::new();
+            for index in layer {
+                let (black_to_move, squares) = layout.decode(index);
+                if black_to_move {
+                    // lost for black: every white move into it wins
+                    for (slot, origin) in generator.white_unmoves(&squares) {
+                        let mut previous = squares;
+                        previous[slot] = origin;
+                        layout.canonical(&mut previous);
+                        if !generator.is_legal(false, &previous) {
+                            continue;
+                        }
+                        let previous = layout.index(false, &previous);
+                        if values[previous] == 0 {
+                            values[previous] = dtm as u8 + 2;
+                            next.push(previous);
+                        }
+                    }
+                } else {
+                    // won for white: one move less for black to get away with
+                    for origin in king_neighbours(squares[1]) {
+                        if !generator.is_empty(&squares, origin) {
+                            continue;
+                        }
+                        let mut previous = squares;
+                        previous[1] = origin;
+                        layout.canonical(&mut previous);
+                        if !generator.is_legal(true, &previous) {
+                            continue;
+                        }
+                        let previous = layout.index(true, &previous);
+                        let count = &mut counts[previous - half];
+                        if values[previous] == 0 && *count != ESCAPE {
+                            *count -= 1;
+                            if *count == 0 {
+                                values[previous] = dtm as u8 + 2;
+                                next.push(previous);
+                            }
+                        }
+                    }
+                }
+            }
+            if !next.is_empty() {
+                if layers.len() <= dtm + 1 {
+                    layers.resize(dtm + 2, Vec::new());
+                }
+                layers[dtm + 1].extend(next);
+            }
+            dtm += 1;
+        }
+
+        Ok(Tablebase {
+            name: endgame_name(&pieces),
+            pieces,
+            values,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn n_wins(&self) -> usize {
+        self.values[..self.values.len() / 2]
+            .iter()
+            .filter(|value| **value > 0)
+            .count()
+    }
+
+    pub fn longest_mate(&self) -> u16 {
+        self.values[..self.values.len() / 2]
+            .iter()
+            .max()
+            .map_or(0, |value| *value as u16)
+            .saturating_sub(1)
+    }
+
+    fn probe_squares(
+        &self,
+        black_to_move: bool,
+        mut squares: [usize; MAX_TABLEBASE_MEN],
+    ) -> TablebaseResult {
+        let layout = Layout::new(&self.pieces);
+        layout.canonical(&mut squares);
+        match self.values[layout.index(black_to_move, &squares)] {
+            0 => TablebaseResult::DRAW,
+            value => TablebaseResult {
+                wdl: if black_to_move { Wdl::Loss } else { Wdl::Win },
+                dtm: value as u16 - 1,
+            },
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut bytes =
+            format!("{} {} {}\n", FILE_HEADER, self.name, self.values.len()).into_bytes();
+        bytes.extend(&self.values);
+        fs::write(path, bytes)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Tablebase, Error> {
+        let bytes = fs::read(path)?;
+        let invalid = || Error::new(ErrorKind::InvalidData, "Not a tablebase file");
+        let header_end = bytes.iter().position(|b| *b == b'\n').ok_or_else(invalid)?;
+        let header = String::from_utf8_lossy(&bytes[..header_end]);
+        let name = header
+            .strip_prefix(FILE_HEADER)
+            .and_then(|rest| rest.split_whitespace().next())
+            .ok_or_else(invalid)?;
+        let pieces = parse_endgame(name)?;
+        let values = bytes[header_end + 1..].to_vec();
+        if values.len() != Layout::new(&pieces).size() {
+            return Err(invalid());
+        }
+        Ok(Tablebase {
+            name: name.to_owned(),
+            pieces,
+            values,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tablebases {
+    tables: Vec<Tablebase>,
+}
+
+impl Tablebases {
+    pub fn new() -> Tablebases {
+        Tablebases::default()
+    }
+
+    pub fn generate(names: &[&str]) -> Result<Tablebases, Error> {
+        let mut tablebases = Tablebases::new();
+        for name in names {
+            let table = Tablebase::generate(name, &tablebases)?;
+            tablebases.add(table);
+        }
+        Ok(tablebases)
+    }
+
+    pub fn load(dir: impl AsRef<Path>) -> Result<Tablebases, Error> {
+        let mut tablebases = Tablebases::new();
+        for name in ENDGAMES {
+            match Tablebase::load(dir.as_ref().join(format!("{}.tb", name))) {
+                Ok(table) => tablebases.add(table),
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(tablebases)
+    }
+
+    pub fn load_or_generate(dir: impl AsRef<Path>) -> Result<Tablebases, Error> {
+        let dir = dir.as_ref();
+        let mut tablebases = Tablebases::new();
+        for name in ENDGAMES {
+            let path = dir.join(format!("{}.tb", name));
+            let table = match Tablebase::load(&path) {
+                Ok(table) => table,
+                Err(error) if error.kind() == ErrorKind::NotFound => {
+                    println!("Generating the {} tablebase", name);
+                    let table = Tablebase::generate(name, &tablebases)?;
+                    fs::create_dir_all(dir)?;
+                    table.save(&path)?;
+                    table
+                }
+                Err(error) => return Err(error),
+            };
+            tablebases.add(table);
+        }
+        Ok(tablebases)
+    }
+
+    pub fn dir_from_env() -> Option<PathBuf> {
+        env::var_os("CHESS_TABLEBASES").map(PathBuf::from)
+    }
+
+    pub fn from_env() -> Result<Tablebases, Error> {
+        Tablebases::load(Tablebases::dir_from_env().unwrap_or(PathBuf::from("./tablebases")))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        ENDGAMES.iter().all(|name| self.get(name).is_some())
+    }
+
+    pub fn add(&mut self, table: Tablebase) {
+        self.tables.retain(|t| t.name != table.name);
+        self.tables.push(table);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Tablebase> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+
+    pub fn tables(&self) -> &[Tablebase] {
+        &self.tables
+    }
+
+    /// The result for the side to move, `None` if no table has the position. Positions where
+    /// nobody can mate any more are draws without a table; castling rights aren't in the tables.
+    pub fn probe(&self, board: &Board) -> Option<TablebaseResult> {
+        if board.board.iter().flatten().count() > MAX_TABLEBASE_MEN {
+            return None;
+        }
+        if !board.has_mating_material(Color::White) && !board.has_mating_material(Color::Black) {
+            return Some(TablebaseResult::DRAW);
+        }
+        if board.castling.values().flatten().any(|right| *right) {
+            return None;
+        }
+        let [white, black] = board.get_pieces();
+        let (strong, mut men) = match (white.len(), black.len()) {
+            (_, 1) => (Color::White, white),
+            (1, _) => (Color::Black, black),
+            _ => return None,
+        };
+        men.retain(|man| man.kind != PieceKind::K);
+        men.sort_by_key(|man| NAME_ORDER.iter().position(|kind| *kind == man.kind));
+        let kinds: Vec<PieceKind> = men.iter().map(|man| man.kind).collect();
+        let table = self.get(&endgame_name(&kinds))?;
+
+        // the tables have the pieces on white's side
+        let (flip, weak) = if strong == Color::White {
+            (0, Color::Black)
+        } else {
+            (56, Color::White)
+        };
+        let mut squares = [0; MAX_TABLEBASE_MEN];
+        squares[0] = board.king_positions[&strong].index() ^ flip;
+        squares[1] = board.king_positions[&weak].index() ^ flip;
+        for (i, man) in men.iter().enumerate() {
+            squares[2 + i] = man.position.index() ^ flip;
+        }
+        Some(table.probe_squares(board.turn != strong, squares))
+    }
+
+    pub fn rank_moves(&self, board: &Board) -> Option<Vec<(Move, TablebaseResult)>> {
+        self.probe(board)?;
+        let mut moves = Vec::new();
+        for le_move in board.generate_moves() {
+            let result = self.probe(&board.try_move(le_move))?.previous_ply();
+            moves.push((le_move, result));
+        }
+        moves.sort_by_key(|(_, result)| -result.preference());
+        Some(moves)
+    }
+
+    pub fn best_move(&self, board: &Board) -> Option<(Move, TablebaseResult)> {
+        self.rank_moves(board)?.first().copied()
+    }
+}
+
+#[cfg(test)]
+mod test_tablebase {
+    use crate::board::Board;
+    use crate::bot::{Tablebase, Tablebases, Wdl};
+    use std::sync::OnceLock;
+
+    fn tablebases() -> &'static Tablebases {
+        static TABLEBASES: OnceLock<Tablebases> = OnceLock::new();
+        TABLEBASES.get_or_init(|| Tablebases::generate(&["KQK", "KRK", "KPK"]).unwrap())
+    }
+
+    fn probe(fen: &str) -> (Wdl, u16) {
+        let result = tablebases().probe(&Board::from_fen(fen).unwrap()).unwrap();
+        (result.wdl, result.dtm)
+    }
+
+    #[test]
+    fn test_longest_mates() {
+        // mate in 10 and 16 moves at most
+        assert_eq!(tablebases().get("KQK").unwrap().longest_mate(), 19);
+        assert_eq!(tablebases().get("KRK").unwrap().longest_mate(), 31);
+    }
+
+    #[test]
+    fn test_probe() {
+        assert_eq!(probe("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1"), (Wdl::Loss, 0));
+        assert_eq!(probe("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));
+        // the black side has the queen, the result is the same
+        assert_eq!(probe("8/8/8/8/8/1k6/1q6/K7 w - - 0 1"), (Wdl::Loss, 0));
+        // the king takes the undefended queen
+        assert_eq!(probe("8/8/8/8/8/8/1q6/K6k w - - 0 1"), (Wdl::Draw, 0));
+        // nobody can mate
+        assert_eq!(probe("8/8/8/3k4/8/8/8/2BK4 w - - 0 1"), (Wdl::Draw, 0));
+
+        // the king in front of its pawn on the sixth rank wins, the rook pawn doesn't
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1").0, Wdl::Loss);
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").0, Wdl::Win);
+        assert_eq!(probe("k7/8/K7/P7/8/8/8/8 w - - 0 1").0, Wdl::Draw);
+        assert!(tablebases()
+            .probe(&Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_results_follow_the_moves() {
+        for fen in [
+            "8/8/3k4/8/8/8/8/R3K3 w - - 0 1",
+            "8/8/3k4/8/8/8/8/R3K3 b - - 0 1",
+            "8/8/8/2k5/8/8/2P5/2K5 w - - 0 1",
+            "8/8/8/8/1k6/8/5Q2/7K b - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let expected = tablebases().probe(&board).unwrap();
+            let (_, best) = tablebases().best_move(&board).unwrap();
+            assert_eq!(best, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_best_moves_mate() {
+        let mut board = Board::from_fen("8/8/8/2k5/8/8/8/K6Q w - - 0 1").unwrap();
+        let (_, result) = tablebases().best_move(&board).unwrap();
+        assert_eq!(result.wdl, Wdl::Win);
+        for _ in 0..result.dtm {
+            let (le_move, _) = tablebases()
+                .best_move(&board)
+                .unwrap_or_else(|| panic!("{}", board.to_fen()));
+            board = board.try_move(le_move);
+        }
+        assert!(board.generate_moves().is_empty());
+        assert!(board.is_king_in_check(&board.turn));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let table = tablebases().get("KQK").unwrap();
+        let path = std::env::temp_dir().join(format!("rust-chess-{}.tb", std::process::id()));
+        table.save(&path).unwrap();
+        let loaded = Tablebase::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&loaded, table);
+        assert!(Tablebase::generate("KQRK", &Tablebases::new()).is_err());
+    }
+
+    #[test]
+    fn test_load_skips_missing_tables() {
+        let dir = std::env::temp_dir().join(format!("rust-chess-tbs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        tablebases()
+            .get("KRK")
+            .unwrap()
+            .save(dir.join("KRK.tb"))
+            .unwrap();
+        let loaded = Tablebases::load(&dir).unwrap();
+        let n_files = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        // only the table that was there, and nothing generated or written
+        assert_eq!(n_files, 1);
+        assert_eq!(loaded.tables().len(), 1);
+        assert!(loaded.get("KRK").is_some());
+        assert!(!loaded.is_complete());
+    }
+}
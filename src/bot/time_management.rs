@@ -0,0 +1,200 @@
+use crate::clock::Clock;
+use crate::pieces::Color;
+
+use std::time::{Duration, Instant};
+
+/// Deepest the search goes when only time limits it.
+pub const MAX_DEPTH: u8 = 64;
+
+/// Moves the remaining time is shared over when the time control doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Kept back on every move for the time it takes to get the move on the board.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+
+/// What the search may spend, like the UCI `go` command. Without any limit it searches to
+/// `MAX_DEPTH`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    /// Exactly this long, unless the search runs out of depth first.
+    pub move_time: Option<Duration>,
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Duration,
+    pub black_increment: Duration,
+    pub moves_to_go: Option<u16>,
+    /// Stops at this many nodes, like the hard time limit.
+    pub nodes: Option<usize>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u8) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn move_time(move_time: Duration) -> SearchLimits {
+        SearchLimits {
+            move_time: Some(move_time),
+            ..Default::default()
+        }
+    }
+
+    /// Limits from what is left on the clock for the player to move.
+    pub fn from_clock(clock: &Clock, color: Color) -> SearchLimits {
+        SearchLimits {
+            white_time: Some(clock.remaining(Color::White)),
+            black_time: Some(clock.remaining(Color::Black)),
+            white_increment: clock.increment(Color::White),
+            black_increment: clock.increment(Color::Black),
+            moves_to_go: clock.moves_to_go(color),
+            ..Default::default()
+        }
+    }
+}
+
+/// Decides when iterative deepening stops. No new depth is started after the soft limit, and a
+/// running one is abandoned at the hard limit. The soft limit shrinks while the best move stays
+/// the same and grows when it keeps changing.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    stable_iterations: u32,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, color: Color) -> TimeManager {
+        let (soft_limit, hard_limit) = TimeManager::limits(limits, color);
+        TimeManager {
+            start: Instant::now(),
+            soft_limit,
+            hard_limit,
+            stable_iterations: 0,
+        }
+    }
+
+    fn limits(limits: &SearchLimits, color: Color) -> (Option<Duration>, Option<Duration>) {
+        // a fixed move time is used up whatever the best move does
+        if let Some(move_time) = limits.move_time {
+            return (None, Some(move_time.saturating_sub(MOVE_OVERHEAD)));
+        }
+        let (time, increment) = match color {
+            Color::White => (limits.white_time, limits.white_increment),
+            Color::Black => (limits.black_time, limits.black_increment),
+        };
+        let time = match time {
+            Some(time) => time.saturating_sub(MOVE_OVERHEAD),
+            None => return (None, None),
+        };
+        let moves_to_go = limits
+            .moves_to_go
+            .map(|moves| moves.max(1) as u32)
+            .unwrap_or(DEFAULT_MOVES_TO_GO);
+
+        let soft_limit = (time / moves_to_go + increment * 3 / 4).min(time / 2);
+        let hard_limit = (soft_limit * 4).min(time / 2).max(soft_limit);
+        (Some(soft_limit), Some(hard_limit))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn is_hard_limit_reached(&self) -> bool {
+        self.hard_limit
+            .is_some_and(|hard_limit| self.elapsed() >= hard_limit)
+    }
+
+    /// Called after every finished depth with whether its best move differs from the previous
+    /// depth's one.
+    pub fn should_stop(&mut self, best_move_changed: bool) -> bool {
+        if best_move_changed {
+            self.stable_iterations = 0;
+        } else {
+            self.stable_iterations += 1;
+        }
+        let soft_limit = match self.soft_limit {
+            Some(soft_limit) => soft_limit,
+            None => return false,
+        };
+        let scale = if best_move_changed {
+            1.25
+        } else {
+            (1. - 0.15 * self.stable_iterations as f32).max(0.5)
+        };
+        self.elapsed() >= soft_limit.mul_f32(scale)
+    }
+}
+
+#[cfg(test)]
+mod test_time_management {
+    use crate::bot::{SearchLimits, TimeManager};
+    use crate::clock::{Clock, TimeControl};
+    use crate::pieces::Color;
+    use std::time::Duration;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = SearchLimits {
+            white_time: Some(secs(300)),
+            black_time: Some(secs(10)),
+            white_increment: secs(4),
+            ..Default::default()
+        };
+        let (soft, hard) = TimeManager::limits(&limits, Color::White);
+        let time = secs(300) - Duration::from_millis(30);
+        assert_eq!(soft, Some(time / 30 + secs(3)));
+        assert_eq!(hard, Some(soft.unwrap() * 4));
+
+        // never more than half of what is left
+        let (soft, hard) = TimeManager::limits(&limits, Color::Black);
+        assert!(hard.unwrap() <= secs(5));
+        assert!(soft <= hard);
+
+        let (soft, hard) = TimeManager::limits(&SearchLimits::move_time(secs(2)), Color::White);
+        assert_eq!(soft, None);
+        assert_eq!(hard, Some(secs(2) - Duration::from_millis(30)));
+        assert_eq!(
+            TimeManager::limits(&SearchLimits::depth(3), Color::White),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn test_limits_from_clock() {
+        let mut clock = Clock::new("40/400".parse::<TimeControl>().unwrap());
+        clock.record_move(secs(100));
+        let limits = SearchLimits::from_clock(&clock, Color::White);
+        assert_eq!(limits.white_time, Some(secs(300)));
+        assert_eq!(limits.black_time, Some(secs(400)));
+        assert_eq!(limits.moves_to_go, Some(39));
+    }
+
+    #[test]
+    fn test_should_stop() {
+        let limits = |time: u64| SearchLimits {
+            white_time: Some(secs(time)),
+            ..Default::default()
+        };
+        let mut time_manager = TimeManager::new(&limits(600), Color::White);
+        assert!(!time_manager.should_stop(true));
+        assert!(!time_manager.should_stop(false));
+        assert!(!time_manager.is_hard_limit_reached());
+        time_manager.soft_limit = Some(Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(time_manager.should_stop(false));
+
+        let mut time_manager = TimeManager::new(&limits(0), Color::White);
+        assert!(time_manager.should_stop(true));
+        assert!(time_manager.is_hard_limit_reached());
+    }
+}
@@ -0,0 +1,303 @@
+use crate::helpers::{Move, Square};
+use crate::pieces::PieceKind;
+
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+pub const DEFAULT_HASH_SIZE_MB: usize = 16;
+
+/// What the stored score says about the real one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The real score is at least this, the search failed high.
+    Lower,
+    /// The real score is at most this, the search failed low.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableEntry {
+    pub key: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+    /// Search the entry was written in.
+    pub age: u8,
+}
+
+impl TableEntry {
+    /// Score that ends the search of a node with the window, if the entry is deep enough.
+    pub fn cutoff(&self, depth: u8, alpha: i32, beta: i32) -> Option<i32> {
+        if self.depth < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score.clamp(alpha, beta)),
+            Bound::Lower if self.score >= beta => Some(beta),
+            Bound::Upper if self.score <= alpha => Some(alpha),
+            _ => None,
+        }
+    }
+}
+
+const SCORE_BITS: u32 = 24;
+const SCORE_LIMIT: i32 = (1 << (SCORE_BITS - 1)) - 1;
+const OCCUPIED: u64 = 1 << 57;
+
+fn encode_move(le_move: Option<Move>) -> u64 {
+    let le_move = match le_move {
+        Some(le_move) => le_move,
+        None => return 0,
+    };
+    let promotion = match le_move.promotion {
+        None => 0,
+        Some(PieceKind::N) => 1,
+        Some(PieceKind::B) => 2,
+        Some(PieceKind::R) => 3,
+        Some(_) => 4,
+    };
+    le_move.from.index() as u64 | (le_move.to.index() as u64) << 6 | promotion << 12
+}
+
+/// A from and to square of 0 stands for no move, no real move goes from a1 to a1.
+fn decode_move(bits: u64) -> Option<Move> {
+    if bits == 0 {
+        return None;
+    }
+    let from = Square::new((bits & 63) as u8);
+    let to = Square::new((bits >> 6 & 63) as u8);
+    Some(match bits >> 12 & 7 {
+        1 => Move::new_promotion(from, to, PieceKind::N),
+        2 => Move::new_promotion(from, to, PieceKind::B),
+        3 => Move::new_promotion(from, to, PieceKind::R),
+        4 => Move::new_promotion(from, to, PieceKind::Q),
+        _ => Move::new(from, to),
+    })
+}
+
+impl TableEntry {
+    /// Everything but the key in one word: score, depth, bound, age and move.
+    fn pack(&self) -> u64 {
+        let score =
+            self.score.clamp(-SCORE_LIMIT, SCORE_LIMIT) as u32 as u64 & ((1 << SCORE_BITS) - 1);
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        score
+            | (self.depth as u64) << 24
+            | bound << 32
+            | (self.age as u64) << 34
+            | encode_move(self.best_move) << 42
+            | OCCUPIED
+    }
+
+    fn unpack(key: u64, data: u64) -> TableEntry {
+        // shift the score up to the sign bit and back to restore negative scores
+        let score = ((data as u32) << (32 - SCORE_BITS)) as i32 >> (32 - SCORE_BITS);
+        TableEntry {
+            key,
+            depth: (data >> 24) as u8,
+            score,
+            bound: match data >> 32 & 3 {
+                0 => Bound::Exact,
+                1 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+            best_move: decode_move(data >> 42 & 0x7FFF),
+            age: (data >> 34) as u8,
+        }
+    }
+}
+
+/// One entry, stored as the key xor the data next to the data. Threads read and write slots
+/// without locks; a slot another thread is halfway through writing doesn't match its key and
+/// reads as empty.
+#[derive(Debug, Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Fixed-size table of searched positions indexed by their Zobrist key, shared by the search
+/// threads. One entry per slot: a new entry replaces one of the same position, one from an
+/// earlier search, or one that isn't searched deeper.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    entries: Vec<Slot>,
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// A size of 0 turns the table off.
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let n_entries = size_mb * 1024 * 1024 / size_of::<Slot>();
+        TranspositionTable {
+            entries: (0..n_entries).map(|_| Slot::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    pub fn size_mb(&self) -> usize {
+        self.entries.len() * size_of::<Slot>() / (1024 * 1024)
+    }
+
+    fn index(&self, key: u64) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        Some((key % self.entries.len() as u64) as usize)
+    }
+
+    /// Marks the entries written so far as old, called before every search.
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.entries {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn read(&self, index: usize) -> Option<TableEntry> {
+        let slot = &self.entries[index];
+        let data = slot.data.load(Ordering::Relaxed);
+        if data & OCCUPIED == 0 {
+            return None;
+        }
+        Some(TableEntry::unpack(
+            slot.check.load(Ordering::Relaxed) ^ data,
+            data,
+        ))
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TableEntry> {
+        self.index(key)
+            .and_then(|index| self.read(index))
+            .filter(|entry| entry.key == key)
+    }
+
+    pub fn store(&self, key: u64, depth: u8, score: i32, bound: Bound, best_move: Option<Move>) {
+        let index = match self.index(key) {
+            Some(index) => index,
+            None => return,
+        };
+        let age = self.age.load(Ordering::Relaxed);
+        let existing = self.read(index);
+        if let Some(entry) = existing {
+            let replace = entry.key == key || entry.age != age || depth >= entry.depth;
+            if !replace {
+                return;
+            }
+        }
+        // an entry without a move keeps the one found for the position before
+        let best_move = best_move.or(existing
+            .filter(|entry| entry.key == key)
+            .and_then(|entry| entry.best_move));
+        let data = TableEntry {
+            key,
+            depth,
+            score,
+            bound,
+            best_move,
+            age,
+        }
+        .pack();
+        let slot = &self.entries[index];
+        slot.check.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    /// Used share of the table in permille, like UCI `hashfull`.
+    pub fn hashfull(&self) -> usize {
+        if self.entries.is_empty() {
+            return 0;
+        }
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = self.entries.len().min(1000);
+        (0..sample)
+            .filter(|index| self.read(*index).is_some_and(|entry| entry.age == age))
+            .count()
+            * 1000
+            / sample
+    }
+}
+
+#[cfg(test)]
+mod test_transposition {
+    use crate::bot::{Bound, TranspositionTable, MATE_SCORE};
+    use crate::helpers::Move;
+    use crate::pieces::PieceKind;
+
+    #[test]
+    fn test_store_and_probe() {
+        let table = TranspositionTable::new(1);
+        assert!(table.size_mb() <= 1);
+        let le_move = Move::new("e2".parse().unwrap(), "e4".parse().unwrap());
+        table.store(42, 3, 25, Bound::Exact, Some(le_move));
+
+        let entry = table.probe(42).unwrap();
+        assert_eq!(
+            (entry.depth, entry.score, entry.best_move),
+            (3, 25, Some(le_move))
+        );
+        assert_eq!(table.probe(43), None);
+        assert_eq!(entry.cutoff(3, -100, 100), Some(25));
+        assert_eq!(entry.cutoff(3, 50, 100), Some(50));
+        assert_eq!(entry.cutoff(4, -100, 100), None);
+
+        // without a new move the old one is kept
+        table.store(42, 4, 10, Bound::Lower, None);
+        let entry = table.probe(42).unwrap();
+        assert_eq!(entry.best_move, Some(le_move));
+        assert_eq!(entry.cutoff(4, -100, 5), Some(5));
+        assert_eq!(entry.cutoff(4, -100, 100), None);
+
+        table.clear();
+        assert_eq!(table.probe(42), None);
+    }
+
+    #[test]
+    fn test_packing() {
+        let table = TranspositionTable::new(1);
+        let promotion =
+            Move::new_promotion("b7".parse().unwrap(), "a8".parse().unwrap(), PieceKind::N);
+        table.store(7, 12, -MATE_SCORE, Bound::Upper, Some(promotion));
+        let entry = table.probe(7).unwrap();
+        assert_eq!(
+            (entry.depth, entry.score, entry.bound, entry.best_move),
+            (12, -MATE_SCORE, Bound::Upper, Some(promotion))
+        );
+        // scores out of range are cut to what fits
+        table.store(8, 1, i32::MAX, Bound::Lower, None);
+        assert!(table.probe(8).unwrap().score > MATE_SCORE);
+    }
+
+    #[test]
+    fn test_replacement() {
+        let table = TranspositionTable::new(1);
+        let n_entries = table.entries.len() as u64;
+        table.store(1, 5, 0, Bound::Exact, None);
+        // a shallower entry of another position doesn't push out a deeper one
+        table.store(1 + n_entries, 2, 0, Bound::Exact, None);
+        assert!(table.probe(1).is_some());
+        // unless the deeper one is from an earlier search
+        table.new_search();
+        table.store(1 + n_entries, 2, 0, Bound::Upper, None);
+        assert!(table.probe(1).is_none());
+        assert!(table.probe(1 + n_entries).is_some());
+    }
+
+    #[test]
+    fn test_disabled_table() {
+        let table = TranspositionTable::new(0);
+        table.store(42, 3, 25, Bound::Exact, None);
+        assert_eq!(table.probe(42), None);
+        assert_eq!(table.hashfull(), 0);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::board::{Board, STARTING_FEN};
+use crate::book_builder::{BookBuilder, BookFormat};
+#[cfg(feature = "nnue")]
+use crate::bot::Network;
+use crate::bot::{evaluate, format_score, ChessBot, Pruning, Tablebase, Tablebases, ENDGAMES};
+use crate::epd::{read_epd_file, run_suite, SearchLimit};
+use crate::game::GameResult;
+use crate::notation::line_to_san;
+use crate::pieces::Color;
+use crate::polyglot::PolyglotKeys;
+
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "nnue")]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "Usage:
+    rust-chess                                  start the GUI
+    rust-chess epd <file> [depth <n> | time <seconds>] [threads <n>]
+                                                run the bot on an EPD test suite
+    rust-chess eval [fen]                       print the evaluation breakdown of a position,
+                                                and the network's with the nnue feature
+    rust-chess analyse [depth <n>] [lines <n>] [fen]
+                                                print the best lines of a position
+    rust-chess bench [depth]                    count the nodes searched with and without move
+                                                ordering
+    rust-chess book <pgn> <output> [min-games <n>] [max-ply <n>] [player <name>] [keys <file>]
+                                                build an opening book from a PGN database, a
+                                                Polyglot book for .bin outputs, the native text
+                                                format otherwise
+    rust-chess selfplay <technique> [games <n>] [depth <n>]
+                                                play the bot against itself without null-move,
+                                                lmr, futility, reverse-futility or aspiration
+                                                and estimate what the technique is worth in Elo
+    rust-chess tablebase [dir]                  generate the endgame tablebases into dir,
+                                                $CHESS_TABLEBASES or ./tablebases by default";
+
+/// Opening, middlegame and endgame positions for `bench`.
+const BENCH_POSITIONS: [&str; 5] = [
+    STARTING_FEN,
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+];
+
+/// Self-play games still going after this many plies are called a draw.
+const MAX_SELFPLAY_PLIES: usize = 300;
+
+fn parse_limit(args: &[String]) -> Option<SearchLimit> {
+    match args {
+        [] => Some(SearchLimit::Depth(4)),
+        [kind, value] if kind == "depth" => value.parse::<u8>().ok().map(SearchLimit::Depth),
+        [kind, value] if kind == "time" => value
+            .parse::<f32>()
+            .ok()
+            .map(|seconds| SearchLimit::Time(Duration::from_secs_f32(seconds))),
+        _ => None,
+    }
+}
+
+fn run_epd(args: &[String]) -> Result<(), String> {
+    let (path, mut limit_args) = args.split_first().ok_or(USAGE)?;
+    let mut threads = 1;
+    if let [rest @ .., option, value] = limit_args {
+        if option == "threads" {
+            threads = value.parse::<usize>().map_err(|_| USAGE)?;
+            limit_args = rest;
+        }
+    }
+    let limit = parse_limit(limit_args).ok_or(USAGE)?;
+    let records = read_epd_file(path).map_err(|error| error.to_string())?;
+
+    let mut bot = ChessBot::new(Color::White, 4);
+    bot.threads = threads;
+    let report = run_suite(&records, &mut bot, limit);
+    println!("{}", report);
+    Ok(())
+}
+
+fn run_eval(args: &[String]) -> Result<(), String> {
+    let fen = if args.is_empty() {
+        STARTING_FEN.to_owned()
+    } else {
+        args.join(" ")
+    };
+    let board = Board::from_fen(&fen).map_err(|error| error.to_string())?;
+    println!("{}", evaluate(&board));
+    #[cfg(feature = "nnue")]
+    if let Some(network) = Network::from_env().map_err(|error| error.to_string())? {
+        println!("Network: {}", format_score(network.evaluate(&board)));
+    }
+    Ok(())
+}
+
+fn run_analyse(args: &[String]) -> Result<(), String> {
+    let mut depth = 5;
+    let mut n_lines = 3;
+    let mut rest = args;
+    while let [name, value, tail @ ..] = rest {
+        match name.as_str() {
+            "depth" => depth = value.parse::<u8>().map_err(|_| USAGE)?,
+            "lines" => n_lines = value.parse::<usize>().map_err(|_| USAGE)?,
+            _ => break,
+        }
+        rest = tail;
+    }
+    let fen = if rest.is_empty() {
+        STARTING_FEN.to_owned()
+    } else {
+        rest.join(" ")
+    };
+    let board = Board::from_fen(&fen).map_err(|error| error.to_string())?;
+
+    let mut bot = ChessBot::new(board.turn, depth);
+    bot.multi_pv = n_lines;
+    #[cfg(feature = "nnue")]
+    {
+        bot.network = Network::from_env()
+            .map_err(|error| error.to_string())?
+            .map(Arc::new);
+    }
+    let start = Instant::now();
+    let result = bot.search(&board);
+    println!(
+        "depth {}, {} nodes in {:?}",
+        result.depth,
+        bot.n_calculations,
+        start.elapsed()
+    );
+    for (i, line) in result.lines.iter().enumerate() {
+        println!(
+            "{:>2}. {:>6}  {}",
+            i + 1,
+            format_score(line.score),
+            line_to_san(&board, &line.pv)
+        );
+    }
+    Ok(())
+}
+
+fn run_bench(args: &[String]) -> Result<(), String> {
+    let depth = match args {
+        [] => 4,
+        [depth] => depth.parse::<u8>().map_err(|_| USAGE)?,
+        _ => return Err(USAGE.to_owned()),
+    };
+    let mut totals = [0; 2];
+    println!("{:>10} {:>10}  position", "unordered", "ordered");
+    for fen in BENCH_POSITIONS {
+        let board = Board::from_fen(fen).map_err(|error| error.to_string())?;
+        let mut nodes = [0; 2];
+        for (i, enabled) in [false, true].into_iter().enumerate() {
+            let mut bot = ChessBot::new(board.turn, depth);
+            bot.move_ordering.enabled = enabled;
+            bot.search(&board);
+            nodes[i] = bot.n_calculations;
+            totals[i] += bot.n_calculations;
+        }
+        println!("{:>10} {:>10}  {}", nodes[0], nodes[1], fen);
+    }
+    println!(
+        "{:>10} {:>10}  total at depth {}",
+        totals[0], totals[1], depth
+    );
+    Ok(())
+}
+
+fn run_book(args: &[String]) -> Result<(), String> {
+    let [pgn_path, output, options @ ..] = args else {
+        return Err(USAGE.to_owned());
+    };
+    if !options.len().is_multiple_of(2) {
+        return Err(USAGE.to_owned());
+    }
+    let mut keys = None;
+    let mut min_games = 1;
+    let mut max_ply = None;
+    let mut player = None;
+    for option in options.chunks_exact(2) {
+        let (name, value) = (&option[0], &option[1]);
+        match name.as_str() {
+            "min-games" => min_games = value.parse::<u32>().map_err(|_| USAGE)?,
+            "max-ply" => max_ply = Some(value.parse::<u16>().map_err(|_| USAGE)?),
+            "player" => player = Some(value.clone()),
+            "keys" => keys = Some(PolyglotKeys::load(value).map_err(|error| error.to_string())?),
+            _ => return Err(USAGE.to_owned()),
+        }
+    }
+
+    let mut builder = BookBuilder::new();
+    if let Some(keys) = keys {
+        builder.keys = keys;
+    }
+    builder.min_games = min_games;
+    if let Some(max_ply) = max_ply {
+        builder.max_ply = max_ply;
+    }
+    builder.player = player;
+    let pgn = fs::read_to_string(pgn_path).map_err(|error| error.to_string())?;
+    builder.add_pgn(&pgn);
+    builder
+        .write(output, BookFormat::from_path(output))
+        .map_err(|error| error.to_string())?;
+    println!(
+        "{} games read, {} skipped, {} moves in {} positions written to {}",
+        builder.n_games,
+        builder.n_skipped,
+        builder.moves().len(),
+        builder.n_positions(),
+        output
+    );
+    Ok(())
+}
+
+/// Plays a game between two bots, the first one white.
+fn play_game(mut board: Board, bots: &mut [ChessBot; 2]) -> GameResult {
+    for _ in 0..MAX_SELFPLAY_PLIES {
+        if board.no_possible_moves() {
+            if board.is_king_in_check(&board.turn) {
+                return GameResult::Win(board.next_turn);
+            }
+            return GameResult::Draw;
+        }
+        if board.is_threefold_repetition() || board.n_half_moves >= 100 || board.is_material_draw()
+        {
+            return GameResult::Draw;
+        }
+        let bot = &mut bots[(board.turn == Color::Black) as usize];
+        let le_move = bot.search(&board).le_move;
+        board.bust_a_move(le_move);
+        board.set_values_at_the_end(true);
+    }
+    GameResult::Draw
+}
+
+/// Elo difference that makes the expected score come out at `score`, from 0 to 1.
+fn elo_difference(score: f64) -> Option<f64> {
+    (score > 0. && score < 1.).then(|| -400. * (1. / score - 1.).log10())
+}
+
+fn run_selfplay(args: &[String]) -> Result<(), String> {
+    let [technique, options @ ..] = args else {
+        return Err(USAGE.to_owned());
+    };
+    let mut without = Pruning::default();
+    if !without.set(technique, false) {
+        return Err(USAGE.to_owned());
+    }
+    if !options.len().is_multiple_of(2) {
+        return Err(USAGE.to_owned());
+    }
+    let mut n_games = 8;
+    let mut depth = 4;
+    for option in options.chunks_exact(2) {
+        match option[0].as_str() {
+            "games" => n_games = option[1].parse::<usize>().map_err(|_| USAGE)?,
+            "depth" => depth = option[1].parse::<u8>().map_err(|_| USAGE)?,
+            _ => return Err(USAGE.to_owned()),
+        }
+    }
+
+    // every opening is played with both colors
+    let openings = &BENCH_POSITIONS[..4];
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    for game in 0..n_games {
+        let board = Board::from_fen(openings[game / 2 % openings.len()])
+            .map_err(|error| error.to_string())?;
+        let with_color = if game % 2 == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let mut bots = [
+            ChessBot::new(Color::White, depth),
+            ChessBot::new(Color::Black, depth),
+        ];
+        for bot in bots.iter_mut() {
+            if bot.color != with_color {
+                bot.pruning = without;
+            }
+        }
+        let result = play_game(board, &mut bots);
+        match result {
+            GameResult::Win(color) if color == with_color => wins += 1,
+            GameResult::Win(_) => losses += 1,
+            GameResult::Draw => draws += 1,
+        }
+        let white = if with_color == Color::White {
+            "with"
+        } else {
+            "without"
+        };
+        println!(
+            "game {}: {}, {} {} as white",
+            game + 1,
+            result,
+            white,
+            technique
+        );
+    }
+
+    let score = (wins as f64 + draws as f64 / 2.) / n_games.max(1) as f64;
+    let elo = match elo_difference(score) {
+        Some(elo) => format!("{:+.0}", elo),
+        None => "out of range".to_owned(),
+    };
+    println!(
+        "with {} against without: +{} ={} -{}, {:.1}%, Elo {}",
+        technique,
+        wins,
+        draws,
+        losses,
+        100. * score,
+        elo
+    );
+    Ok(())
+}
+
+fn run_tablebase(args: &[String]) -> Result<(), String> {
+    let dir = match args {
+        [] => std::env::var("CHESS_TABLEBASES").unwrap_or("./tablebases".to_owned()),
+        [dir] => dir.clone(),
+        _ => return Err(USAGE.to_owned()),
+    };
+    let dir = Path::new(&dir);
+    fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+    let mut tablebases = Tablebases::new();
+    for name in ENDGAMES {
+        let start = Instant::now();
+        let table = Tablebase::generate(name, &tablebases).map_err(|error| error.to_string())?;
+        table
+            .save(dir.join(format!("{}.tb", name)))
+            .map_err(|error| error.to_string())?;
+        println!(
+            "{}: {} won positions, longest mate {} plies, generated in {:?}",
+            name,
+            table.n_wins(),
+            table.longest_mate(),
+            start.elapsed()
+        );
+        tablebases.add(table);
+    }
+    Ok(())
+}
+
+/// Runs the command given on the command line, the GUI is started when there is none.
+pub fn run(args: &[String]) -> Result<(), String> {
+    match args.split_first() {
+        Some((command, rest)) if command == "epd" => run_epd(rest),
+        Some((command, rest)) if command == "eval" => run_eval(rest),
+        Some((command, rest)) if command == "analyse" => run_analyse(rest),
+        Some((command, rest)) if command == "bench" => run_bench(rest),
+        Some((command, rest)) if command == "book" => run_book(rest),
+        Some((command, rest)) if command == "selfplay" => run_selfplay(rest),
+        Some((command, rest)) if command == "tablebase" => run_tablebase(rest),
+        _ => Err(USAGE.to_owned()),
+    }
+}
@@ -0,0 +1,461 @@
+use crate::board::Board;
+use crate::game::GameResult;
+use crate::pieces::Color;
+
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Time given back to a player for each move.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bonus {
+    None,
+    /// Added after every move.
+    Fischer(Duration),
+    /// Gives back the time used on the move, up to the delay.
+    Bronstein(Duration),
+    /// The clock only starts running once the delay has passed (US delay).
+    SimpleDelay(Duration),
+}
+
+/// `moves` have to be played in `time`, `None` means the rest of the game.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Stage {
+    pub moves: Option<u16>,
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TimeControl {
+    /// Played one after another, a last stage with a move count repeats.
+    Stages(Vec<Stage>),
+    /// The time one player uses is added to the other one.
+    Hourglass(Duration),
+}
+
+impl TimeControl {
+    pub fn sudden_death(time: Duration) -> TimeControl {
+        TimeControl::with_bonus(time, Bonus::None)
+    }
+
+    pub fn fischer(time: Duration, increment: Duration) -> TimeControl {
+        TimeControl::with_bonus(time, Bonus::Fischer(increment))
+    }
+
+    pub fn bronstein(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::with_bonus(time, Bonus::Bronstein(delay))
+    }
+
+    pub fn simple_delay(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::with_bonus(time, Bonus::SimpleDelay(delay))
+    }
+
+    pub fn hourglass(time: Duration) -> TimeControl {
+        TimeControl::Hourglass(time)
+    }
+
+    fn with_bonus(time: Duration, bonus: Bonus) -> TimeControl {
+        TimeControl::Stages(vec![Stage {
+            moves: None,
+            time,
+            bonus,
+        }])
+    }
+
+    fn stage(&self, index: usize) -> Option<&Stage> {
+        match self {
+            TimeControl::Stages(stages) => stages.get(index.min(stages.len() - 1)),
+            TimeControl::Hourglass(_) => None,
+        }
+    }
+}
+
+/// PGN `TimeControl` tag format, e.g. `300+2`, `40/5400+30:1800+30` or `*180` for hourglass.
+/// Delays, which the tag has no notation for, are written as `300+5b` (Bronstein) and `300+5d`
+/// (simple delay).
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stages = match self {
+            TimeControl::Hourglass(time) => return write!(f, "*{}", time.as_secs()),
+            TimeControl::Stages(stages) => stages,
+        };
+        let stages: Vec<String> = stages
+            .iter()
+            .map(|stage| {
+                let mut output = match stage.moves {
+                    Some(moves) => format!("{}/{}", moves, stage.time.as_secs()),
+                    None => stage.time.as_secs().to_string(),
+                };
+                match stage.bonus {
+                    Bonus::None => {}
+                    Bonus::Fischer(increment) => {
+                        output.push_str(&format!("+{}", increment.as_secs()))
+                    }
+                    Bonus::Bronstein(delay) => output.push_str(&format!("+{}b", delay.as_secs())),
+                    Bonus::SimpleDelay(delay) => output.push_str(&format!("+{}d", delay.as_secs())),
+                }
+                output
+            })
+            .collect();
+        write!(f, "{}", stages.join(":"))
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = Error;
+
+    fn from_str(time_control: &str) -> Result<TimeControl, Error> {
+        let invalid_time_control = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid time control {}", time_control),
+            )
+        };
+        let seconds = |text: &str| -> Result<Duration, Error> {
+            text.parse::<u64>()
+                .map(Duration::from_secs)
+                .map_err(|_| invalid_time_control())
+        };
+
+        let time_control = time_control.trim();
+        if let Some(time) = time_control.strip_prefix('*') {
+            return Ok(TimeControl::Hourglass(seconds(time)?));
+        }
+
+        let mut stages = Vec::new();
+        for stage in time_control.split(':') {
+            let (moves, rest) = match stage.split_once('/') {
+                Some((moves, rest)) => (
+                    Some(moves.parse::<u16>().map_err(|_| invalid_time_control())?),
+                    rest,
+                ),
+                None => (None, stage),
+            };
+            let (time, bonus) = match rest.split_once('+') {
+                Some((time, bonus)) => {
+                    let bonus = if let Some(delay) = bonus.strip_suffix('b') {
+                        Bonus::Bronstein(seconds(delay)?)
+                    } else if let Some(delay) = bonus.strip_suffix('d') {
+                        Bonus::SimpleDelay(seconds(delay)?)
+                    } else {
+                        Bonus::Fischer(seconds(bonus)?)
+                    };
+                    (seconds(time)?, bonus)
+                }
+                None => (seconds(rest)?, Bonus::None),
+            };
+            stages.push(Stage { moves, time, bonus });
+        }
+        Ok(TimeControl::Stages(stages))
+    }
+}
+
+/// Clocks of both players. `press` is the button a player hits after moving, `record_move`
+/// does the same with a given thinking time.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    pub time_control: TimeControl,
+    pub turn: Color,
+    remaining: [Duration; 2],
+    stage: [usize; 2],
+    moves_in_stage: [u16; 2],
+    flagged: Option<Color>,
+    turn_started: Option<Instant>,
+}
+
+fn color_index(color: Color) -> usize {
+    (color == Color::Black) as usize
+}
+
+fn other_color(color: Color) -> Color {
+    if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    }
+}
+
+impl Clock {
+    pub fn new(time_control: TimeControl) -> Clock {
+        let time = match &time_control {
+            TimeControl::Hourglass(time) => *time,
+            TimeControl::Stages(stages) => stages[0].time,
+        };
+        Clock {
+            time_control,
+            turn: Color::White,
+            remaining: [time, time],
+            stage: [0, 0],
+            moves_in_stage: [0, 0],
+            flagged: None,
+            turn_started: None,
+        }
+    }
+
+    /// Starts the clock of the player to move.
+    pub fn start(&mut self, turn: Color) {
+        self.turn = turn;
+        self.turn_started = Some(Instant::now());
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.turn_started.is_some()
+    }
+
+    /// Ends the turn of the player to move with the time measured since their clock started.
+    pub fn press(&mut self) {
+        let used = self
+            .turn_started
+            .map(|started| started.elapsed())
+            .unwrap_or(Duration::ZERO);
+        self.record_move(used);
+        self.turn_started = Some(Instant::now());
+    }
+
+    /// Ends the turn of the player to move, who used `used` on the move.
+    pub fn record_move(&mut self, used: Duration) {
+        if self.flagged.is_some() {
+            return;
+        }
+        let mover = color_index(self.turn);
+        let stage = self.time_control.stage(self.stage[mover]).copied();
+
+        let charged = match stage.map(|stage| stage.bonus) {
+            Some(Bonus::SimpleDelay(delay)) => used.saturating_sub(delay),
+            _ => used,
+        };
+        if charged > self.remaining[mover] {
+            self.remaining[mover] = Duration::ZERO;
+            self.flagged = Some(self.turn);
+            return;
+        }
+        self.remaining[mover] -= charged;
+
+        match stage {
+            None => self.remaining[1 - mover] += used,
+            Some(stage) => {
+                match stage.bonus {
+                    Bonus::Fischer(increment) => self.remaining[mover] += increment,
+                    Bonus::Bronstein(delay) => self.remaining[mover] += used.min(delay),
+                    Bonus::None | Bonus::SimpleDelay(_) => {}
+                }
+                self.moves_in_stage[mover] += 1;
+                if Some(self.moves_in_stage[mover]) == stage.moves {
+                    self.stage[mover] += 1;
+                    self.moves_in_stage[mover] = 0;
+                    if let Some(next_stage) = self.time_control.stage(self.stage[mover]) {
+                        self.remaining[mover] += next_stage.time;
+                    }
+                }
+            }
+        }
+        self.turn = other_color(self.turn);
+    }
+
+    /// Time left on the clock, counting the running move of the player to move.
+    pub fn remaining(&self, color: Color) -> Duration {
+        let index = color_index(color);
+        let running = match self.turn_started {
+            Some(started) if color == self.turn && self.flagged.is_none() => started.elapsed(),
+            _ => Duration::ZERO,
+        };
+        let running = match self.current_stage(color).map(|stage| stage.bonus) {
+            Some(Bonus::SimpleDelay(delay)) => running.saturating_sub(delay),
+            _ => running,
+        };
+        self.remaining[index].saturating_sub(running)
+    }
+
+    /// The player whose time ran out, if any.
+    pub fn flagged(&self) -> Option<Color> {
+        if self.flagged.is_some() {
+            return self.flagged;
+        }
+        if self.is_running() && self.remaining(self.turn) == Duration::ZERO {
+            return Some(self.turn);
+        }
+        None
+    }
+
+    fn current_stage(&self, color: Color) -> Option<&Stage> {
+        self.time_control.stage(self.stage[color_index(color)])
+    }
+
+    /// Moves left to play in the current stage, `None` when it runs to the end of the game.
+    pub fn moves_to_go(&self, color: Color) -> Option<u16> {
+        self.current_stage(color)
+            .and_then(|stage| stage.moves)
+            .map(|moves| moves - self.moves_in_stage[color_index(color)])
+    }
+
+    /// Time the player gets back per move in the current stage, at most.
+    pub fn increment(&self, color: Color) -> Duration {
+        match self.current_stage(color).map(|stage| stage.bonus) {
+            Some(Bonus::Fischer(bonus))
+            | Some(Bonus::Bronstein(bonus))
+            | Some(Bonus::SimpleDelay(bonus)) => bonus,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Running out of time loses, unless the opponent couldn't mate anyway.
+pub fn timeout_result(board: &Board, flagged: Color) -> GameResult {
+    let opponent = other_color(flagged);
+    if board.has_mating_material(opponent) {
+        GameResult::Win(opponent)
+    } else {
+        GameResult::Draw
+    }
+}
+
+/// Shows the time as `m:ss`, with tenths when under ten seconds.
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds < 10 {
+        format!("0:{:02}.{}", seconds, time.subsec_millis() / 100)
+    } else if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod test_clock {
+    use crate::board::Board;
+    use crate::clock::{format_time, timeout_result, Bonus, Clock, Stage, TimeControl};
+    use crate::game::GameResult;
+    use crate::pieces::Color;
+    use std::time::Duration;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_sudden_death_and_flag() {
+        let mut clock = Clock::new(TimeControl::sudden_death(secs(60)));
+        clock.record_move(secs(10));
+        assert_eq!(clock.remaining(Color::White), secs(50));
+        assert_eq!(clock.turn, Color::Black);
+        clock.record_move(secs(61));
+        assert_eq!(clock.flagged(), Some(Color::Black));
+        assert_eq!(clock.remaining(Color::Black), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_bonuses() {
+        let mut clock = Clock::new(TimeControl::fischer(secs(60), secs(2)));
+        clock.record_move(secs(10));
+        assert_eq!(clock.remaining(Color::White), secs(52));
+
+        let mut clock = Clock::new(TimeControl::bronstein(secs(60), secs(5)));
+        clock.record_move(secs(3));
+        clock.record_move(secs(10));
+        assert_eq!(clock.remaining(Color::White), secs(60));
+        assert_eq!(clock.remaining(Color::Black), secs(55));
+
+        let mut clock = Clock::new(TimeControl::simple_delay(secs(60), secs(5)));
+        clock.record_move(secs(3));
+        clock.record_move(secs(10));
+        assert_eq!(clock.remaining(Color::White), secs(60));
+        assert_eq!(clock.remaining(Color::Black), secs(55));
+        // the delay also saves a player who would otherwise have flagged
+        let mut clock = Clock::new(TimeControl::simple_delay(secs(1), secs(5)));
+        clock.record_move(secs(4));
+        assert_eq!(clock.flagged(), None);
+    }
+
+    #[test]
+    fn test_hourglass() {
+        let mut clock = Clock::new(TimeControl::hourglass(secs(60)));
+        clock.record_move(secs(10));
+        assert_eq!(clock.remaining(Color::White), secs(50));
+        assert_eq!(clock.remaining(Color::Black), secs(70));
+    }
+
+    #[test]
+    fn test_multi_stage() {
+        let time_control: TimeControl = "40/5400+30:1800+30".parse().unwrap();
+        assert_eq!(
+            time_control,
+            TimeControl::Stages(vec![
+                Stage {
+                    moves: Some(40),
+                    time: secs(5400),
+                    bonus: Bonus::Fischer(secs(30)),
+                },
+                Stage {
+                    moves: None,
+                    time: secs(1800),
+                    bonus: Bonus::Fischer(secs(30)),
+                },
+            ])
+        );
+        assert_eq!(time_control.to_string(), "40/5400+30:1800+30");
+
+        let mut clock = Clock::new(time_control);
+        for _ in 0..39 {
+            clock.record_move(secs(60));
+            clock.record_move(secs(0));
+        }
+        assert_eq!(clock.remaining(Color::White), secs(5400 - 39 * 30));
+        clock.record_move(secs(60));
+        assert_eq!(clock.remaining(Color::White), secs(5400 - 40 * 30 + 1800));
+    }
+
+    #[test]
+    fn test_time_control_strings() {
+        for time_control in ["300", "180+2", "*180", "40/7200:3600", "300+5b", "300+5d"] {
+            let parsed: TimeControl = time_control.parse().unwrap();
+            assert_eq!(parsed.to_string(), time_control);
+        }
+        assert!("5 minutes".parse::<TimeControl>().is_err());
+        assert!("40/:300".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn test_timeout_result() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4KQ2 w - - 0 1").unwrap();
+        assert_eq!(
+            timeout_result(&board, Color::Black),
+            GameResult::Win(Color::White)
+        );
+        assert_eq!(timeout_result(&board, Color::White), GameResult::Draw);
+
+        // a lone knight can only mate with help from the opponent's own pieces
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4KN2 w - - 0 1").unwrap();
+        assert_eq!(timeout_result(&board, Color::Black), GameResult::Draw);
+        let board = Board::from_fen("4k3/4p3/8/8/8/8/8/4KN2 w - - 0 1").unwrap();
+        assert_eq!(
+            timeout_result(&board, Color::Black),
+            GameResult::Win(Color::White)
+        );
+
+        // bishops all on dark squares can never mate
+        let board = Board::from_fen("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1").unwrap();
+        assert_eq!(timeout_result(&board, Color::Black), GameResult::Draw);
+    }
+
+    #[test]
+    fn test_moves_to_go_and_increment() {
+        let clock = Clock::new(TimeControl::fischer(secs(300), secs(4)));
+        assert_eq!(clock.moves_to_go(Color::White), None);
+        assert_eq!(clock.increment(Color::White), secs(4));
+        let mut clock = Clock::new("40/400".parse().unwrap());
+        clock.record_move(secs(1));
+        assert_eq!(clock.moves_to_go(Color::White), Some(39));
+        assert_eq!(clock.moves_to_go(Color::Black), Some(40));
+        assert_eq!(clock.increment(Color::Black), Duration::ZERO);
+        assert_eq!(format_time(secs(75)), "1:15");
+        assert_eq!(format_time(Duration::from_millis(5300)), "0:05.3");
+    }
+}
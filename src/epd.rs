@@ -0,0 +1,432 @@
+use crate::board::Board;
+use crate::bot::{mate_distance, ChessBot, SearchLimits};
+use crate::helpers::Move;
+use crate::notation::{move_to_san, san_to_move};
+use crate::pieces::Color;
+
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdRecord {
+    pub board: Board,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+fn invalid_epd(line: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("Invalid EPD record: {}", line),
+    )
+}
+
+/// Splits the operations part on `;`, keeping quoted operands (which may contain `;`) whole.
+fn parse_operations(operations: &str) -> Vec<(String, Vec<String>)> {
+    let mut parsed = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut in_quotes = false;
+
+    for c in operations.chars() {
+        match c {
+            '"' => {
+                if in_quotes {
+                    words.push(word.clone());
+                    word.clear();
+                }
+                in_quotes = !in_quotes;
+            }
+            ';' if !in_quotes => {
+                if !word.is_empty() {
+                    words.push(word.clone());
+                    word.clear();
+                }
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    parsed.push((opcode, words.clone()));
+                    words.clear();
+                }
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !word.is_empty() {
+                    words.push(word.clone());
+                    word.clear();
+                }
+            }
+            _ => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        parsed.push((opcode, words));
+    }
+    parsed
+}
+
+impl EpdRecord {
+    /// Parses the four FEN fields followed by opcodes, e.g.
+    /// `2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";`.
+    /// The move counters come from the `hmvc` and `fmvn` opcodes when they are present.
+    pub fn from_epd(line: &str) -> Result<EpdRecord, Error> {
+        let line = line.trim();
+        let mut fields = line.splitn(5, char::is_whitespace);
+        let mut fen_fields: Vec<&str> = Vec::new();
+        for _ in 0..4 {
+            match fields.next() {
+                Some(field) if !field.is_empty() => fen_fields.push(field),
+                _ => return Err(invalid_epd(line)),
+            }
+        }
+        let operations = parse_operations(fields.next().unwrap_or(""));
+
+        let operand = |opcode: &str, default: &str| -> String {
+            operations
+                .iter()
+                .find(|(name, _)| name == opcode)
+                .and_then(|(_, operands)| operands.first().cloned())
+                .unwrap_or(default.to_owned())
+        };
+        let fen = format!(
+            "{} {} {}",
+            fen_fields.join(" "),
+            operand("hmvc", "0"),
+            operand("fmvn", "1")
+        );
+        let board = Board::from_fen(&fen).map_err(|_| invalid_epd(line))?;
+
+        Ok(EpdRecord { board, operations })
+    }
+
+    pub fn get(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn id(&self) -> String {
+        self.get("id")
+            .and_then(|operands| operands.first().cloned())
+            .unwrap_or_else(|| self.board.to_fen())
+    }
+
+    /// Empty only without a `bm` opcode; a `bm` without moves or with a move that isn't legal
+    /// here is an error.
+    pub fn best_moves(&self) -> Result<Vec<Move>, Error> {
+        self.moves_of("bm")
+    }
+
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, Error> {
+        self.moves_of("am")
+    }
+
+    pub fn mate_in(&self) -> Option<u8> {
+        self.get("dm")
+            .and_then(|operands| operands.first())
+            .and_then(|operand| operand.parse::<u8>().ok())
+    }
+
+    fn moves_of(&self, opcode: &str) -> Result<Vec<Move>, Error> {
+        let sans = match self.get(opcode) {
+            Some(sans) => sans,
+            None => return Ok(Vec::new()),
+        };
+        if sans.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} without moves in {}", opcode, self.id()),
+            ));
+        }
+        sans.iter()
+            .map(|san| {
+                san_to_move(&self.board, san).ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Invalid move {} in {} of {}", san, opcode, self.id()),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+pub fn read_epd(epd: &str) -> Result<Vec<EpdRecord>, Error> {
+    epd.lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(EpdRecord::from_epd)
+        .collect()
+}
+
+pub fn read_epd_file(path: &str) -> Result<Vec<EpdRecord>, Error> {
+    read_epd(&fs::read_to_string(path)?)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchLimit {
+    Depth(u8),
+    /// Searches for this long per position.
+    Time(Duration),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuiteResult {
+    pub id: String,
+    pub found: String,
+    pub expected: String,
+    pub solved: bool,
+    pub depth: u8,
+    pub time: Duration,
+    /// Why the record couldn't be run, it counts as failed.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuiteReport {
+    pub results: Vec<SuiteResult>,
+}
+
+impl SuiteReport {
+    pub fn n_solved(&self) -> usize {
+        self.results.iter().filter(|result| result.solved).count()
+    }
+
+    pub fn n_failed(&self) -> usize {
+        self.results.len() - self.n_solved()
+    }
+}
+
+impl fmt::Display for SuiteReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in &self.results {
+            if let Some(error) = &result.error {
+                writeln!(f, "ERROR  {:<16} {}", result.id, error)?;
+                continue;
+            }
+            writeln!(
+                f,
+                "{} {:<16} found {:<8} expected {:<16} depth {} in {:?}",
+                if result.solved { "solved" } else { "FAILED" },
+                result.id,
+                result.found,
+                result.expected,
+                result.depth,
+                result.time
+            )?;
+        }
+        write!(
+            f,
+            "Solved {}/{}, failed {}",
+            self.n_solved(),
+            self.results.len(),
+            self.n_failed()
+        )
+    }
+}
+
+fn is_same_move(expected: &Move, found: &Move) -> bool {
+    expected.from == found.from
+        && expected.to == found.to
+        && (expected.promotion.is_none() || expected.promotion == found.promotion)
+}
+
+/// Whether the score is a mate for `color` in at most `moves` of its moves.
+fn is_mate_in(score: i32, color: Color, moves: u8) -> bool {
+    let plies = match (mate_distance(score), color) {
+        (Some(plies), Color::White) if plies > 0 => plies,
+        (Some(plies), Color::Black) if plies < 0 => -plies,
+        _ => return false,
+    };
+    (plies + 1) / 2 <= moves as i32
+}
+
+/// Runs the bot on every record. A record is solved when the chosen move is one of `bm`, is none
+/// of `am`, and for `dm` records without `bm` when the bot sees a mate that fast. Records whose
+/// `bm` or `am` moves don't parse aren't searched and fail with the error. A time limit is a
+/// hard limit, it stops the search partway through a depth.
+pub fn run_suite(records: &[EpdRecord], bot: &mut ChessBot, limit: SearchLimit) -> SuiteReport {
+    let mut results = Vec::new();
+
+    for record in records {
+        let (best_moves, avoid_moves) = match (record.best_moves(), record.avoid_moves()) {
+            (Ok(best_moves), Ok(avoid_moves)) => (best_moves, avoid_moves),
+            (Err(error), _) | (_, Err(error)) => {
+                results.push(SuiteResult {
+                    id: record.id(),
+                    found: String::new(),
+                    expected: String::new(),
+                    solved: false,
+                    depth: 0,
+                    time: Duration::ZERO,
+                    error: Some(error.to_string()),
+                });
+                continue;
+            }
+        };
+        let start = Instant::now();
+        let (result, depth) = match limit {
+            SearchLimit::Depth(depth) => (
+                bot.search_with_limits(&record.board, &SearchLimits::depth(depth)),
+                depth,
+            ),
+            SearchLimit::Time(time_limit) => {
+                let result =
+                    bot.search_with_limits(&record.board, &SearchLimits::move_time(time_limit));
+                let depth = result.depth;
+                (result, depth)
+            }
+        };
+
+        let mut solved =
+            best_moves.is_empty() || best_moves.iter().any(|m| is_same_move(m, &result.le_move));
+        solved &= !avoid_moves.iter().any(|m| is_same_move(m, &result.le_move));
+        if let Some(moves) = record.mate_in().filter(|_| best_moves.is_empty()) {
+            solved &= is_mate_in(result.score, record.board.turn, moves);
+        }
+
+        let mut expected = Vec::new();
+        if let Some(bm) = record.get("bm") {
+            expected.push(format!("bm {}", bm.join(" ")));
+        }
+        if let Some(am) = record.get("am") {
+            expected.push(format!("am {}", am.join(" ")));
+        }
+        if let Some(dm) = record.mate_in() {
+            expected.push(format!("dm {}", dm));
+        }
+
+        results.push(SuiteResult {
+            id: record.id(),
+            found: move_to_san(&record.board, result.le_move),
+            expected: expected.join(", "),
+            solved,
+            depth,
+            time: start.elapsed(),
+            error: None,
+        });
+    }
+
+    SuiteReport { results }
+}
+
+#[cfg(test)]
+mod test_epd {
+    use crate::bot::ChessBot;
+    use crate::epd::{is_same_move, read_epd, run_suite, EpdRecord, SearchLimit};
+    use crate::helpers::{chess_move, Move};
+    use crate::pieces::{Color, PieceKind};
+    use std::time::Duration;
+
+    #[test]
+    fn test_from_epd() {
+        let record = EpdRecord::from_epd(
+            r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001"; c0 "mate; in two"; acd 12;"#,
+        )
+        .unwrap();
+
+        assert_eq!(record.id(), "WAC.001");
+        assert_eq!(record.get("c0").unwrap(), ["mate; in two"]);
+        assert_eq!(record.get("acd").unwrap(), ["12"]);
+        assert_eq!(record.best_moves().unwrap().len(), 1);
+        assert!(record.avoid_moves().unwrap().is_empty());
+        assert_eq!(
+            record.board.to_fen(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_from_epd_move_counters_and_errors() {
+        let record = EpdRecord::from_epd("8/8/8/8/8/8/4k3/4K3 b - - hmvc 7; fmvn 40;").unwrap();
+        assert_eq!(record.board.n_half_moves, 7);
+        assert_eq!(record.board.n_full_moves, 40);
+        assert_eq!(record.board.turn, Color::Black);
+
+        assert!(EpdRecord::from_epd("8/8/8/8 w -").is_err());
+        assert!(read_epd("# comment\n\n4k3/8/8/8/8/8/8/4K3 w - - id \"x\";").is_ok());
+    }
+
+    #[test]
+    fn test_run_suite() {
+        let records = read_epd(
+            r#"6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id "mate.1";
+6k1/5ppp/8/8/8/8/8/R5K1 w - - am Ra8; id "avoid.1";
+r5k1/8/8/8/8/8/5PPP/6K1 b - - dm 1; id "mate.2";"#,
+        )
+        .unwrap();
+        let mut bot = ChessBot::new(Color::White, 5);
+
+        let report = run_suite(&records, &mut bot, SearchLimit::Depth(2));
+        // the depth is for the suite only
+        assert_eq!(bot.max_depth, 5);
+        let solved: Vec<bool> = report.results.iter().map(|r| r.solved).collect();
+        assert_eq!(solved, vec![true, false, true]);
+        assert_eq!(report.results[0].found, "Ra8#");
+        assert_eq!(report.n_failed(), 1);
+
+        let report = run_suite(
+            &records[..1],
+            &mut bot,
+            SearchLimit::Time(Duration::from_secs(60)),
+        );
+        assert_eq!(report.n_solved(), 1);
+    }
+
+    #[test]
+    fn test_promotions_have_to_match() {
+        let promotion =
+            |kind| Move::new_promotion("b7".parse().unwrap(), "a8".parse().unwrap(), kind);
+        assert!(is_same_move(
+            &promotion(PieceKind::Q),
+            &promotion(PieceKind::Q)
+        ));
+        assert!(!is_same_move(
+            &promotion(PieceKind::Q),
+            &promotion(PieceKind::N)
+        ));
+        assert!(!is_same_move(
+            &promotion(PieceKind::N),
+            &promotion(PieceKind::Q)
+        ));
+        assert!(is_same_move(
+            &chess_move("e2", "e4"),
+            &chess_move("e2", "e4")
+        ));
+    }
+
+    #[test]
+    fn test_run_suite_with_unparsable_moves() {
+        let records = read_epd(
+            r#"6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra9#; id "typo";
+6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8# Qd8; id "illegal";
+6k1/5ppp/8/8/8/8/8/R5K1 w - - am Rb8 Rz1; id "avoid";
+6k1/5ppp/8/8/8/8/8/R5K1 w - - bm; id "empty";"#,
+        )
+        .unwrap();
+        assert!(records[1].best_moves().is_err());
+        let mut bot = ChessBot::new(Color::White, 2);
+
+        let report = run_suite(&records, &mut bot, SearchLimit::Depth(2));
+        assert_eq!(report.n_solved(), 0);
+        assert!(report.results.iter().all(|result| result.error.is_some()));
+        assert!(report.to_string().contains("Invalid move Ra9#"));
+    }
+
+    #[test]
+    fn test_run_suite_stops_at_the_time_limit() {
+        let records = read_epd(
+            r#"r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - id "kiwipete";"#,
+        )
+        .unwrap();
+        let mut bot = ChessBot::new(Color::White, 2);
+        let limit = Duration::from_millis(300);
+
+        let report = run_suite(&records, &mut bot, SearchLimit::Time(limit));
+        // well short of what the next depth would take
+        assert!(report.results[0].time < limit * 3);
+    }
+}
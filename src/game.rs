@@ -0,0 +1,229 @@
+use crate::board::Board;
+use crate::helpers::Move;
+use crate::pieces::{Color, Piece, PieceKind};
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GameResult {
+    Win(Color),
+    Draw,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match self {
+            GameResult::Win(Color::White) => "1-0",
+            GameResult::Win(Color::Black) => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct HistoryEntry {
+    le_move: Move,
+    board: Board,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    pub board: Board,
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game::from_board(Board::new())
+    }
+
+    pub fn from_board(board: Board) -> Game {
+        Game {
+            board,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    /// Plays the move on the board and remembers the whole board before it, so undo brings back
+    /// everything (has_moved flags, castling, en passant, clocks, pending promotion).
+    /// Like `Board::bust_a_move`, the turn is not finished until `finish_turn` is called.
+    pub fn make_move(&mut self, le_move: Move) {
+        self.undo_stack.push(HistoryEntry {
+            le_move,
+            board: self.board.clone(),
+        });
+        self.redo_stack.clear();
+        self.board.bust_a_move(le_move);
+    }
+
+    pub fn promote(&mut self, piece_kind: PieceKind) {
+        if let Some(position) = self.board.promotion_position {
+            self.board.board[position.index()] =
+                Some(Piece::new(self.board.turn, piece_kind, position));
+            self.board.promotion_position = None;
+            if let Some(entry) = self.undo_stack.last_mut() {
+                entry.le_move.promotion = Some(piece_kind);
+            }
+        }
+    }
+
+    pub fn finish_turn(&mut self) {
+        self.board.set_values_at_the_end(true);
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some(entry) => {
+                let board_after = std::mem::replace(&mut self.board, entry.board);
+                self.redo_stack.push(HistoryEntry {
+                    le_move: entry.le_move,
+                    board: board_after,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(entry) => {
+                let board_before = std::mem::replace(&mut self.board, entry.board);
+                self.undo_stack.push(HistoryEntry {
+                    le_move: entry.le_move,
+                    board: board_before,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn last_move(&self) -> Option<Move> {
+        self.undo_stack.last().map(|entry| entry.le_move)
+    }
+
+    pub fn moves(&self) -> Vec<Move> {
+        self.undo_stack.iter().map(|entry| entry.le_move).collect()
+    }
+}
+
+#[cfg(test)]
+mod test_game {
+    use crate::board::Board;
+    use crate::game::Game;
+    use crate::helpers::chess_move;
+    use crate::pieces::{Color, PieceKind};
+
+    fn play(game: &mut Game, from: &str, to: &str) {
+        game.make_move(chess_move(from, to));
+        game.finish_turn();
+    }
+
+    #[test]
+    fn test_undo_restores_board() {
+        let mut game = Game::new();
+        play(&mut game, "e2", "e4");
+        play(&mut game, "e7", "e5");
+
+        assert!(game.undo());
+        assert!(game.undo());
+        assert!(!game.undo());
+        assert_eq!(game.board, Board::new());
+    }
+
+    #[test]
+    fn test_undo_restores_has_moved_and_castling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 3 10";
+        let mut game = Game::from_board(Board::from_fen(fen).unwrap());
+        play(&mut game, "e1", "g1");
+
+        let king_position = "g1".parse().unwrap();
+        assert!(game.board.castling[&Color::White].iter().all(|x| !x));
+        assert_eq!(game.board.n_half_moves, 4);
+
+        game.undo();
+        let king_position_before = "e1".parse().unwrap();
+        let king = game
+            .board
+            .get_piece_from_position(&king_position_before)
+            .unwrap();
+        assert!(!king.has_moved);
+        assert!(game.board.get_piece_from_position(&king_position).is_none());
+        assert_eq!(game.board.castling[&Color::White], [true, true]);
+        assert_eq!(
+            game.board.king_positions[&Color::White],
+            king_position_before
+        );
+        assert_eq!(game.board.n_half_moves, 3);
+        assert_eq!(game.board.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_undo_restores_en_passant() {
+        let fen = "4k3/8/8/8/5p2/8/4P3/4K3 w - - 0 1";
+        let mut game = Game::from_board(Board::from_fen(fen).unwrap());
+        play(&mut game, "e2", "e4");
+        play(&mut game, "f4", "e3");
+        assert!(game.board.en_passant.is_none());
+
+        game.undo();
+        assert_eq!(game.board.en_passant, "e3".parse().ok());
+        assert!(game
+            .board
+            .get_piece_from_position(&"e4".parse().unwrap())
+            .is_some());
+    }
+
+    #[test]
+    fn test_undo_pending_promotion() {
+        let fen = "4k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        let mut game = Game::from_board(Board::from_fen(fen).unwrap());
+        game.make_move(chess_move("a7", "a8"));
+        assert!(game.board.promotion_position.is_some());
+
+        game.undo();
+        assert!(game.board.promotion_position.is_none());
+        assert_eq!(game.board.to_fen(), fen);
+
+        game.redo();
+        assert_eq!(game.board.promotion_position, "a8".parse().ok());
+        game.promote(PieceKind::N);
+        game.finish_turn();
+        let promoted = game
+            .board
+            .get_piece_from_position(&"a8".parse().unwrap())
+            .unwrap();
+        assert_eq!(promoted.kind, PieceKind::N);
+        assert_eq!(promoted.color, Color::White);
+    }
+
+    #[test]
+    fn test_redo_and_new_move_clears_redo() {
+        let mut game = Game::new();
+        play(&mut game, "e2", "e4");
+        let after_e4 = game.board.clone();
+
+        game.undo();
+        assert!(game.can_redo());
+        assert!(game.redo());
+        assert_eq!(game.board, after_e4);
+        assert_eq!(game.moves(), vec![chess_move("e2", "e4")]);
+
+        game.undo();
+        play(&mut game, "d2", "d4");
+        assert!(!game.can_redo());
+        assert_eq!(game.moves(), vec![chess_move("d2", "d4")]);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::board::Board;
+use crate::helpers::Move;
+use crate::notation::move_to_san;
+
+pub type NodeId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameNode {
+    pub le_move: Option<Move>,
+    pub san: String,
+    pub board: Board,
+    pub comment: Option<String>,
+    pub nags: Vec<u8>,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+}
+
+/// Tree of moves for analysis. Every node owns the position after its move, the first child
+/// is the main line and the other children are variations. Nodes live in one arena and are
+/// referenced by index; deleted nodes are only detached from their parent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameTree {
+    pub tags: Vec<(String, String)>,
+    nodes: Vec<GameNode>,
+}
+
+impl GameTree {
+    pub fn new(board: Board) -> GameTree {
+        GameTree {
+            tags: Vec::new(),
+            nodes: vec![GameNode {
+                le_move: None,
+                san: String::new(),
+                board,
+                comment: None,
+                nags: Vec::new(),
+                parent: None,
+                children: Vec::new(),
+            }],
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn node(&self, node_id: NodeId) -> &GameNode {
+        &self.nodes[node_id]
+    }
+
+    pub fn node_mut(&mut self, node_id: NodeId) -> &mut GameNode {
+        &mut self.nodes[node_id]
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some(tag) => tag.1 = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// Adds the move after the given node. If the move is already there, the existing node is
+    /// returned, otherwise it becomes the main line or, when the node already has a
+    /// continuation, a new variation.
+    pub fn add_move(&mut self, parent: NodeId, le_move: Move) -> NodeId {
+        if let Some(existing) = self.find_child(parent, le_move) {
+            return existing;
+        }
+
+        let parent_board = &self.nodes[parent].board;
+        let san = move_to_san(parent_board, le_move);
+        let mut board = parent_board.clone();
+        board.bust_a_move(le_move);
+        board.set_values_at_the_end(true);
+
+        let node_id = self.nodes.len();
+        self.nodes.push(GameNode {
+            le_move: Some(le_move),
+            san,
+            board,
+            comment: None,
+            nags: Vec::new(),
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        self.nodes[parent].children.push(node_id);
+        node_id
+    }
+
+    pub fn find_child(&self, parent: NodeId, le_move: Move) -> Option<NodeId> {
+        self.nodes[parent]
+            .children
+            .iter()
+            .copied()
+            .find(|child| self.nodes[*child].le_move == Some(le_move))
+    }
+
+    /// Moves the variation one place up among its siblings.
+    pub fn promote_variation(&mut self, node_id: NodeId) {
+        if let Some(parent) = self.nodes[node_id].parent {
+            let siblings = &mut self.nodes[parent].children;
+            let index = siblings.iter().position(|x| *x == node_id).unwrap();
+            if index > 0 {
+                siblings.swap(index, index - 1);
+            }
+        }
+    }
+
+    /// Makes the line leading to the node the main line all the way from the root.
+    pub fn promote_to_mainline(&mut self, node_id: NodeId) {
+        let mut current = node_id;
+        while let Some(parent) = self.nodes[current].parent {
+            let siblings = &mut self.nodes[parent].children;
+            let index = siblings.iter().position(|x| *x == current).unwrap();
+            let promoted = siblings.remove(index);
+            siblings.insert(0, promoted);
+            current = parent;
+        }
+    }
+
+    /// Removes the node and everything after it. Returns the parent, the root cannot be deleted.
+    pub fn delete(&mut self, node_id: NodeId) -> Option<NodeId> {
+        let parent = self.nodes[node_id].parent?;
+        self.nodes[parent]
+            .children
+            .retain(|child| *child != node_id);
+        Some(parent)
+    }
+
+    pub fn is_mainline(&self, node_id: NodeId) -> bool {
+        let mut current = node_id;
+        while let Some(parent) = self.nodes[current].parent {
+            if self.nodes[parent].children[0] != current {
+                return false;
+            }
+            current = parent;
+        }
+        true
+    }
+
+    /// Node ids of the main line, without the root.
+    pub fn mainline(&self) -> Vec<NodeId> {
+        let mut line = Vec::new();
+        let mut current = self.root();
+        while let Some(next) = self.nodes[current].children.first() {
+            line.push(*next);
+            current = *next;
+        }
+        line
+    }
+
+    /// Node ids from the root (included) to the node.
+    pub fn path_to(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut path = vec![node_id];
+        let mut current = node_id;
+        while let Some(parent) = self.nodes[current].parent {
+            path.push(parent);
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    pub fn moves_to(&self, node_id: NodeId) -> Vec<Move> {
+        self.path_to(node_id)
+            .into_iter()
+            .filter_map(|id| self.nodes[id].le_move)
+            .collect()
+    }
+
+    /// Follows the moves from the root, returns None if the line is not in the tree.
+    pub fn find_line(&self, moves: &[Move]) -> Option<NodeId> {
+        let mut current = self.root();
+        for le_move in moves {
+            current = self.find_child(current, *le_move)?;
+        }
+        Some(current)
+    }
+
+    /// Walks the whole tree from the root in depth-first order, main line first.
+    pub fn walk(&self) -> Vec<NodeId> {
+        let mut visited = Vec::new();
+        let mut stack = vec![self.root()];
+        while let Some(node_id) = stack.pop() {
+            visited.push(node_id);
+            stack.extend(self.nodes[node_id].children.iter().rev());
+        }
+        visited
+    }
+}
+
+#[cfg(test)]
+mod test_game_tree {
+    use crate::board::Board;
+    use crate::game_tree::GameTree;
+    use crate::helpers::chess_move;
+
+    fn sample_tree() -> GameTree {
+        let mut tree = GameTree::new(Board::new());
+        let e4 = tree.add_move(tree.root(), chess_move("e2", "e4"));
+        tree.add_move(e4, chess_move("e7", "e5"));
+        let c5 = tree.add_move(e4, chess_move("c7", "c5"));
+        tree.add_move(c5, chess_move("g1", "f3"));
+        tree.add_move(tree.root(), chess_move("d2", "d4"));
+        tree
+    }
+
+    #[test]
+    fn test_add_move_and_variations() {
+        let tree = sample_tree();
+        let root = tree.node(tree.root());
+        assert_eq!(root.children.len(), 2);
+
+        let mainline_san: Vec<&str> = tree
+            .mainline()
+            .into_iter()
+            .map(|id| tree.node(id).san.as_str())
+            .collect();
+        assert_eq!(mainline_san, vec!["e4", "e5"]);
+
+        let mut tree = tree;
+        let e4 = tree.node(tree.root()).children[0];
+        let n_nodes = tree.walk().len();
+        assert_eq!(
+            tree.add_move(e4, chess_move("e7", "e5")),
+            tree.node(e4).children[0]
+        );
+        assert_eq!(tree.walk().len(), n_nodes);
+    }
+
+    #[test]
+    fn test_promote_to_mainline() {
+        let mut tree = sample_tree();
+        let nf3 = tree
+            .find_line(&[
+                chess_move("e2", "e4"),
+                chess_move("c7", "c5"),
+                chess_move("g1", "f3"),
+            ])
+            .unwrap();
+        assert!(!tree.is_mainline(nf3));
+
+        tree.promote_to_mainline(nf3);
+        assert!(tree.is_mainline(nf3));
+        assert_eq!(*tree.mainline().last().unwrap(), nf3);
+    }
+
+    #[test]
+    fn test_promote_variation_and_delete() {
+        let mut tree = sample_tree();
+        let d4 = tree.node(tree.root()).children[1];
+        tree.promote_variation(d4);
+        assert_eq!(tree.node(tree.root()).children[0], d4);
+
+        let e4 = tree.node(tree.root()).children[1];
+        assert_eq!(tree.delete(e4), Some(tree.root()));
+        assert_eq!(tree.node(tree.root()).children, vec![d4]);
+        assert_eq!(tree.walk(), vec![tree.root(), d4]);
+        assert_eq!(tree.delete(tree.root()), None);
+    }
+
+    #[test]
+    fn test_path_and_moves_to() {
+        let tree = sample_tree();
+        let moves = vec![chess_move("e2", "e4"), chess_move("c7", "c5")];
+        let c5 = tree.find_line(&moves).unwrap();
+        assert_eq!(tree.moves_to(c5), moves);
+        assert_eq!(tree.path_to(c5).len(), 3);
+        assert_eq!(
+            tree.node(c5).board.to_fen(),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2"
+        );
+    }
+}
@@ -1,24 +1,51 @@
+use crate::archive::{Archive, ArchiveEntry, GameMetadata, Termination};
 use crate::board::Board;
-use crate::bot::ChessBot;
+#[cfg(feature = "nnue")]
+use crate::bot::Network;
+use crate::bot::{
+    evaluate, format_score, AlphaBetaResult, ChessBot, SearchLine, SkillLevel, Tablebases,
+    MAX_SKILL_LEVEL, MIN_SKILL_LEVEL,
+};
+use crate::clock::{format_time, timeout_result, Clock, TimeControl};
+use crate::game::{Game, GameResult};
+use crate::game_tree::{GameTree, NodeId};
 use crate::gui::utils::*;
-use crate::helpers::{Move, Position};
+use crate::helpers::{Move, Square};
+use crate::notation::{line_to_san, move_to_san};
+use crate::pgn::{movetext_tokens, read_pgn, write_pgn};
 use crate::pieces::{Color, Piece, PieceKind};
+use crate::polyglot::OpeningBook;
+use crate::profile::{profiles_from_env, BotProfile};
 
 use eframe::egui::{
-    self, Align2, Button, CentralPanel, Color32, Context, Image, Layout, Pos2, Rect, RichText,
-    Shape, Ui, Vec2,
+    self, Align2, Button, CentralPanel, Color32, Context, Image, Key, Layout, Modifiers, Pos2,
+    Rect, RichText, Shape, TopBottomPanel, Ui, Vec2,
 };
 use eframe::{self, App, Frame};
 use fnv::FnvHashMap;
+use std::io::Error;
 use std::process::exit;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const TIME_CONTROLS: [(&str, &str); 6] = [
+    ("3+2", "180+2"),
+    ("5 min", "300"),
+    ("5 min, 5 s Bronstein", "300+5b"),
+    ("5 min, 5 s delay", "300+5d"),
+    ("Hourglass 1 min", "*60"),
+    ("40/90+30", "40/5400+30:1800+30"),
+];
 
 pub struct ChessApp<'a> {
     piece_images: FnvHashMap<(PieceKind, Color), Image<'a>>,
-    board: Board,
+    game: Game,
     window_size: f32,
     square_size: f32,
     chosen_piece: Option<Piece>,
-    possible_moves: Vec<Position>,
+    possible_moves: Vec<Square>,
     in_menu: bool,
     in_from_fen: bool,
     in_options: bool,
@@ -29,16 +56,76 @@ pub struct ChessApp<'a> {
     colors: [[f32; 3]; 2],
     player_color: Color,
     chess_bot: ChessBot,
+    tree: GameTree,
+    tree_node: NodeId,
+    analysis_mode: bool,
+    show_captures: bool,
+    show_evaluation: bool,
+    show_engine_lines: bool,
+    /// How many lines the Analyse button asks the engine for.
+    n_engine_lines: usize,
+    /// Lines of the last analysis of the current position and the depth they come from.
+    engine_lines: Vec<SearchLine>,
+    engine_depth: u8,
+    /// The position the lines start from, and what the bot made of them when it played.
+    engine_board: Board,
+    engine_note: String,
+    pgn_string: String,
+    clock: Option<Clock>,
+    archive: Archive,
+    archive_entries: Option<Vec<ArchiveEntry>>,
+    /// Bot personalities to play against, and the one playing, `None` for the default bot.
+    profiles: Vec<BotProfile>,
+    opponent: Option<usize>,
+    /// Tables being generated in the background, handed to the bot when they are done.
+    tablebase_generation: Option<Receiver<Result<Tablebases, Error>>>,
 }
 
 impl<'a> Default for ChessApp<'a> {
     fn default() -> ChessApp<'a> {
         let size = 600.;
         let square_size = size / 8.;
+        let mut chess_bot = ChessBot::with_move_time(Color::Black, Duration::from_secs(2));
+        chess_bot.book = OpeningBook::from_env().unwrap_or_else(|error| {
+            println!("Opening book not loaded: {}", error);
+            None
+        });
+        let tablebases = Tablebases::from_env().unwrap_or_else(|error| {
+            println!("Tablebases not loaded: {}", error);
+            Tablebases::new()
+        });
+        // generating takes a while, so only when asked for and not before the window opens
+        let tablebase_generation = match Tablebases::dir_from_env() {
+            Some(dir) if !tablebases.is_complete() => {
+                let (sender, receiver) = mpsc::channel();
+                thread::spawn(move || {
+                    let _ = sender.send(Tablebases::load_or_generate(dir));
+                });
+                Some(receiver)
+            }
+            _ => None,
+        };
+        if !tablebases.tables().is_empty() {
+            chess_bot.tablebases = Some(Arc::new(tablebases));
+        }
+        #[cfg(feature = "nnue")]
+        {
+            chess_bot.network = Network::from_env()
+                .unwrap_or_else(|error| {
+                    println!("Network not loaded: {}", error);
+                    None
+                })
+                .map(Arc::new);
+        }
+
+        let profiles = profiles_from_env().unwrap_or_else(|error| {
+            println!("Bot profiles not loaded: {}", error);
+            Vec::new()
+        });
 
         ChessApp {
             piece_images: init_assets(square_size),
-            board: Board::new(),
+            game: Game::new(),
             window_size: size,
             square_size,
             chosen_piece: None,
@@ -52,18 +139,50 @@ impl<'a> Default for ChessApp<'a> {
             black_color: [165., 82., 42.],
             colors: [[255., 228., 196.], [165., 82., 42.]],
             player_color: Color::White,
-            chess_bot: ChessBot::new(Color::Black, 4),
+            chess_bot,
+            tree: GameTree::new(Board::new()),
+            tree_node: 0,
+            analysis_mode: false,
+            show_captures: false,
+            show_evaluation: false,
+            show_engine_lines: false,
+            n_engine_lines: 3,
+            engine_lines: Vec::new(),
+            engine_depth: 0,
+            engine_board: Board::new(),
+            engine_note: String::new(),
+            pgn_string: String::from(""),
+            clock: None,
+            archive: Archive::from_env(),
+            archive_entries: None,
+            profiles,
+            opponent: None,
+            tablebase_generation,
         }
     }
 }
 
 impl<'a> App for ChessApp<'a> {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        self.receive_tablebases(ctx);
         ctx.used_rect().set_height(self.window_size);
         ctx.screen_rect().set_width(self.window_size);
 
         let my_frame = egui::containers::Frame::default().fill(Color32::from_rgb(100, 100, 100));
 
+        let in_game = !(self.in_menu || self.in_from_fen || self.in_options);
+        if in_game {
+            TopBottomPanel::bottom("game_controls").show(ctx, |ui| {
+                self.draw_game_controls(ui);
+            });
+            self.handle_takeback_shortcuts(ctx);
+            self.draw_move_tree(ctx);
+            self.draw_archive(ctx);
+            self.draw_evaluation(ctx);
+            self.draw_engine_lines(ctx);
+            self.run_clock(ctx);
+        }
+
         CentralPanel::default().frame(my_frame).show(ctx, |ui| {
             ui.set_min_size(Vec2::new(self.window_size, self.window_size));
             ui.style_mut().text_styles.insert(
@@ -81,42 +200,44 @@ impl<'a> App for ChessApp<'a> {
                 self.draw_board_with_pieces(ui);
                 self.draw_move_selection(ui);
 
-                if self.player_color == self.board.turn {
-                    if let Some(promotion_position) = self.board.promotion_position {
+                if self.analysis_mode || self.player_color == self.game.board.turn {
+                    if let Some(promotion_position) = self.game.board.promotion_position {
                         self.do_promotion_stuff(promotion_position, ui, ctx);
-                    } else if let Some(pos) = ctx.input(|i| i.pointer.press_origin()) {
-                        let click_position = convert_click_to_board_position(
-                            pos,
-                            self.player_color,
-                            self.square_size,
-                        );
+                    } else if let Some(click_position) =
+                        ctx.input(|i| i.pointer.press_origin()).and_then(|pos| {
+                            convert_click_to_board_position(
+                                pos,
+                                self.player_color,
+                                self.square_size,
+                            )
+                        })
+                    {
                         match self.chosen_piece {
-                            Some(piece) => {
-                                if self.possible_moves.contains(&click_position) {
-                                    self.board
-                                        .bust_a_move(Move::new(piece.position, click_position));
-                                    if self.board.promotion_position.is_none() {
-                                        self.end_of_turn_ceremonies();
-                                    }
-                                    // // The ui is so damn fast that without sleep, it uses the same click multiple times
-                                    // sleep(Duration::from_secs_f32(0.3));
-                                } else {
-                                    self.select_piece_and_update_moves(&click_position);
+                            Some(piece) if self.possible_moves.contains(&click_position) => {
+                                self.game
+                                    .make_move(Move::new(piece.position, click_position));
+                                if self.game.board.promotion_position.is_none() {
+                                    self.end_of_turn_ceremonies();
                                 }
+                                // // The ui is so damn fast that without sleep, it uses the same click multiple times
+                                // sleep(Duration::from_secs_f32(0.3));
                             }
-                            None => {
+                            _ => {
                                 self.select_piece_and_update_moves(&click_position);
                             }
                         }
                     }
                 } else {
-                    let bot_move = self.chess_bot.get_bot_move(&self.board);
-                    self.board.bust_a_move(bot_move);
-                    if self.board.promotion_position.is_some() {
-                        let new_piece =
-                            Some(Piece::new(self.board.turn, PieceKind::Q, bot_move.to));
-                        self.board.board[bot_move.to.x][bot_move.to.y] = new_piece;
-                        self.board.promotion_position = None;
+                    let bot_move = match &self.clock {
+                        Some(clock) => self
+                            .chess_bot
+                            .get_bot_move_with_clock(&self.game.board, clock),
+                        None => self.chess_bot.get_bot_move(&self.game.board),
+                    };
+                    self.show_bot_move(&bot_move);
+                    self.game.make_move(bot_move.le_move);
+                    if self.game.board.promotion_position.is_some() {
+                        self.game.promote(PieceKind::Q);
                     }
                     self.end_of_turn_ceremonies();
                 }
@@ -198,7 +319,9 @@ impl<'a> ChessApp<'a> {
             match Board::from_fen(self.fen_string.trim()) {
                 Ok(board) => {
                     board.print_board(&board.turn);
-                    self.board = board;
+                    self.tree = GameTree::new(board.clone());
+                    self.tree_node = self.tree.root();
+                    self.game = Game::from_board(board);
                     self.in_from_fen = false;
                 }
                 Err(_) => println!("Invalid FEN string"),
@@ -293,6 +416,347 @@ impl<'a> ChessApp<'a> {
         );
     }
 
+    fn draw_game_controls(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.analysis_mode, "Analysis");
+            ui.checkbox(&mut self.show_captures, "Captures");
+            ui.checkbox(&mut self.show_evaluation, "Eval");
+            ui.checkbox(&mut self.show_engine_lines, "Lines");
+            if ui
+                .add_enabled(self.game.can_undo(), Button::new("Takeback"))
+                .on_hover_text("Ctrl+Z or Left arrow")
+                .clicked()
+            {
+                self.takeback();
+            }
+            if ui
+                .add_enabled(self.game.can_redo(), Button::new("Redo"))
+                .on_hover_text("Ctrl+Y or Right arrow")
+                .clicked()
+            {
+                self.redo();
+            }
+            self.draw_archive_buttons(ui);
+        });
+        self.draw_clock_controls(ui);
+        self.draw_opponent_controls(ui);
+        self.draw_skill_controls(ui);
+    }
+
+    /// Picks the bot personality to play against, when there are profiles.
+    fn draw_opponent_controls(&mut self, ui: &mut Ui) {
+        if self.profiles.is_empty() {
+            return;
+        }
+        let name = |opponent: Option<usize>, profiles: &[BotProfile]| match opponent {
+            Some(index) => profiles[index].name.clone(),
+            None => "Default".to_owned(),
+        };
+        let mut opponent = self.opponent;
+        egui::ComboBox::from_label("Opponent")
+            .selected_text(name(opponent, &self.profiles))
+            .show_ui(ui, |ui| {
+                for choice in [None].into_iter().chain((0..self.profiles.len()).map(Some)) {
+                    ui.selectable_value(&mut opponent, choice, name(choice, &self.profiles));
+                }
+            });
+        if opponent != self.opponent {
+            self.set_opponent(opponent);
+        }
+    }
+
+    /// Replaces the bot with a fresh one set up by the profile, keeping its color, tablebases
+    /// and network.
+    fn set_opponent(&mut self, opponent: Option<usize>) {
+        let mut bot = ChessBot::with_move_time(self.chess_bot.color, Duration::from_secs(2));
+        bot.book = OpeningBook::from_env().unwrap_or_else(|error| {
+            println!("Opening book not loaded: {}", error);
+            None
+        });
+        bot.tablebases = self.chess_bot.tablebases.clone();
+        #[cfg(feature = "nnue")]
+        {
+            bot.network = self.chess_bot.network.clone();
+        }
+        if let Some(profile) = opponent.map(|index| &self.profiles[index]) {
+            if let Err(error) = profile.apply(&mut bot) {
+                println!("Profile {} not loaded: {}", profile.id, error);
+                return;
+            }
+        }
+        self.chess_bot = bot;
+        self.opponent = opponent;
+    }
+
+    /// Level of the bot, from a beginner's sparring partner to full strength.
+    fn draw_skill_controls(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let mut level = self.chess_bot.skill.level();
+            let slider =
+                egui::Slider::new(&mut level, MIN_SKILL_LEVEL..=MAX_SKILL_LEVEL).text("Bot level");
+            if ui.add(slider).changed() {
+                self.chess_bot.skill = SkillLevel::new(level);
+            }
+            let strength = if self.chess_bot.skill.is_full_strength() {
+                "full strength".to_owned()
+            } else {
+                format!("~{} Elo", self.chess_bot.skill.elo())
+            };
+            ui.label(strength);
+        });
+    }
+
+    fn draw_archive_buttons(&mut self, ui: &mut Ui) {
+        if ui.button("Save").clicked() {
+            self.save_game(None, Termination::Unterminated);
+        }
+        if ui.button("Games").clicked() {
+            match self.archive.list() {
+                Ok(entries) => self.archive_entries = Some(entries),
+                Err(error) => println!("Could not read the game archive: {}", error),
+            }
+        }
+    }
+
+    fn draw_clock_controls(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let selected = match &self.clock {
+                Some(clock) => clock.time_control.to_string(),
+                None => String::from("No clock"),
+            };
+            let mut chosen: Option<Option<&str>> = None;
+            // the time control can only be changed before the first move
+            ui.add_enabled_ui(!self.game.can_undo(), |ui| {
+                egui::ComboBox::from_label("Time control")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        if ui
+                            .selectable_label(self.clock.is_none(), "No clock")
+                            .clicked()
+                        {
+                            chosen = Some(None);
+                        }
+                        for (label, time_control) in TIME_CONTROLS {
+                            if ui.selectable_label(false, label).clicked() {
+                                chosen = Some(Some(time_control));
+                            }
+                        }
+                    });
+            });
+            if let Some(time_control) = chosen {
+                self.clock = time_control
+                    .and_then(|time_control| time_control.parse::<TimeControl>().ok())
+                    .map(Clock::new);
+            }
+
+            if let Some(clock) = &self.clock {
+                for color in [Color::White, Color::Black] {
+                    let time = RichText::new(format!(
+                        "{:?} {}",
+                        color,
+                        format_time(clock.remaining(color))
+                    ));
+                    if clock.is_running() && clock.turn == color {
+                        ui.label(time.strong());
+                    } else {
+                        ui.label(time);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Hands the bot the tables generated in the background once they are done.
+    fn receive_tablebases(&mut self, ctx: &Context) {
+        let Some(receiver) = &self.tablebase_generation else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(Ok(tablebases)) => {
+                self.chess_bot.tablebases = Some(Arc::new(tablebases));
+                self.tablebase_generation = None;
+            }
+            Ok(Err(error)) => {
+                println!("Tablebases not generated: {}", error);
+                self.tablebase_generation = None;
+            }
+            Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(500)),
+            Err(TryRecvError::Disconnected) => self.tablebase_generation = None,
+        }
+    }
+
+    /// Starts the clock once the game is on and ends the game when a flag falls. Clocks don't
+    /// run while analysing.
+    fn run_clock(&mut self, ctx: &Context) {
+        if self.analysis_mode {
+            return;
+        }
+        if let Some(clock) = &mut self.clock {
+            if !clock.is_running() {
+                clock.start(self.game.board.turn);
+            }
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        self.check_flag();
+    }
+
+    fn check_flag(&mut self) {
+        if let Some(flagged) = self.clock.as_ref().and_then(|clock| clock.flagged()) {
+            let result = timeout_result(&self.game.board, flagged);
+            println!("{:?} ran out of time, {}", flagged, result);
+            self.end_game(Some(result), Termination::TimeForfeit);
+        }
+    }
+
+    fn handle_takeback_shortcuts(&mut self, ctx: &Context) {
+        let (undo_pressed, redo_pressed) = ctx.input_mut(|i| {
+            (
+                i.consume_key(Modifiers::COMMAND, Key::Z)
+                    | i.consume_key(Modifiers::NONE, Key::ArrowLeft),
+                i.consume_key(Modifiers::COMMAND, Key::Y)
+                    | i.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z)
+                    | i.consume_key(Modifiers::NONE, Key::ArrowRight),
+            )
+        });
+        if undo_pressed {
+            self.takeback();
+        } else if redo_pressed {
+            self.redo();
+        }
+    }
+
+    fn is_players_move(&self) -> bool {
+        (self.analysis_mode || self.game.board.turn == self.player_color)
+            && self.game.board.promotion_position.is_none()
+    }
+
+    /// Takes back moves until it is the player's turn again, so the bot's reply goes too.
+    fn takeback(&mut self) {
+        while self.game.undo() {
+            if self.is_players_move() {
+                break;
+            }
+        }
+        self.after_position_change();
+    }
+
+    fn redo(&mut self) {
+        while self.game.redo() {
+            if self.is_players_move() {
+                break;
+            }
+        }
+        self.after_position_change();
+    }
+
+    fn after_position_change(&mut self) {
+        self.tree_node = self
+            .tree
+            .find_line(&self.game.moves())
+            .unwrap_or(self.tree.root());
+        self.chosen_piece = None;
+        self.possible_moves = Vec::new();
+        self.engine_lines.clear();
+        self.engine_note.clear();
+    }
+
+    /// Replays the moves leading to the node, so takeback keeps working from there.
+    fn jump_to_node(&mut self, node_id: NodeId) {
+        let mut game = Game::from_board(self.tree.node(self.tree.root()).board.clone());
+        for le_move in self.tree.moves_to(node_id) {
+            game.make_move(le_move);
+            game.finish_turn();
+        }
+        self.game = game;
+        self.after_position_change();
+    }
+
+    fn draw_move_tree(&mut self, ctx: &Context) {
+        let mut clicked_node: Option<NodeId> = None;
+
+        egui::Window::new("Moves")
+            .default_pos(Pos2::new(self.window_size, 0.))
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("|<").clicked() {
+                        clicked_node = Some(self.tree.root());
+                    }
+                    if ui.button("<").clicked() {
+                        clicked_node = self.tree.node(self.tree_node).parent;
+                    }
+                    if ui.button(">").clicked() {
+                        clicked_node = self.tree.node(self.tree_node).children.first().copied();
+                    }
+                    if ui.button(">|").clicked() {
+                        let mut node_id = self.tree_node;
+                        while let Some(next) = self.tree.node(node_id).children.first() {
+                            node_id = *next;
+                        }
+                        clicked_node = Some(node_id);
+                    }
+                    if ui.button("Promote").clicked() {
+                        self.tree.promote_to_mainline(self.tree_node);
+                    }
+                    if ui.button("Delete").clicked() {
+                        clicked_node = self.tree.delete(self.tree_node);
+                    }
+                });
+
+                egui::ScrollArea::vertical()
+                    .max_height(self.window_size / 3.)
+                    .show(ui, |ui| {
+                        ui.horizontal_wrapped(|ui| {
+                            for (text, node_id) in movetext_tokens(&self.tree) {
+                                match node_id {
+                                    Some(node_id) => {
+                                        let label = if self.tree.is_mainline(node_id) {
+                                            RichText::new(text).strong()
+                                        } else {
+                                            RichText::new(text)
+                                        };
+                                        if ui
+                                            .selectable_label(node_id == self.tree_node, label)
+                                            .clicked()
+                                        {
+                                            clicked_node = Some(node_id);
+                                        }
+                                    }
+                                    None => {
+                                        ui.label(RichText::new(text).weak());
+                                    }
+                                }
+                            }
+                        });
+                    });
+
+                ui.separator();
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.pgn_string)
+                        .desired_rows(3)
+                        .hint_text("PGN"),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Load PGN").clicked() {
+                        match read_pgn(&self.pgn_string) {
+                            Ok(tree) => {
+                                self.tree = tree;
+                                clicked_node = Some(self.tree.root());
+                            }
+                            Err(error) => println!("{}", error),
+                        }
+                    }
+                    if ui.button("Copy PGN").clicked() {
+                        ui.output_mut(|o| o.copied_text = write_pgn(&self.tree));
+                    }
+                });
+            });
+
+        if let Some(node_id) = clicked_node {
+            self.jump_to_node(node_id);
+        }
+    }
+
     fn draw_board_with_pieces(&self, ui: &mut Ui) {
         for row in 0..8 {
             for col in 0..8 {
@@ -319,18 +783,13 @@ impl<'a> ChessApp<'a> {
                 let square = make_square(rect, square_color, true);
                 ui.painter().add(square);
 
-                let mut board_x = col as usize;
-                let mut board_y = row as usize;
-
-                if self.player_color == Color::White {
-                    board_x = 7 - board_x;
-                    board_y = 7 - board_y;
-                }
+                let square = if self.player_color == Color::White {
+                    Square::from_file_rank(col as u8, 7 - row as u8)
+                } else {
+                    Square::from_file_rank(7 - col as u8, row as u8)
+                };
 
-                if let Some(piece) = self
-                    .board
-                    .get_piece_from_position(&Position::new(board_x, board_y))
-                {
+                if let Some(piece) = self.game.board.get_piece_from_position(&square) {
                     let piece_image = self.piece_images.get(&(piece.kind, piece.color)).unwrap();
                     piece_image.paint_at(ui, rect);
                 }
@@ -339,6 +798,17 @@ impl<'a> ChessApp<'a> {
     }
 
     fn draw_move_selection(&mut self, ui: &mut Ui) {
+        if self.show_captures {
+            for capture in self.game.board.generate_captures() {
+                let capture_pos =
+                    convert_board_position_to_ui(&capture.to, self.player_color, self.square_size);
+                let capture_rect =
+                    Rect::from_min_size(capture_pos, Vec2::new(self.square_size, self.square_size));
+                ui.painter()
+                    .add(make_square(capture_rect, Color32::RED, false));
+            }
+        }
+
         if let Some(piece) = self.chosen_piece {
             let piece_pos =
                 convert_board_position_to_ui(&piece.position, self.player_color, self.square_size);
@@ -363,7 +833,7 @@ impl<'a> ChessApp<'a> {
         }
     }
 
-    fn select_piece_and_update_moves(&mut self, position: &Position) {
+    fn select_piece_and_update_moves(&mut self, position: &Square) {
         self.select_piece(position);
         if let Some(piece) = self.chosen_piece {
             self.get_possible_moves(piece);
@@ -372,10 +842,10 @@ impl<'a> ChessApp<'a> {
         }
     }
 
-    fn select_piece(&mut self, position: &Position) -> Option<Piece> {
-        let piece_option = self.board.get_piece_from_position(position);
+    fn select_piece(&mut self, position: &Square) -> Option<Piece> {
+        let piece_option = self.game.board.get_piece_from_position(position);
         if let Some(piece) = piece_option {
-            if piece.color == self.board.turn {
+            if piece.color == self.game.board.turn {
                 self.chosen_piece = Some(*piece);
                 return Some(*piece);
             }
@@ -385,27 +855,31 @@ impl<'a> ChessApp<'a> {
     }
 
     fn get_possible_moves(&mut self, chosen_piece: Piece) {
-        let positions = self.board.get_all_positions();
-        let friendly_positions = &positions[(self.board.turn == Color::Black) as usize];
-        let opponent_positions = &positions[(self.board.turn == Color::White) as usize];
+        let board = &self.game.board;
+        let positions = board.get_all_positions();
+        let friendly_positions = &positions[(board.turn == Color::Black) as usize];
+        let opponent_positions = &positions[(board.turn == Color::White) as usize];
         self.possible_moves =
-            chosen_piece.get_piece_moves(friendly_positions, opponent_positions, &self.board);
+            chosen_piece.get_piece_moves(friendly_positions, opponent_positions, board);
     }
 
     fn set_values_at_the_end_of_turn(&mut self) {
         self.chosen_piece = None;
-        self.board.set_values_at_the_end(true);
+        self.game.finish_turn();
+        if let Some(last_move) = self.game.last_move() {
+            self.tree_node = self.tree.add_move(self.tree_node, last_move);
+        }
         self.possible_moves = Vec::new();
     }
 
-    fn do_promotion_stuff(&mut self, promotion_position: Position, ui: &mut Ui, ctx: &Context) {
+    fn do_promotion_stuff(&mut self, promotion_position: Square, ui: &mut Ui, ctx: &Context) {
         let ui_pos =
             convert_board_position_to_ui(&promotion_position, self.player_color, self.square_size);
         let pieces = [
-            Piece::new(self.board.turn, PieceKind::Q, promotion_position),
-            Piece::new(self.board.turn, PieceKind::R, promotion_position),
-            Piece::new(self.board.turn, PieceKind::N, promotion_position),
-            Piece::new(self.board.turn, PieceKind::B, promotion_position),
+            Piece::new(self.game.board.turn, PieceKind::Q, promotion_position),
+            Piece::new(self.game.board.turn, PieceKind::R, promotion_position),
+            Piece::new(self.game.board.turn, PieceKind::N, promotion_position),
+            Piece::new(self.game.board.turn, PieceKind::B, promotion_position),
         ];
 
         for (i, piece) in pieces.iter().enumerate() {
@@ -420,16 +894,13 @@ impl<'a> ChessApp<'a> {
             let piece_image = self.piece_images.get(&(piece.kind, piece.color)).unwrap();
             piece_image.paint_at(ui, rect);
         }
-        if let Some(click_pos) = ctx.input(|i| i.pointer.press_origin()) {
-            let click_position =
-                convert_click_to_board_position(click_pos, self.player_color, self.square_size);
-
-            if (promotion_position.x == click_position.x)
-                & (promotion_position.y.abs_diff(click_position.y) <= 3)
-            {
-                let piece = pieces[promotion_position.y.abs_diff(click_position.y)];
-                self.board.board[promotion_position.x][promotion_position.y] = Some(piece);
-                self.board.promotion_position = None;
+        if let Some(click_position) = ctx.input(|i| i.pointer.press_origin()).and_then(|pos| {
+            convert_click_to_board_position(pos, self.player_color, self.square_size)
+        }) {
+            let rank_distance = promotion_position.rank().abs_diff(click_position.rank());
+            if (promotion_position.file() == click_position.file()) & (rank_distance <= 3) {
+                let piece = pieces[rank_distance as usize];
+                self.game.promote(piece.kind);
                 self.end_of_turn_ceremonies();
             }
         }
@@ -438,27 +909,206 @@ impl<'a> ChessApp<'a> {
     fn end_of_turn_ceremonies(&mut self) {
         self.set_values_at_the_end_of_turn();
 
-        if self.board.no_possible_moves() {
-            if self.board.is_king_in_check(&self.board.turn) {
-                println!("Checkmate! {:?} won", self.board.next_turn);
+        // no game to end while analysing, the position can be navigated away from
+        if self.analysis_mode {
+            println!("{}", self.game.board.to_fen());
+            return;
+        }
+
+        if let Some(clock) = &mut self.clock {
+            clock.turn = self.game.board.next_turn;
+            clock.press();
+        }
+        self.check_flag();
+
+        if self.game.board.no_possible_moves() {
+            if self.game.board.is_king_in_check(&self.game.board.turn) {
+                println!("Checkmate! {:?} won", self.game.board.next_turn);
+                self.end_game(
+                    Some(GameResult::Win(self.game.board.next_turn)),
+                    Termination::Checkmate,
+                );
             } else {
                 println!("Draw!");
+                self.end_game(Some(GameResult::Draw), Termination::Stalemate);
             }
-            self.board.write_to_file();
-            exit(0);
         }
 
-        if self.board.n_half_moves > 7 && self.board.is_repetition(self.board.n_half_moves) {
+        if self.game.board.is_threefold_repetition() {
             println!("Repetition draw. Y'all suck!");
-            self.board.write_to_file();
-            exit(0);
+            self.end_game(Some(GameResult::Draw), Termination::Repetition);
         }
 
-        if self.board.n_half_moves >= 100 || self.board.is_material_draw() {
+        if self.game.board.n_half_moves >= 100 {
             println!("Tis a draw");
-            self.board.write_to_file();
-            exit(0);
+            self.end_game(Some(GameResult::Draw), Termination::FiftyMoves);
+        }
+        if self.game.board.is_material_draw() {
+            println!("Tis a draw");
+            self.end_game(Some(GameResult::Draw), Termination::InsufficientMaterial);
+        }
+        println!("{}", self.game.board.to_fen());
+    }
+
+    fn game_metadata(&self, result: Option<GameResult>, termination: Termination) -> GameMetadata {
+        let player_name = |color: Color| -> String {
+            if self.analysis_mode || color == self.player_color {
+                String::from("Player")
+            } else {
+                String::from("ChessBot")
+            }
+        };
+        GameMetadata {
+            white: player_name(Color::White),
+            black: player_name(Color::Black),
+            bot_depth: (!self.analysis_mode).then_some(self.chess_bot.max_depth),
+            result,
+            termination,
+            time_control: self.clock.as_ref().map(|clock| clock.time_control.clone()),
+        }
+    }
+
+    fn save_game(&mut self, result: Option<GameResult>, termination: Termination) {
+        let metadata = self.game_metadata(result, termination);
+        match self.archive.save(&self.tree, &metadata) {
+            Ok(entry) => {
+                println!("Saved game {} to {}", entry.id, self.archive.dir.display());
+                if let Some(entries) = &mut self.archive_entries {
+                    entries.push(entry);
+                }
+            }
+            Err(error) => println!("Could not save the game: {}", error),
+        }
+    }
+
+    fn end_game(&mut self, result: Option<GameResult>, termination: Termination) {
+        self.save_game(result, termination);
+        exit(0);
+    }
+
+    fn draw_evaluation(&mut self, ctx: &Context) {
+        let evaluation = evaluate(&self.game.board);
+        egui::Window::new("Evaluation")
+            .open(&mut self.show_evaluation)
+            .show(ctx, |ui| {
+                ui.label(RichText::new(evaluation.to_string()).monospace());
+            });
+    }
+
+    /// Searches the position for about a second with the bot's tables and network.
+    fn analyse(&mut self) {
+        let board = &self.game.board;
+        let mut bot = ChessBot::with_move_time(board.turn, Duration::from_secs(1));
+        bot.tablebases = self.chess_bot.tablebases.clone();
+        #[cfg(feature = "nnue")]
+        {
+            bot.network = self.chess_bot.network.clone();
+        }
+        bot.multi_pv = self.n_engine_lines;
+        let result = bot.search(board);
+        self.engine_board = board.clone();
+        self.engine_note.clear();
+        self.engine_lines = result.lines;
+        self.engine_depth = result.depth;
+    }
+
+    /// Shows the lines the bot chose its move from in the engine lines window.
+    fn show_bot_move(&mut self, bot_move: &AlphaBetaResult) {
+        let board = &self.game.board;
+        let san = move_to_san(board, bot_move.le_move);
+        self.engine_note = if bot_move.from_book {
+            format!("Book move {}", san)
+        } else if bot_move.chosen_line > 0 {
+            format!(
+                "Played {} instead of {}",
+                san,
+                move_to_san(board, bot_move.lines[0].pv[0])
+            )
+        } else {
+            format!("Played {}", san)
+        };
+        self.engine_board = board.clone();
+        self.engine_lines = bot_move.lines.clone();
+        self.engine_depth = bot_move.depth;
+    }
+
+    /// The best lines of the current position, best first, each with its score.
+    fn draw_engine_lines(&mut self, ctx: &Context) {
+        let mut open = self.show_engine_lines;
+        let mut analyse = false;
+        egui::Window::new("Engine lines")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Lines");
+                    ui.add(egui::DragValue::new(&mut self.n_engine_lines).clamp_range(1..=8));
+                    analyse = ui.button("Analyse").clicked();
+                });
+                if !self.engine_note.is_empty() {
+                    ui.label(&self.engine_note);
+                }
+                if !self.engine_lines.is_empty() {
+                    ui.label(format!("Depth {}", self.engine_depth));
+                }
+                for line in &self.engine_lines {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(format_score(line.score)).monospace());
+                        ui.label(line_to_san(&self.engine_board, &line.pv));
+                    });
+                }
+            });
+        self.show_engine_lines = open;
+        if analyse {
+            self.analyse();
+        }
+    }
+
+    /// Window with the saved games, opened by the Games button. Loaded games open in analysis
+    /// mode at their last move.
+    fn draw_archive(&mut self, ctx: &Context) {
+        let mut open = self.archive_entries.is_some();
+        let mut to_load: Option<String> = None;
+        if let Some(entries) = &self.archive_entries {
+            egui::Window::new("Games")
+                .open(&mut open)
+                .default_width(350.)
+                .show(ctx, |ui| {
+                    ui.label(format!("Archive: {}", self.archive.dir.display()));
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        if entries.is_empty() {
+                            ui.label("No saved games");
+                        }
+                        for entry in entries.iter().rev() {
+                            ui.horizontal(|ui| {
+                                if ui.button("Load").clicked() {
+                                    to_load = Some(entry.id.clone());
+                                }
+                                ui.label(entry.to_string());
+                            });
+                        }
+                    });
+                });
+        }
+        if !open {
+            self.archive_entries = None;
+        }
+
+        if let Some(id) = to_load {
+            match self.archive.load(&id) {
+                Ok(tree) => {
+                    self.tree = tree;
+                    self.analysis_mode = true;
+                    self.clock = None;
+                    let last_node = self
+                        .tree
+                        .mainline()
+                        .last()
+                        .copied()
+                        .unwrap_or(self.tree.root());
+                    self.jump_to_node(last_node);
+                }
+                Err(error) => println!("Could not load game {}: {}", id, error),
+            }
         }
-        println!("{}", self.board.to_fen());
     }
 }
@@ -1,4 +1,4 @@
-use crate::helpers::Position;
+use crate::helpers::Square;
 use crate::pieces::{Color, PieceKind};
 use eframe::egui::{
     Color32, FontId, Image, Pos2, Rect, RichText, Rounding, Shape, Slider, Stroke, TextStyle, Ui,
@@ -76,19 +76,22 @@ pub fn convert_click_to_board_position(
     click_position: Pos2,
     player_color: Color,
     square_size: f32,
-) -> Position {
+) -> Option<Square> {
+    if (click_position.x < 0.) | (click_position.y < 0.) {
+        return None;
+    }
     let x = (click_position.x / square_size) as i32;
     let y = (click_position.y / square_size) as i32;
 
     if player_color == Color::White {
-        return Position::get_valid_position(7 - x, 7 - y).unwrap();
+        return Square::get_valid_square(x, 7 - y);
     }
 
-    Position::get_valid_position(x, y).unwrap()
+    Square::get_valid_square(7 - x, y)
 }
 
 pub fn convert_board_position_to_ui(
-    position: &Position,
+    position: &Square,
     player_color: Color,
     square_size: f32,
 ) -> Pos2 {
@@ -96,11 +99,11 @@ pub fn convert_board_position_to_ui(
     let y: f32;
 
     if player_color == Color::White {
-        x = (7 - position.x) as f32 * square_size;
-        y = (7 - position.y) as f32 * square_size;
+        x = position.file() as f32 * square_size;
+        y = (7 - position.rank()) as f32 * square_size;
     } else {
-        x = position.x as f32 * square_size;
-        y = position.y as f32 * square_size;
+        x = (7 - position.file()) as f32 * square_size;
+        y = position.rank() as f32 * square_size;
     }
     Pos2::new(x, y)
 }
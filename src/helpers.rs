@@ -1,33 +1,82 @@
-use std::str::from_utf8;
+use crate::pieces::PieceKind;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Position {
-    pub x: usize,
-    pub y: usize,
-}
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+/// Square of the board indexed the usual way, a1 = 0, b1 = 1, ..., h1 = 7, a2 = 8, ..., h8 = 63.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Square(u8);
+
+impl Square {
+    pub fn new(index: u8) -> Square {
+        assert!(index < 64, "Square index out of the board: {}", index);
+        Square(index)
+    }
 
-impl Position {
-    pub fn new(x: usize, y: usize) -> Position {
-        Position { x, y }
+    pub fn from_file_rank(file: u8, rank: u8) -> Square {
+        assert!(file < 8 && rank < 8, "Square out of the board");
+        Square(rank * 8 + file)
     }
 
-    pub fn get_valid_position(x: i32, y: i32) -> Option<Position> {
-        if (0..8).contains(&x) & (0..8).contains(&y) {
-            return Some(Position::new(x as usize, y as usize));
+    pub fn get_valid_square(file: i32, rank: i32) -> Option<Square> {
+        if (0..8).contains(&file) & (0..8).contains(&rank) {
+            return Some(Square::from_file_rank(file as u8, rank as u8));
         }
         None
     }
 
-    pub fn get_x_y_as_int(&self) -> (i32, i32) {
-        (self.x as i32, self.y as i32)
+    pub fn index(&self) -> usize {
+        self.0 as usize
     }
 
-    pub fn get_as_chess_string(&self) -> String {
-        let mut string_position = "".to_owned();
-        string_position.push_str(from_utf8(&[(104 - self.x) as u8]).unwrap());
-        string_position.push_str(from_utf8(&[49 + self.y as u8]).unwrap());
+    /// 0 is the a-file, 7 is the h-file.
+    pub fn file(&self) -> u8 {
+        self.0 % 8
+    }
+
+    /// 0 is the first rank, 7 is the eighth rank.
+    pub fn rank(&self) -> u8 {
+        self.0 / 8
+    }
 
-        string_position
+    pub fn offset(&self, file_step: i32, rank_step: i32) -> Option<Square> {
+        Square::get_valid_square(
+            self.file() as i32 + file_step,
+            self.rank() as i32 + rank_step,
+        )
+    }
+
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            (b'a' + self.file()) as char,
+            (b'1' + self.rank()) as char
+        )
+    }
+}
+
+impl FromStr for Square {
+    type Err = Error;
+
+    fn from_str(coord: &str) -> Result<Square, Error> {
+        let invalid_square_error =
+            Error::new(ErrorKind::InvalidInput, format!("Invalid square {}", coord));
+        let chars: Vec<char> = coord.trim().chars().collect();
+        if chars.len() != 2 {
+            return Err(invalid_square_error);
+        }
+        let file = chars[0].to_ascii_lowercase() as i32 - 'a' as i32;
+        let rank = chars[1] as i32 - '1' as i32;
+
+        Square::get_valid_square(file, rank).ok_or(invalid_square_error)
     }
 }
 
@@ -38,13 +87,26 @@ pub struct Direction {
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Move {
-    pub from: Position,
-    pub to: Position,
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceKind>,
 }
 
 impl Move {
-    pub fn new(from: Position, to: Position) -> Move {
-        Move { from, to }
+    pub fn new(from: Square, to: Square) -> Move {
+        Move {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+
+    pub fn new_promotion(from: Square, to: Square, promotion: PieceKind) -> Move {
+        Move {
+            from,
+            to,
+            promotion: Some(promotion),
+        }
     }
 }
 
@@ -53,3 +115,46 @@ impl Direction {
         Direction { x, y }
     }
 }
+
+/// A move between two squares given by name, for tests.
+#[cfg(test)]
+pub fn chess_move(from: &str, to: &str) -> Move {
+    Move::new(from.parse().unwrap(), to.parse().unwrap())
+}
+
+#[cfg(test)]
+mod test_helpers {
+    use crate::helpers::Square;
+
+    #[test]
+    fn test_square_indexing() {
+        let square = Square::from_file_rank(4, 3);
+        assert_eq!(square.index(), 28);
+        assert_eq!(square.file(), 4);
+        assert_eq!(square.rank(), 3);
+        assert_eq!(Square::new(0).to_string(), "a1");
+        assert_eq!(Square::new(7).to_string(), "h1");
+        assert_eq!(Square::new(63).to_string(), "h8");
+    }
+
+    #[test]
+    fn test_square_from_str() {
+        assert_eq!(
+            "e4".parse::<Square>().unwrap(),
+            Square::from_file_rank(4, 3)
+        );
+        assert_eq!("A8".parse::<Square>().unwrap(), Square::new(56));
+        assert!("i1".parse::<Square>().is_err());
+        assert!("a9".parse::<Square>().is_err());
+        assert!("-".parse::<Square>().is_err());
+        assert!("e44".parse::<Square>().is_err());
+    }
+
+    #[test]
+    fn test_square_offset() {
+        let square: Square = "b1".parse().unwrap();
+        assert_eq!(square.offset(1, 2), Some("c3".parse().unwrap()));
+        assert_eq!(square.offset(-2, 1), None);
+        assert_eq!(square.offset(0, -1), None);
+    }
+}
@@ -1,18 +1,39 @@
 #![allow(dead_code)]
+mod archive;
 mod board;
+mod book_builder;
 mod bot;
+mod cli;
+mod clock;
+mod epd;
+mod game;
+mod game_tree;
 mod gui;
 mod helpers;
 mod moves;
+mod notation;
+mod pgn;
 mod pieces;
+mod polyglot;
+mod profile;
 mod utils;
+mod zobrist;
 
 use crate::gui::ChessApp;
 
 use eframe::egui;
 
 fn main() -> Result<(), eframe::Error> {
-    let window_size = (600., 600.);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(message) = cli::run(&args) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let window_size = (600., 670.);
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size(window_size),
         // .with_resizable(false),
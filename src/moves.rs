@@ -1,5 +1,5 @@
 use crate::board::Board;
-use crate::helpers::{Direction, Position};
+use crate::helpers::{Direction, Square};
 use crate::pieces::{Color, Piece, PieceKind};
 
 // TODO: This needs to change to constants so it can be fastaaaah
@@ -23,24 +23,16 @@ fn rook_directions() -> Vec<Direction> {
 
 fn get_straight_moves(
     directions: Vec<Direction>,
-    piece_position: &Position,
-    friendly_positions: &[Position],
-    opponent_positions: &[Position],
-) -> Vec<Vec<Position>> {
-    let mut allowed_moves: Vec<Vec<Position>> = Vec::new();
-    let (current_x, current_y) = piece_position.get_x_y_as_int();
-    let max_step = [current_x, 7 - current_x, current_y, 7 - current_y]
-        .into_iter()
-        .max()
-        .unwrap();
+    piece_position: &Square,
+    friendly_positions: &[Square],
+    opponent_positions: &[Square],
+) -> Vec<Vec<Square>> {
+    let mut allowed_moves: Vec<Vec<Square>> = Vec::new();
 
     for direction in directions.iter() {
-        let mut moves_in_one_direction: Vec<Position> = Vec::new();
-        for step in 1..=max_step {
-            if let Some(position) = Position::get_valid_position(
-                current_x + step * direction.x,
-                current_y + step * direction.y,
-            ) {
+        let mut moves_in_one_direction: Vec<Square> = Vec::new();
+        for step in 1..8 {
+            if let Some(position) = piece_position.offset(step * direction.x, step * direction.y) {
                 if friendly_positions.contains(&position) {
                     break;
                 } else if opponent_positions.contains(&position) {
@@ -59,10 +51,10 @@ fn get_straight_moves(
 }
 
 pub fn get_rook_moves(
-    piece_position: &Position,
-    friendly_positions: &[Position],
-    opponent_positions: &[Position],
-) -> Vec<Position> {
+    piece_position: &Square,
+    friendly_positions: &[Square],
+    opponent_positions: &[Square],
+) -> Vec<Square> {
     get_straight_moves(
         rook_directions(),
         piece_position,
@@ -75,10 +67,10 @@ pub fn get_rook_moves(
 }
 
 pub fn get_bishop_moves(
-    piece_position: &Position,
-    friendly_positions: &[Position],
-    opponent_positions: &[Position],
-) -> Vec<Position> {
+    piece_position: &Square,
+    friendly_positions: &[Square],
+    opponent_positions: &[Square],
+) -> Vec<Square> {
     get_straight_moves(
         bishop_directions(),
         piece_position,
@@ -91,10 +83,10 @@ pub fn get_bishop_moves(
 }
 
 pub fn get_queen_moves(
-    piece_position: &Position,
-    friendly_positions: &[Position],
-    opponent_positions: &[Position],
-) -> Vec<Position> {
+    piece_position: &Square,
+    friendly_positions: &[Square],
+    opponent_positions: &[Square],
+) -> Vec<Square> {
     let mut moves = get_rook_moves(piece_position, friendly_positions, opponent_positions);
     moves.append(&mut get_bishop_moves(
         piece_position,
@@ -104,12 +96,7 @@ pub fn get_queen_moves(
     moves
 }
 
-pub fn get_knight_moves(
-    piece_position: &Position,
-    friendly_positions: &[Position],
-) -> Vec<Position> {
-    let x = piece_position.x;
-    let y = piece_position.y;
+pub fn get_knight_moves(piece_position: &Square, friendly_positions: &[Square]) -> Vec<Square> {
     let knight_moves: [(i32, i32); 8] = [
         (-2, -1),
         (-2, 1),
@@ -120,12 +107,10 @@ pub fn get_knight_moves(
         (-1, 2),
         (1, 2),
     ];
-    let mut moves: Vec<Position> = Vec::new();
+    let mut moves: Vec<Square> = Vec::new();
 
     for (move_x, move_y) in knight_moves {
-        let new_x = x as i32 + move_x;
-        let new_y = y as i32 + move_y;
-        if let Some(new_position) = Position::get_valid_position(new_x, new_y) {
+        if let Some(new_position) = piece_position.offset(move_x, move_y) {
             if !friendly_positions.contains(&new_position) {
                 moves.push(new_position);
             }
@@ -135,34 +120,33 @@ pub fn get_knight_moves(
 }
 
 pub fn get_pawn_moves(
-    position: &Position,
+    position: &Square,
     has_moved: &bool,
-    friendly_positions: &[Position],
-    opponent_positions: &[Position],
+    friendly_positions: &[Square],
+    opponent_positions: &[Square],
     move_direction: i32,
-    en_passant: &Option<Position>,
-) -> Vec<Position> {
+    en_passant: &Option<Square>,
+) -> Vec<Square> {
     // Fuck Pawns
-    let mut moves: Vec<Position> = Vec::new();
-    let (x, y) = position.get_x_y_as_int();
+    let mut moves: Vec<Square> = Vec::new();
     let mut opponent_positions = opponent_positions.to_vec();
 
     if let Some(en_passant_position) = en_passant {
         opponent_positions.push(*en_passant_position)
     }
 
-    let mut forward_moves: Vec<Position> = Vec::new();
+    let mut forward_moves: Vec<Square> = Vec::new();
 
-    if let Some(position) = Position::get_valid_position(x, y + move_direction) {
-        forward_moves.push(position);
-    }
+    if let Some(blocking_position) = position.offset(0, move_direction) {
+        forward_moves.push(blocking_position);
 
-    if !has_moved {
-        let blocking_position = Position::get_valid_position(x, y + move_direction).unwrap();
-        if !friendly_positions.contains(&blocking_position)
+        if !has_moved
+            & !friendly_positions.contains(&blocking_position)
             & !opponent_positions.contains(&blocking_position)
         {
-            forward_moves.push(Position::get_valid_position(x, y + 2 * move_direction).unwrap());
+            if let Some(double_step) = position.offset(0, 2 * move_direction) {
+                forward_moves.push(double_step);
+            }
         }
     }
 
@@ -173,8 +157,8 @@ pub fn get_pawn_moves(
     }
 
     let capture_moves = [
-        Position::get_valid_position(x - 1, y + move_direction),
-        Position::get_valid_position(x + 1, y + move_direction),
+        position.offset(-1, move_direction),
+        position.offset(1, move_direction),
     ];
 
     for position in capture_moves.into_iter().flatten() {
@@ -185,27 +169,32 @@ pub fn get_pawn_moves(
     moves
 }
 
-fn get_castling_move(board: &Board, short: bool) -> Option<Position> {
-    let row = if board.turn == Color::White { 0 } else { 7 };
-    let col: Vec<usize>;
-    let target_col: usize;
+fn get_castling_move(board: &Board, short: bool) -> Option<Square> {
+    let rank = if board.turn == Color::White { 0 } else { 7 };
+    let files: [u8; 2];
+    let target_file: u8;
 
     if short {
-        col = vec![1, 2];
-        target_col = 1;
+        files = [5, 6];
+        target_file = 6;
     } else {
-        col = vec![4, 5];
+        files = [3, 2];
         if board
-            .get_piece_from_position(&Position::new(6, row))
+            .get_piece_from_position(&Square::from_file_rank(1, rank))
             .is_some()
         {
             return None;
         }
-        target_col = 5;
+        target_file = 2;
+    }
+
+    // no castling out of check
+    if is_field_in_check(board.king_positions[&board.turn], board) {
+        return None;
     }
 
-    for x in col {
-        let position = Position::new(x, row);
+    for file in files {
+        let position = Square::from_file_rank(file, rank);
         if board.get_piece_from_position(&position).is_some() {
             return None;
         }
@@ -214,16 +203,16 @@ fn get_castling_move(board: &Board, short: bool) -> Option<Position> {
         }
     }
 
-    Some(Position::new(target_col, row))
+    Some(Square::from_file_rank(target_file, rank))
 }
 
 pub fn get_king_moves(
-    position: &Position,
+    position: &Square,
     board: &Board,
-    friendly_positions: &[Position],
+    friendly_positions: &[Square],
     include_castling: bool,
-) -> Vec<Position> {
-    let mut moves: Vec<Position> = Vec::new();
+) -> Vec<Square> {
+    let mut moves: Vec<Square> = Vec::new();
 
     if include_castling {
         let [can_castle_short, can_castle_long] = board.castling[&board.turn];
@@ -240,17 +229,15 @@ pub fn get_king_moves(
         }
     }
 
-    let (x, y) = position.get_x_y_as_int();
-
     let possible_moves = [
-        Position::get_valid_position(x, y + 1),
-        Position::get_valid_position(x, y - 1),
-        Position::get_valid_position(x + 1, y),
-        Position::get_valid_position(x - 1, y),
-        Position::get_valid_position(x + 1, y + 1),
-        Position::get_valid_position(x + 1, y - 1),
-        Position::get_valid_position(x - 1, y + 1),
-        Position::get_valid_position(x - 1, y - 1),
+        position.offset(0, 1),
+        position.offset(0, -1),
+        position.offset(1, 0),
+        position.offset(-1, 0),
+        position.offset(1, 1),
+        position.offset(1, -1),
+        position.offset(-1, 1),
+        position.offset(-1, -1),
     ];
 
     for position in possible_moves.into_iter().flatten() {
@@ -262,7 +249,7 @@ pub fn get_king_moves(
     moves
 }
 
-pub fn is_field_in_check(field_position: Position, board: &Board) -> bool {
+pub fn is_field_in_check(field_position: Square, board: &Board) -> bool {
     let all_positions = board.get_all_positions();
     let friendly_positions = &all_positions[(board.turn == Color::Black) as usize];
     let opponent_positions = &all_positions[(board.turn != Color::Black) as usize];
@@ -303,7 +290,7 @@ pub fn is_field_in_check(field_position: Position, board: &Board) -> bool {
         friendly_positions,
         opponent_positions,
     );
-    let last_bishop_moves: Vec<Option<&Position>> = bishop_moves
+    let last_bishop_moves: Vec<Option<&Square>> = bishop_moves
         .iter()
         .map(|positions_in_direction| positions_in_direction.last())
         .collect();
@@ -323,10 +310,7 @@ pub fn is_field_in_check(field_position: Position, board: &Board) -> bool {
         Direction::new(-1, attack_direction),
         Direction::new(1, attack_direction),
     ] {
-        if let Some(position_to_check) = Position::get_valid_position(
-            field_position.x as i32 + direction.x,
-            field_position.y as i32 + direction.y,
-        ) {
+        if let Some(position_to_check) = field_position.offset(direction.x, direction.y) {
             if let Some(piece) = board.get_piece_from_position(&position_to_check) {
                 if (piece.color != board.turn) & (piece.kind == PieceKind::P) {
                     return true;
@@ -347,19 +331,117 @@ pub fn is_field_in_check(field_position: Position, board: &Board) -> bool {
     false
 }
 
+/// Squares with pieces of the attacker color that attack the target. The board is given as a
+/// lookup, so positions that only exist hypothetically (e.g. after a move) can be checked too.
+pub fn get_attackers(
+    target: Square,
+    attacker: Color,
+    piece_at: impl Fn(Square) -> Option<Piece>,
+) -> Vec<Square> {
+    let mut attackers: Vec<Square> = Vec::new();
+    let is_attacker = |square: Square, kinds: &[PieceKind]| -> bool {
+        matches!(piece_at(square), Some(piece) if piece.color == attacker && kinds.contains(&piece.kind))
+    };
+
+    for (x, y) in [
+        (-2, -1),
+        (-2, 1),
+        (2, -1),
+        (2, 1),
+        (-1, -2),
+        (1, -2),
+        (-1, 2),
+        (1, 2),
+    ] {
+        if let Some(square) = target.offset(x, y) {
+            if is_attacker(square, &[PieceKind::N]) {
+                attackers.push(square);
+            }
+        }
+    }
+
+    for (directions, kinds) in [
+        (rook_directions(), [PieceKind::R, PieceKind::Q]),
+        (bishop_directions(), [PieceKind::B, PieceKind::Q]),
+    ] {
+        for direction in directions {
+            for step in 1..8 {
+                match target.offset(step * direction.x, step * direction.y) {
+                    Some(square) => {
+                        if piece_at(square).is_some() {
+                            if is_attacker(square, &kinds) {
+                                attackers.push(square);
+                            }
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    // pawns attack forward, so look backwards from the target
+    let pawn_rank_step = if attacker == Color::White { -1 } else { 1 };
+    for file_step in [-1, 1] {
+        if let Some(square) = target.offset(file_step, pawn_rank_step) {
+            if is_attacker(square, &[PieceKind::P]) {
+                attackers.push(square);
+            }
+        }
+    }
+
+    for (x, y) in [
+        (0, 1),
+        (0, -1),
+        (1, 0),
+        (-1, 0),
+        (1, 1),
+        (1, -1),
+        (-1, 1),
+        (-1, -1),
+    ] {
+        if let Some(square) = target.offset(x, y) {
+            if is_attacker(square, &[PieceKind::K]) {
+                attackers.push(square);
+            }
+        }
+    }
+
+    attackers
+}
+
+/// Squares strictly between two squares on the same line, empty if they are not on one.
+pub fn get_squares_between(from: Square, to: Square) -> Vec<Square> {
+    let file_diff = to.file() as i32 - from.file() as i32;
+    let rank_diff = to.rank() as i32 - from.rank() as i32;
+    if !((file_diff == 0) | (rank_diff == 0) | (file_diff.abs() == rank_diff.abs())) {
+        return Vec::new();
+    }
+    let steps = file_diff.abs().max(rank_diff.abs());
+    (1..steps)
+        .filter_map(|step| from.offset(step * file_diff.signum(), step * rank_diff.signum()))
+        .collect()
+}
+
 pub fn filter_check_moves(
-    from_position: Position,
-    to_positions: Vec<Position>,
+    from_position: Square,
+    to_positions: Vec<Square>,
     board: &Board,
     piece: Piece,
-) -> Vec<Position> {
+) -> Vec<Square> {
     // this method needs to be cleaned up
-    // also it is missing en passant capture checks
-    let mut filtered_moves: Vec<Position> = Vec::new();
+    let mut filtered_moves: Vec<Square> = Vec::new();
 
     for to_position in to_positions {
         let mut temp_board = board.clone();
-        let moved_piece = match &mut temp_board.board[from_position.x][from_position.y] {
+        if (piece.kind == PieceKind::P) & (Some(to_position) == board.en_passant) {
+            temp_board.remove_piece(&Square::from_file_rank(
+                to_position.file(),
+                from_position.rank(),
+            ));
+        }
+        let moved_piece = match &mut temp_board.board[from_position.index()] {
             Some(moved_piece) => moved_piece,
             None => panic!("Oops, you done goofed"),
         };
@@ -381,50 +463,48 @@ pub fn filter_check_moves(
     filtered_moves
 }
 
-pub fn get_rook_old_and_new_castling_positions(
-    king_new_position: &Position,
-) -> (Position, Position) {
-    let old_x_position: usize;
-    let new_x_position: usize;
-    if king_new_position.x == 1 {
-        old_x_position = 0;
-        new_x_position = 2;
+pub fn get_rook_old_and_new_castling_positions(king_new_position: &Square) -> (Square, Square) {
+    let old_file: u8;
+    let new_file: u8;
+    if king_new_position.file() == 6 {
+        old_file = 7;
+        new_file = 5;
     } else {
-        old_x_position = 7;
-        new_x_position = 4;
+        old_file = 0;
+        new_file = 3;
     }
-    let old_rook_position = Position::new(old_x_position, king_new_position.y);
-    let new_rook_position = Position::new(new_x_position, king_new_position.y);
+    let old_rook_position = Square::from_file_rank(old_file, king_new_position.rank());
+    let new_rook_position = Square::from_file_rank(new_file, king_new_position.rank());
 
     (old_rook_position, new_rook_position)
 }
 
 #[cfg(test)]
 mod test_moves {
-    use crate::helpers::{Direction, Position};
+    use crate::helpers::{Direction, Square};
     use crate::moves::{get_knight_moves, get_pawn_moves, get_straight_moves};
 
     #[test]
     fn test_get_knight_moves() {
-        let piece_position = Position::new(1, 6);
-        let friendly_positions: Vec<Position> = vec![
-            Position::new(0, 0),
-            Position::new(2, 6),
-            Position::new(3, 5),
+        let piece_position = Square::from_file_rank(1, 6);
+        let friendly_positions: Vec<Square> = vec![
+            Square::from_file_rank(0, 0),
+            Square::from_file_rank(2, 6),
+            Square::from_file_rank(3, 5),
         ];
 
         let output = get_knight_moves(&piece_position, &friendly_positions);
-        let expected_output: Vec<Position> = vec![
-            Position::new(3, 7),
-            Position::new(0, 4),
-            Position::new(2, 4),
+        let expected_output: Vec<Square> = vec![
+            Square::from_file_rank(3, 7),
+            Square::from_file_rank(0, 4),
+            Square::from_file_rank(2, 4),
         ];
         assert_eq!(expected_output, output);
     }
 
     #[test]
     fn test_get_straight_moves() {
-        let piece_position = Position::new(4, 4);
+        let piece_position = Square::from_file_rank(4, 4);
         let directions = vec![
             Direction::new(-1, 0),
             Direction::new(1, 0),
@@ -433,23 +513,23 @@ mod test_moves {
             Direction::new(-1, -1),
         ];
         let friendly_positions = vec![
-            Position::new(4, 5),
-            Position::new(2, 4),
-            Position::new(6, 2),
+            Square::from_file_rank(4, 5),
+            Square::from_file_rank(2, 4),
+            Square::from_file_rank(6, 2),
         ];
-        let opponent_positions = vec![Position::new(4, 3), Position::new(1, 1)];
+        let opponent_positions = vec![Square::from_file_rank(4, 3), Square::from_file_rank(1, 1)];
         let expected_output = vec![
-            Position::new(3, 4),
-            Position::new(5, 4),
-            Position::new(6, 4),
-            Position::new(7, 4),
-            Position::new(4, 3),
-            Position::new(3, 3),
-            Position::new(2, 2),
-            Position::new(1, 1),
+            Square::from_file_rank(3, 4),
+            Square::from_file_rank(5, 4),
+            Square::from_file_rank(6, 4),
+            Square::from_file_rank(7, 4),
+            Square::from_file_rank(4, 3),
+            Square::from_file_rank(3, 3),
+            Square::from_file_rank(2, 2),
+            Square::from_file_rank(1, 1),
         ];
 
-        let output: Vec<Position> = get_straight_moves(
+        let output: Vec<Square> = get_straight_moves(
             directions,
             &piece_position,
             &friendly_positions,
@@ -463,87 +543,88 @@ mod test_moves {
 
     #[test]
     fn test_get_pawn_moves_double() {
-        let position = Position::new(1, 1);
+        let position = Square::from_file_rank(1, 1);
 
         let output = get_pawn_moves(&position, &false, &Vec::new(), &Vec::new(), 1, &None);
-        let expected_output = vec![Position::new(1, 2), Position::new(1, 3)];
+        let expected_output = vec![Square::from_file_rank(1, 2), Square::from_file_rank(1, 3)];
 
         assert_eq!(expected_output, output);
     }
 
     #[test]
     fn test_get_pawn_moves_single() {
-        let position = Position::new(1, 1);
+        let position = Square::from_file_rank(1, 1);
 
         let output = get_pawn_moves(&position, &true, &Vec::new(), &Vec::new(), 1, &None);
-        let expected_output = vec![Position::new(1, 2)];
+        let expected_output = vec![Square::from_file_rank(1, 2)];
         assert_eq!(expected_output, output);
     }
 
     #[test]
     fn test_get_pawn_moves_blocked_by_friendly() {
-        let position = Position::new(1, 1);
+        let position = Square::from_file_rank(1, 1);
         let output = get_pawn_moves(
             &position,
             &false,
-            &[Position::new(1, 2)],
+            &[Square::from_file_rank(1, 2)],
             &Vec::new(),
             1,
             &None,
         );
-        let expected_output: Vec<Position> = Vec::new();
+        let expected_output: Vec<Square> = Vec::new();
         assert_eq!(expected_output, output);
     }
 
     #[test]
     fn test_get_pawn_moves_blocked_by_opponent() {
-        let position = Position::new(1, 1);
+        let position = Square::from_file_rank(1, 1);
         let output = get_pawn_moves(
             &position,
             &false,
             &Vec::new(),
-            &[Position::new(1, 2)],
+            &[Square::from_file_rank(1, 2)],
             1,
             &None,
         );
-        let expected_output: Vec<Position> = Vec::new();
+        let expected_output: Vec<Square> = Vec::new();
         assert_eq!(expected_output, output);
     }
 
     #[test]
     fn test_get_pawn_moves_capture() {
-        let position = Position::new(1, 1);
+        let position = Square::from_file_rank(1, 1);
         let output = get_pawn_moves(
             &position,
             &false,
             &Vec::new(),
             &[
-                Position::new(1, 2),
-                Position::new(0, 2),
-                Position::new(2, 2),
+                Square::from_file_rank(1, 2),
+                Square::from_file_rank(0, 2),
+                Square::from_file_rank(2, 2),
             ],
             1,
             &None,
         );
-        let expected_output: Vec<Position> = vec![Position::new(0, 2), Position::new(2, 2)];
+        let expected_output: Vec<Square> =
+            vec![Square::from_file_rank(0, 2), Square::from_file_rank(2, 2)];
         assert_eq!(expected_output, output);
     }
 
     #[test]
     fn test_get_pawn_moves_en_passant() {
-        let position = Position::new(1, 1);
+        let position = Square::from_file_rank(1, 1);
         let output = get_pawn_moves(
             &position,
             &false,
             &Vec::new(),
-            &[Position::new(0, 1)],
+            &[Square::from_file_rank(0, 1)],
             1,
-            &Some(Position::new(0, 2)),
+            &Some(Square::from_file_rank(0, 2)),
         );
-        let expected_output: Vec<Position> = vec![
-            Position::new(1, 2),
-            Position::new(1, 3),
-            Position::new(0, 2),
+        let expected_output: Vec<Square> = vec![
+            Square::from_file_rank(1, 2),
+            Square::from_file_rank(1, 3),
+            Square::from_file_rank(0, 2),
         ];
         assert_eq!(expected_output, output);
     }
@@ -0,0 +1,267 @@
+use crate::board::Board;
+use crate::helpers::{Move, Square};
+use crate::pieces::{Color, Piece, PieceKind};
+
+pub fn piece_kind_to_san_char(piece_kind: &PieceKind) -> char {
+    match piece_kind {
+        PieceKind::P => 'P',
+        PieceKind::R => 'R',
+        PieceKind::N => 'N',
+        PieceKind::B => 'B',
+        PieceKind::Q => 'Q',
+        PieceKind::K => 'K',
+    }
+}
+
+fn san_char_to_piece_kind(san_char: char) -> Option<PieceKind> {
+    match san_char {
+        'R' => Some(PieceKind::R),
+        'N' => Some(PieceKind::N),
+        'B' => Some(PieceKind::B),
+        'Q' => Some(PieceKind::Q),
+        'K' => Some(PieceKind::K),
+        _ => None,
+    }
+}
+
+fn is_promotion_move(piece: &Piece, le_move: &Move) -> bool {
+    piece.kind == PieceKind::P && (le_move.to.rank() == 0 || le_move.to.rank() == 7)
+}
+
+/// Converts a legal move to Standard Algebraic Notation, including the check/mate suffix.
+pub fn move_to_san(board: &Board, le_move: Move) -> String {
+    let piece = board
+        .get_piece_from_position(&le_move.from)
+        .expect("There should be a piece at the starting position");
+    let mut san = String::new();
+
+    if piece.kind == PieceKind::K && le_move.from.file().abs_diff(le_move.to.file()) == 2 {
+        if le_move.to.file() == 6 {
+            san.push_str("O-O");
+        } else {
+            san.push_str("O-O-O");
+        }
+    } else {
+        let from_string = le_move.from.to_string();
+        let is_capture = board.get_piece_from_position(&le_move.to).is_some()
+            || (piece.kind == PieceKind::P && le_move.from.file() != le_move.to.file());
+
+        if piece.kind == PieceKind::P {
+            if is_capture {
+                san.push_str(&from_string[..1]);
+            }
+        } else {
+            san.push(piece_kind_to_san_char(&piece.kind));
+
+            let rivals: Vec<Square> = board
+                .get_all_moves_of_color(board.turn)
+                .into_iter()
+                .filter(|other| {
+                    other.to == le_move.to
+                        && other.from != le_move.from
+                        && board.get_piece_from_position(&other.from).unwrap().kind == piece.kind
+                })
+                .map(|other| other.from)
+                .collect();
+
+            if !rivals.is_empty() {
+                if rivals
+                    .iter()
+                    .all(|rival| rival.file() != le_move.from.file())
+                {
+                    san.push_str(&from_string[..1]);
+                } else if rivals
+                    .iter()
+                    .all(|rival| rival.rank() != le_move.from.rank())
+                {
+                    san.push_str(&from_string[1..]);
+                } else {
+                    san.push_str(&from_string);
+                }
+            }
+        }
+
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&le_move.to.to_string());
+
+        if is_promotion_move(&piece, &le_move) {
+            san.push('=');
+            san.push(piece_kind_to_san_char(
+                &le_move.promotion.unwrap_or(PieceKind::Q),
+            ));
+        }
+    }
+
+    if board.gives_check(le_move) {
+        if board.try_move(le_move).no_possible_moves() {
+            san.push('#');
+        } else {
+            san.push('+');
+        }
+    }
+
+    san
+}
+
+/// SAN of a line of legal moves from the position, numbered like "12... Nc6 13. Bb5".
+pub fn line_to_san(board: &Board, moves: &[Move]) -> String {
+    let mut board = board.clone();
+    let mut parts = Vec::new();
+    for (i, le_move) in moves.iter().enumerate() {
+        if board.turn == Color::White {
+            parts.push(format!("{}.", board.n_full_moves));
+        } else if i == 0 {
+            parts.push(format!("{}...", board.n_full_moves));
+        }
+        parts.push(move_to_san(&board, *le_move));
+        board = board.try_move(*le_move);
+    }
+    parts.join(" ")
+}
+
+/// Finds the legal move described by a SAN string. Annotation and check suffixes are ignored,
+/// and a pawn reaching the last rank without a promotion piece promotes to a queen.
+pub fn san_to_move(board: &Board, san: &str) -> Option<Move> {
+    let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = board.get_all_moves_of_color(board.turn);
+
+    if ["O-O", "0-0", "O-O-O", "0-0-0"].contains(&san) {
+        let king_position = board.king_positions[&board.turn];
+        let target_file = if san.len() == 3 { 6 } else { 2 };
+        return legal_moves.into_iter().find(|le_move| {
+            le_move.from == king_position
+                && le_move.to.file() == target_file
+                && le_move.from.file().abs_diff(le_move.to.file()) == 2
+        });
+    }
+
+    let mut chars: Vec<char> = san.chars().filter(|c| *c != 'x' && *c != '=').collect();
+    if chars.len() < 2 {
+        return None;
+    }
+
+    let piece_kind = match san_char_to_piece_kind(chars[0]) {
+        Some(piece_kind) => {
+            chars.remove(0);
+            piece_kind
+        }
+        None => PieceKind::P,
+    };
+
+    let mut promotion = None;
+    if piece_kind == PieceKind::P {
+        if let Some(promotion_kind) = chars.last().and_then(|c| san_char_to_piece_kind(*c)) {
+            promotion = Some(promotion_kind);
+            chars.pop();
+        }
+    }
+
+    if chars.len() < 2 {
+        return None;
+    }
+    let destination: String = chars[chars.len() - 2..].iter().collect();
+    let to = destination.parse::<Square>().ok()?;
+    let disambiguation = &chars[..chars.len() - 2];
+
+    let candidates: Vec<Move> = legal_moves
+        .into_iter()
+        .filter(|le_move| {
+            if le_move.to != to {
+                return false;
+            }
+            if board.get_piece_from_position(&le_move.from).unwrap().kind != piece_kind {
+                return false;
+            }
+            let from_string: Vec<char> = le_move.from.to_string().chars().collect();
+            disambiguation.iter().all(|c| {
+                if c.is_ascii_digit() {
+                    *c == from_string[1]
+                } else {
+                    *c == from_string[0]
+                }
+            })
+        })
+        .collect();
+
+    if candidates.len() != 1 {
+        return None;
+    }
+
+    let mut le_move = candidates[0];
+    if piece_kind == PieceKind::P && (to.rank() == 0 || to.rank() == 7) {
+        le_move.promotion = Some(promotion.unwrap_or(PieceKind::Q));
+    }
+    Some(le_move)
+}
+
+#[cfg(test)]
+mod test_notation {
+    use crate::board::Board;
+    use crate::helpers::{chess_move, Move};
+    use crate::notation::{line_to_san, move_to_san, san_to_move};
+    use crate::pieces::PieceKind;
+
+    #[test]
+    fn test_move_to_san() {
+        let board = Board::new();
+        assert_eq!(move_to_san(&board, chess_move("e2", "e4")), "e4");
+        assert_eq!(move_to_san(&board, chess_move("g1", "f3")), "Nf3");
+
+        let board = Board::from_fen("r3k2r/8/8/3p4/4P3/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(move_to_san(&board, chess_move("e4", "d5")), "exd5");
+        assert_eq!(move_to_san(&board, chess_move("e1", "g1")), "O-O");
+        assert_eq!(move_to_san(&board, chess_move("e1", "c1")), "O-O-O");
+        assert_eq!(move_to_san(&board, chess_move("a1", "a8")), "Rxa8+");
+    }
+
+    #[test]
+    fn test_move_to_san_disambiguation_and_promotion() {
+        let board = Board::from_fen("7k/P7/8/8/8/8/8/R5RK w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&board, chess_move("a1", "d1")), "Rad1");
+        let promotion =
+            Move::new_promotion("a7".parse().unwrap(), "a8".parse().unwrap(), PieceKind::Q);
+        assert_eq!(move_to_san(&board, promotion), "a8=Q+");
+
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&board, chess_move("a1", "a8")), "Ra8#");
+    }
+
+    #[test]
+    fn test_san_to_move() {
+        let board = Board::new();
+        assert_eq!(san_to_move(&board, "e4"), Some(chess_move("e2", "e4")));
+        assert_eq!(san_to_move(&board, "Nf3"), Some(chess_move("g1", "f3")));
+        assert_eq!(san_to_move(&board, "Ke2"), None);
+
+        let board = Board::from_fen("7k/P7/8/8/8/8/8/R5RK w - - 0 1").unwrap();
+        assert_eq!(san_to_move(&board, "Rd1"), None);
+        assert_eq!(san_to_move(&board, "Rgd1"), Some(chess_move("g1", "d1")));
+        assert_eq!(
+            san_to_move(&board, "a8=N"),
+            Some(Move::new_promotion(
+                "a7".parse().unwrap(),
+                "a8".parse().unwrap(),
+                PieceKind::N,
+            ))
+        );
+
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        assert_eq!(san_to_move(&board, "O-O-O"), Some(chess_move("e8", "c8")));
+    }
+
+    #[test]
+    fn test_line_to_san() {
+        let board = Board::new();
+        let line = [
+            chess_move("e2", "e4"),
+            chess_move("e7", "e5"),
+            chess_move("g1", "f3"),
+        ];
+        assert_eq!(line_to_san(&board, &line), "1. e4 e5 2. Nf3");
+        let board = board.try_move(line[0]);
+        assert_eq!(line_to_san(&board, &line[1..]), "1... e5 2. Nf3");
+        assert_eq!(line_to_san(&board, &[]), "");
+    }
+}
@@ -0,0 +1,493 @@
+use crate::board::Board;
+use crate::game_tree::{GameTree, NodeId};
+use crate::notation::san_to_move;
+use crate::pieces::Color;
+
+use std::io::{Error, ErrorKind};
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Nag(u8),
+    Result(String),
+    San(String),
+}
+
+fn invalid_pgn(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("Invalid PGN: {}", message))
+}
+
+fn suffix_to_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn parse_tag(tag: &str) -> Result<Token, Error> {
+    let tag = tag.trim();
+    let (name, value) = tag
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| invalid_pgn(tag))?;
+    let value = value.trim();
+    if !(value.starts_with('"') && value.ends_with('"') && value.len() >= 2) {
+        return Err(invalid_pgn(tag));
+    }
+    let value = value[1..value.len() - 1]
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\");
+    Ok(Token::Tag(name.to_owned(), value))
+}
+
+fn tokenize(pgn: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = pgn.chars().collect();
+    let mut i = 0;
+    let mut line_start = true;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '%' && line_start {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        line_start = c == '\n';
+
+        match c {
+            '[' => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|x| *x == ']')
+                    .ok_or_else(|| invalid_pgn("unclosed tag"))?;
+                let tag: String = chars[i + 1..i + end].iter().collect();
+                tokens.push(parse_tag(&tag)?);
+                i += end + 1;
+            }
+            '{' => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|x| *x == '}')
+                    .ok_or_else(|| invalid_pgn("unclosed comment"))?;
+                let comment: String = chars[i + 1..i + end].iter().collect();
+                tokens.push(Token::Comment(
+                    comment.split_whitespace().collect::<Vec<&str>>().join(" "),
+                ));
+                i += end + 1;
+            }
+            ';' => {
+                let start = i + 1;
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                let comment: String = chars[start..i].iter().collect();
+                tokens.push(Token::Comment(comment.trim().to_owned()));
+            }
+            '(' => {
+                tokens.push(Token::VariationStart);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::VariationEnd);
+                i += 1;
+            }
+            '$' => {
+                let start = i + 1;
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let nag: String = chars[start..i].iter().collect();
+                tokens.push(Token::Nag(
+                    nag.parse::<u8>().map_err(|_| invalid_pgn("bad NAG"))?,
+                ));
+            }
+            c if c.is_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && !['[', ']', '{', '}', '(', ')', ';', '$'].contains(&chars[i])
+                {
+                    i += 1;
+                }
+                let symbol: String = chars[start..i].iter().collect();
+                push_symbol(&symbol, &mut tokens);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn push_symbol(symbol: &str, tokens: &mut Vec<Token>) {
+    if RESULTS.contains(&symbol) {
+        tokens.push(Token::Result(symbol.to_owned()));
+        return;
+    }
+
+    // move numbers can be glued to the move, e.g. "12.e4" or "12...Nf6"
+    let number_length = symbol
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(symbol.len());
+    let symbol = if number_length == symbol.len() {
+        ""
+    } else if symbol[number_length..].starts_with('.') {
+        symbol[number_length..].trim_start_matches('.')
+    } else {
+        symbol
+    };
+    if symbol.is_empty() {
+        return;
+    }
+
+    let san = symbol.trim_end_matches(['!', '?']);
+    let suffix = &symbol[san.len()..];
+    if !san.is_empty() {
+        tokens.push(Token::San(san.to_owned()));
+    }
+    if let Some(nag) = suffix_to_nag(suffix) {
+        tokens.push(Token::Nag(nag));
+    }
+}
+
+/// Whether the line is a tag pair like `[White "Ales"]`.
+fn is_tag_pair(line: &str) -> bool {
+    let Some(inner) = line
+        .trim()
+        .strip_prefix('[')
+        .and_then(|inner| inner.strip_suffix(']'))
+    else {
+        return false;
+    };
+    let Some((name, value)) = inner.trim_start().split_once(char::is_whitespace) else {
+        return false;
+    };
+    let value = value.trim();
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && value.len() >= 2
+        && value.starts_with('"')
+        && value.ends_with('"')
+}
+
+/// Splits a PGN database into its games. A new one starts at a tag pair after movetext and a
+/// blank line, so a comment line starting with `[` doesn't cut a game in two.
+pub fn split_pgn_games(pgn: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut in_movetext = false;
+    let mut after_blank_line = false;
+    for line in pgn.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            after_blank_line = true;
+        } else {
+            if in_movetext && after_blank_line && is_tag_pair(line) {
+                games.push(&pgn[start..offset]);
+                start = offset;
+                in_movetext = false;
+            } else if !line.trim_start().starts_with('[') {
+                in_movetext = true;
+            }
+            after_blank_line = false;
+        }
+        offset += line.len();
+    }
+    if !pgn[start..].trim().is_empty() {
+        games.push(&pgn[start..]);
+    }
+    games
+}
+
+/// Parses the first game of a PGN string into a game tree, variations in parentheses included.
+pub fn read_pgn(pgn: &str) -> Result<GameTree, Error> {
+    let tokens = tokenize(pgn)?;
+
+    let tags: Vec<(String, String)> = tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Tag(name, value) => Some((name.clone(), value.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let board = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Board::from_fen(fen)?,
+        None => Board::new(),
+    };
+    let mut tree = GameTree::new(board);
+    tree.tags = tags;
+
+    let mut current = tree.root();
+    let mut variation_stack: Vec<NodeId> = Vec::new();
+
+    for token in tokens {
+        match token {
+            Token::Tag(_, _) => {}
+            Token::Comment(comment) => {
+                let node = tree.node_mut(current);
+                node.comment = match node.comment.take() {
+                    Some(existing) => Some(format!("{} {}", existing, comment)),
+                    None => Some(comment),
+                };
+            }
+            Token::VariationStart => {
+                let parent = tree
+                    .node(current)
+                    .parent
+                    .ok_or_else(|| invalid_pgn("variation without a move"))?;
+                variation_stack.push(current);
+                current = parent;
+            }
+            Token::VariationEnd => {
+                current = variation_stack
+                    .pop()
+                    .ok_or_else(|| invalid_pgn("unmatched ')'"))?;
+            }
+            Token::Nag(nag) => tree.node_mut(current).nags.push(nag),
+            Token::Result(result) => {
+                if !variation_stack.is_empty() {
+                    return Err(invalid_pgn("result inside a variation"));
+                }
+                if tree.get_tag("Result").is_none() {
+                    tree.set_tag("Result", &result);
+                }
+                break;
+            }
+            Token::San(san) => {
+                let le_move = san_to_move(&tree.node(current).board, &san)
+                    .ok_or_else(|| invalid_pgn(&format!("illegal move {}", san)))?;
+                current = tree.add_move(current, le_move);
+            }
+        }
+    }
+
+    if !variation_stack.is_empty() {
+        return Err(invalid_pgn("unclosed variation"));
+    }
+    Ok(tree)
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn move_number_token(tree: &GameTree, node_id: NodeId, force: bool) -> Option<String> {
+    let parent = tree.node(node_id).parent?;
+    let board = &tree.node(parent).board;
+    if board.turn == Color::White {
+        Some(format!("{}.", board.n_full_moves))
+    } else if force {
+        Some(format!("{}...", board.n_full_moves))
+    } else {
+        None
+    }
+}
+
+/// Piece of movetext, moves carry the id of their node so the GUI can make them clickable.
+pub type MovetextToken = (String, Option<NodeId>);
+
+fn push_move_tokens(
+    tree: &GameTree,
+    node_id: NodeId,
+    force_number: bool,
+    out: &mut Vec<MovetextToken>,
+) {
+    let node = tree.node(node_id);
+    if let Some(number) = move_number_token(tree, node_id, force_number) {
+        out.push((number, None));
+    }
+    out.push((node.san.clone(), Some(node_id)));
+    for nag in &node.nags {
+        out.push((format!("${}", nag), None));
+    }
+    if let Some(comment) = &node.comment {
+        out.push((format!("{{{}}}", comment), None));
+    }
+}
+
+fn push_line_tokens(
+    tree: &GameTree,
+    node_id: NodeId,
+    force_number: bool,
+    out: &mut Vec<MovetextToken>,
+) {
+    let children = &tree.node(node_id).children;
+    let Some(&main) = children.first() else {
+        return;
+    };
+
+    push_move_tokens(tree, main, force_number, out);
+    let mut force_next = tree.node(main).comment.is_some();
+
+    for &variation in &children[1..] {
+        out.push(("(".to_owned(), None));
+        push_move_tokens(tree, variation, true, out);
+        push_line_tokens(tree, variation, tree.node(variation).comment.is_some(), out);
+        out.push((")".to_owned(), None));
+        force_next = true;
+    }
+
+    push_line_tokens(tree, main, force_next, out);
+}
+
+/// Movetext of the whole tree (root comment, moves, variations), without the result.
+pub fn movetext_tokens(tree: &GameTree) -> Vec<MovetextToken> {
+    let mut tokens = Vec::new();
+    if let Some(comment) = &tree.node(tree.root()).comment {
+        tokens.push((format!("{{{}}}", comment), None));
+    }
+    push_line_tokens(tree, tree.root(), true, &mut tokens);
+    tokens
+}
+
+/// Writes the game tree as PGN with the Seven Tag Roster first and variations in parentheses.
+pub fn write_pgn(tree: &GameTree) -> String {
+    let mut pgn = String::new();
+
+    for (name, default) in SEVEN_TAG_ROSTER {
+        let value = tree.get_tag(name).unwrap_or(default);
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+    }
+    for (name, value) in &tree.tags {
+        if SEVEN_TAG_ROSTER
+            .iter()
+            .all(|(roster_name, _)| roster_name != name)
+        {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+        }
+    }
+    pgn.push('\n');
+
+    let mut tokens: Vec<String> = movetext_tokens(tree)
+        .into_iter()
+        .map(|(text, _)| text)
+        .collect();
+    tokens.push(tree.get_tag("Result").unwrap_or("*").to_owned());
+
+    let mut line_length = 0;
+    for token in tokens {
+        let separator = !(line_length == 0 || token == ")" || pgn.ends_with('('));
+        if line_length + token.len() + separator as usize > 79 && line_length > 0 {
+            pgn.push('\n');
+            line_length = 0;
+        } else if separator {
+            pgn.push(' ');
+            line_length += 1;
+        }
+        line_length += token.len();
+        pgn.push_str(&token);
+    }
+    pgn.push('\n');
+    pgn
+}
+
+#[cfg(test)]
+mod test_pgn {
+    use crate::pgn::{read_pgn, split_pgn_games, write_pgn};
+
+    const PGN_WITH_VARIATIONS: &str = r#"[Event "Casual game"]
+[Site "?"]
+[Date "2024.05.01"]
+[Round "?"]
+[White "Ales"]
+[Black "Bot"]
+[Result "1-0"]
+
+{Opening} 1. e4 e5 (1... c5 {Sicilian} 2. Nf3 (2. c3 d5) 2... d6) 2. Nf3 $1 Nc6
+3. Bb5 a6 4. Ba4 Nf6 5. O-O 1-0
+"#;
+
+    #[test]
+    fn test_read_pgn() {
+        let tree = read_pgn(PGN_WITH_VARIATIONS).unwrap();
+        assert_eq!(tree.get_tag("White"), Some("Ales"));
+        assert_eq!(tree.mainline().len(), 9);
+        assert_eq!(tree.node(tree.root()).comment.as_deref(), Some("Opening"));
+
+        let e4 = tree.node(tree.root()).children[0];
+        assert_eq!(tree.node(e4).children.len(), 2);
+        let c5 = tree.node(e4).children[1];
+        assert_eq!(tree.node(c5).san, "c5");
+        assert_eq!(tree.node(c5).comment.as_deref(), Some("Sicilian"));
+
+        let nf3 = tree.mainline()[2];
+        assert_eq!(tree.node(nf3).nags, vec![1]);
+    }
+
+    #[test]
+    fn test_pgn_round_trip() {
+        let tree = read_pgn(PGN_WITH_VARIATIONS).unwrap();
+        let written = write_pgn(&tree);
+        assert_eq!(written, PGN_WITH_VARIATIONS);
+        assert_eq!(read_pgn(&written).unwrap(), tree);
+    }
+
+    #[test]
+    fn test_read_pgn_annotations_and_fen() {
+        let pgn = r#"[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+[SetUp "1"]
+
+1.e4!? Kd7 ; line comment
+2.e5?? *"#;
+        let tree = read_pgn(pgn).unwrap();
+        let mainline = tree.mainline();
+        assert_eq!(mainline.len(), 3);
+        assert_eq!(tree.node(mainline[0]).nags, vec![5]);
+        assert_eq!(
+            tree.node(mainline[1]).comment.as_deref(),
+            Some("line comment")
+        );
+        assert_eq!(tree.node(mainline[2]).nags, vec![4]);
+        assert_eq!(tree.get_tag("Result"), Some("*"));
+    }
+
+    #[test]
+    fn test_read_pgn_errors() {
+        assert!(read_pgn("1. e4 e5 2. Ke3").is_err());
+        assert!(read_pgn("1. e4 (1. d4").is_err());
+        assert!(read_pgn("(1. e4)").is_err());
+    }
+
+    #[test]
+    fn test_split_pgn_games() {
+        let database = format!(
+            "{}\n[Event \"Second\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n",
+            PGN_WITH_VARIATIONS
+        );
+        let games = split_pgn_games(&database);
+        assert_eq!(games.len(), 2);
+        assert_eq!(read_pgn(games[0]).unwrap().get_tag("White"), Some("Ales"));
+        let second = read_pgn(games[1]).unwrap();
+        assert_eq!(second.get_tag("Event"), Some("Second"));
+        assert_eq!(second.mainline().len(), 4);
+        assert!(split_pgn_games("\n\n").is_empty());
+
+        // a comment spilling onto a line starting with a bracket stays in its game
+        let commented = "[Result \"1-0\"]\n\n1. e4 {the move\n[often played]} e5 1-0\n";
+        let games = split_pgn_games(commented);
+        assert_eq!(games, [commented]);
+        assert_eq!(read_pgn(games[0]).unwrap().mainline().len(), 2);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::board::Board;
-use crate::helpers::Position;
+use crate::helpers::Square;
 use crate::moves::{
     filter_check_moves, get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_moves,
     get_queen_moves, get_rook_moves,
@@ -25,13 +25,13 @@ pub enum PieceKind {
 pub struct Piece {
     pub color: Color,
     pub kind: PieceKind,
-    pub position: Position,
+    pub position: Square,
     pub points: i32,
     pub has_moved: bool,
 }
 
 impl Piece {
-    pub fn new(color: Color, kind: PieceKind, position: Position) -> Piece {
+    pub fn new(color: Color, kind: PieceKind, position: Square) -> Piece {
         let points = match kind {
             PieceKind::P => 1,
             PieceKind::R => 5,
@@ -49,18 +49,30 @@ impl Piece {
         }
     }
 
-    pub fn move_piece(&mut self, new_position: Position) {
+    pub fn move_piece(&mut self, new_position: Square) {
         self.position = new_position;
         self.has_moved = true;
     }
 
     pub fn get_piece_moves(
         &self,
-        friendly_positions: &[Position],
-        opponent_positions: &[Position],
+        friendly_positions: &[Square],
+        opponent_positions: &[Square],
         board: &Board,
-    ) -> Vec<Position> {
-        let all_moves = match self.kind {
+    ) -> Vec<Square> {
+        let all_moves = self.get_pseudo_legal_moves(friendly_positions, opponent_positions, board);
+
+        filter_check_moves(self.position, all_moves, board, *self)
+    }
+
+    /// Moves of the piece without checking whether they leave the own king in check.
+    pub fn get_pseudo_legal_moves(
+        &self,
+        friendly_positions: &[Square],
+        opponent_positions: &[Square],
+        board: &Board,
+    ) -> Vec<Square> {
+        match self.kind {
             PieceKind::P => get_pawn_moves(
                 &self.position,
                 &self.has_moved,
@@ -76,9 +88,7 @@ impl Piece {
             }
             PieceKind::Q => get_queen_moves(&self.position, friendly_positions, opponent_positions),
             PieceKind::K => get_king_moves(&self.position, board, friendly_positions, true),
-        };
-
-        filter_check_moves(self.position, all_moves, board, *self)
+        }
     }
 
     pub fn get_piece_kind_and_color(piece_char: &char) -> (PieceKind, Color) {
@@ -0,0 +1,503 @@
+use crate::board::Board;
+use crate::helpers::{Move, Square};
+use crate::pieces::{Color, PieceKind};
+use crate::zobrist::{hash_with_keys, KEYS, N_KEYS};
+
+use rand::Rng;
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+pub const POLYGLOT_START_POSITION_KEY: u64 = 0x463B96181691FC9C;
+
+pub const DEFAULT_BOOK_DEPTH: u16 = 16;
+
+const ENTRY_SIZE: usize = 16;
+
+pub const NATIVE_BOOK_HEADER: &str = "# rust-chess opening book";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolyglotKeys {
+    keys: Box<[u64; N_KEYS]>,
+}
+
+impl PolyglotKeys {
+    pub fn builtin() -> PolyglotKeys {
+        PolyglotKeys {
+            keys: Box::new(KEYS),
+        }
+    }
+
+    /// Reads the 781 keys from a file instead, either as 6248 bytes of big-endian numbers or as text
+    /// with one hex number per key (`0x` and separating commas allowed), like the table in the
+    /// Polyglot sources. The keys are checked against the known key of the starting position.
+    pub fn load(path: impl AsRef<Path>) -> Result<PolyglotKeys, Error> {
+        let bytes = fs::read(path)?;
+        let numbers: Vec<u64> = if bytes.len() == N_KEYS * 8 {
+            bytes
+                .chunks_exact(8)
+                .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap()))
+                .collect()
+        } else {
+            String::from_utf8_lossy(&bytes)
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|word| !word.is_empty())
+                .map(|word| {
+                    let word = word.trim_start_matches("0x").trim_start_matches("0X");
+                    u64::from_str_radix(word.trim_end_matches(['U', 'L', 'u', 'l']), 16)
+                })
+                .collect::<Result<_, _>>()
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error.to_string()))?
+        };
+        let keys: [u64; N_KEYS] = numbers.try_into().map_err(|numbers: Vec<u64>| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Expected {} keys, found {}", N_KEYS, numbers.len()),
+            )
+        })?;
+        let keys = PolyglotKeys {
+            keys: Box::new(keys),
+        };
+        if keys.hash(&Board::new()) != POLYGLOT_START_POSITION_KEY {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Not the standard Polyglot keys, the starting position has the wrong key",
+            ));
+        }
+        Ok(keys)
+    }
+
+    pub fn from_env() -> Result<PolyglotKeys, Error> {
+        match env::var("CHESS_BOOK_KEYS") {
+            Ok(path) => PolyglotKeys::load(path),
+            Err(_) => Ok(PolyglotKeys::builtin()),
+        }
+    }
+
+    pub fn hash(&self, board: &Board) -> u64 {
+        hash_with_keys(board, &self.keys)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BookSelection {
+    Weighted,
+    Best,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub le_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> BookEntry {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            le_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.le_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// Polyglot move bits: to file, to rank, from file and from rank three bits each, then the
+/// promotion piece. Castling is written as the king taking its own rook.
+pub fn encode_book_move(board: &Board, le_move: Move) -> u16 {
+    let mut to = le_move.to;
+    let is_king = board
+        .get_piece_from_position(&le_move.from)
+        .is_some_and(|piece| piece.kind == PieceKind::K);
+    if is_king && le_move.from.file() == 4 && le_move.from.file().abs_diff(to.file()) == 2 {
+        let rook_file = if to.file() == 6 { 7 } else { 0 };
+        to = Square::from_file_rank(rook_file, to.rank());
+    }
+    let promotion = match le_move.promotion {
+        None => 0,
+        Some(PieceKind::N) => 1,
+        Some(PieceKind::B) => 2,
+        Some(PieceKind::R) => 3,
+        Some(_) => 4,
+    };
+    to.file() as u16
+        | (to.rank() as u16) << 3
+        | (le_move.from.file() as u16) << 6
+        | (le_move.from.rank() as u16) << 9
+        | promotion << 12
+}
+
+pub fn decode_book_move(board: &Board, bits: u16) -> Option<Move> {
+    let square = |file: u16, rank: u16| Square::from_file_rank(file as u8 & 7, rank as u8 & 7);
+    let from = square(bits >> 6, bits >> 9);
+    let mut to = square(bits, bits >> 3);
+    let is_king = board
+        .get_piece_from_position(&from)
+        .is_some_and(|piece| piece.kind == PieceKind::K);
+    let takes_own_rook = board
+        .get_piece_from_position(&to)
+        .is_some_and(|piece| piece.kind == PieceKind::R && piece.color == board.turn);
+    if is_king && takes_own_rook {
+        let king_file = if to.file() == 7 { 6 } else { 2 };
+        to = Square::from_file_rank(king_file, to.rank());
+    }
+    let le_move = match bits >> 12 & 7 {
+        1 => Move::new_promotion(from, to, PieceKind::N),
+        2 => Move::new_promotion(from, to, PieceKind::B),
+        3 => Move::new_promotion(from, to, PieceKind::R),
+        4 => Move::new_promotion(from, to, PieceKind::Q),
+        _ => Move::new(from, to),
+    };
+    board.generate_moves().into_iter().find(|m| *m == le_move)
+}
+
+pub fn book_move_to_string(bits: u16) -> String {
+    let square = |file: u16, rank: u16| Square::from_file_rank(file as u8 & 7, rank as u8 & 7);
+    let promotion = match bits >> 12 & 7 {
+        1 => "n",
+        2 => "b",
+        3 => "r",
+        4 => "q",
+        _ => "",
+    };
+    format!(
+        "{}{}{}",
+        square(bits >> 6, bits >> 9),
+        square(bits, bits >> 3),
+        promotion
+    )
+}
+
+pub fn parse_book_move(text: &str) -> Option<u16> {
+    if !(4..=5).contains(&text.len()) || !text.is_ascii() {
+        return None;
+    }
+    let from: Square = text[0..2].parse().ok()?;
+    let to: Square = text[2..4].parse().ok()?;
+    let promotion = match &text[4..] {
+        "" => 0,
+        "n" => 1,
+        "b" => 2,
+        "r" => 3,
+        "q" => 4,
+        _ => return None,
+    };
+    Some(
+        to.file() as u16
+            | (to.rank() as u16) << 3
+            | (from.file() as u16) << 6
+            | (from.rank() as u16) << 9
+            | promotion << 12,
+    )
+}
+
+pub fn result_weights(results: &[(u32, u32)]) -> Vec<u16> {
+    let points: Vec<u64> = results
+        .iter()
+        .map(|(wins, draws)| 2 * *wins as u64 + *draws as u64)
+        .collect();
+    let max = points
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(u16::MAX as u64);
+    points
+        .into_iter()
+        .map(|points| {
+            let weight = points * u16::MAX as u64 / max;
+            // scaling mustn't drop a move that scored
+            (weight.max((points > 0) as u64)) as u16
+        })
+        .collect()
+}
+
+pub fn game_ply(board: &Board) -> u16 {
+    (board.n_full_moves.max(1) - 1) * 2 + (board.turn == Color::Black) as u16
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpeningBook {
+    entries: Vec<BookEntry>,
+    keys: PolyglotKeys,
+    pub selection: BookSelection,
+    pub max_depth: u16,
+}
+
+impl OpeningBook {
+    pub fn from_bytes(bytes: &[u8], keys: PolyglotKeys) -> Result<OpeningBook, Error> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Polyglot book size isn't a multiple of 16 bytes",
+            ));
+        }
+        let mut entries: Vec<BookEntry> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(BookEntry::from_bytes)
+            .collect();
+        // books are meant to be sorted, but don't trust it
+        entries.sort_by_key(|entry| entry.key);
+        Ok(OpeningBook {
+            entries,
+            keys,
+            selection: BookSelection::Weighted,
+            max_depth: DEFAULT_BOOK_DEPTH,
+        })
+    }
+
+    pub fn from_native(text: &str, keys: PolyglotKeys) -> Result<OpeningBook, Error> {
+        let invalid_line = |line: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid book line {}", line),
+            )
+        };
+        let mut lines = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [key, le_move, wins, draws, _losses] = fields[..] else {
+                return Err(invalid_line(line));
+            };
+            let key = u64::from_str_radix(key, 16).map_err(|_| invalid_line(line))?;
+            let le_move = parse_book_move(le_move).ok_or_else(|| invalid_line(line))?;
+            let wins: u32 = wins.parse().map_err(|_| invalid_line(line))?;
+            let draws: u32 = draws.parse().map_err(|_| invalid_line(line))?;
+            lines.push((key, le_move, (wins, draws)));
+        }
+        let results: Vec<(u32, u32)> = lines.iter().map(|(_, _, result)| *result).collect();
+        let mut entries: Vec<BookEntry> = lines
+            .iter()
+            .zip(result_weights(&results))
+            .map(|((key, le_move, _), weight)| BookEntry {
+                key: *key,
+                le_move: *le_move,
+                weight,
+                learn: 0,
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.key);
+        Ok(OpeningBook {
+            entries,
+            keys,
+            selection: BookSelection::Weighted,
+            max_depth: DEFAULT_BOOK_DEPTH,
+        })
+    }
+
+    pub fn open(path: impl AsRef<Path>, keys: PolyglotKeys) -> Result<OpeningBook, Error> {
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(NATIVE_BOOK_HEADER.as_bytes()) {
+            let text = String::from_utf8(bytes)
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error.to_string()))?;
+            return OpeningBook::from_native(&text, keys);
+        }
+        OpeningBook::from_bytes(&bytes, keys)
+    }
+
+    pub fn from_env() -> Result<Option<OpeningBook>, Error> {
+        let path = match env::var("CHESS_BOOK") {
+            Ok(path) => path,
+            Err(_) => return Ok(None),
+        };
+        let mut book = OpeningBook::open(path, PolyglotKeys::from_env()?)?;
+        if let Ok(depth) = env::var("CHESS_BOOK_DEPTH") {
+            book.max_depth = depth.parse().map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid book depth {}", depth),
+                )
+            })?;
+        }
+        Ok(Some(book))
+    }
+
+    pub fn write_entries(entries: &[BookEntry]) -> Vec<u8> {
+        let mut entries = entries.to_vec();
+        entries.sort_by_key(|entry| (entry.key, u16::MAX - entry.weight));
+        entries.iter().flat_map(|entry| entry.to_bytes()).collect()
+    }
+
+    pub fn keys(&self) -> &PolyglotKeys {
+        &self.keys
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        let key = self.keys.hash(board);
+        let first = self.entries.partition_point(|entry| entry.key < key);
+        self.entries[first..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| {
+                decode_book_move(board, entry.le_move).map(|le_move| (le_move, entry.weight))
+            })
+            .collect()
+    }
+
+    pub fn choose(&self, board: &Board) -> Option<Move> {
+        if game_ply(board) >= self.max_depth {
+            return None;
+        }
+        let moves: Vec<(Move, u16)> = self
+            .moves(board)
+            .into_iter()
+            .filter(|(_, weight)| *weight > 0)
+            .collect();
+        match self.selection {
+            BookSelection::Best => moves
+                .iter()
+                .max_by_key(|(_, weight)| *weight)
+                .map(|(le_move, _)| *le_move),
+            BookSelection::Weighted => {
+                let total: u32 = moves.iter().map(|(_, weight)| *weight as u32).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut pick = rand::thread_rng().gen_range(0..total);
+                for (le_move, weight) in moves {
+                    if pick < weight as u32 {
+                        return Some(le_move);
+                    }
+                    pick -= weight as u32;
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_polyglot {
+    use crate::board::Board;
+    use crate::helpers::{chess_move, Move};
+    use crate::pieces::PieceKind;
+    use crate::polyglot::{
+        decode_book_move, encode_book_move, BookEntry, BookSelection, OpeningBook, PolyglotKeys,
+        POLYGLOT_START_POSITION_KEY,
+    };
+    use crate::zobrist::KEYS;
+    use std::fs;
+
+    fn entry(board: &Board, le_move: Move, weight: u16) -> BookEntry {
+        BookEntry {
+            key: PolyglotKeys::builtin().hash(board),
+            le_move: encode_book_move(board, le_move),
+            weight,
+            learn: 0,
+        }
+    }
+
+    #[test]
+    fn test_move_encoding() {
+        let board = Board::new();
+        // e2e4: to e4 (file 4, rank 3), from e2 (file 4, rank 1)
+        assert_eq!(
+            encode_book_move(&board, chess_move("e2", "e4")),
+            4 | 3 << 3 | 4 << 6 | 1 << 9
+        );
+
+        let board = Board::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for le_move in [
+            chess_move("e1", "g1"),
+            chess_move("e1", "c1"),
+            Move::new_promotion("b7".parse().unwrap(), "a8".parse().unwrap(), PieceKind::N),
+        ] {
+            let bits = encode_book_move(&board, le_move);
+            assert_eq!(decode_book_move(&board, bits), Some(le_move));
+        }
+        // castling is stored as the king taking the rook
+        assert_eq!(
+            encode_book_move(&board, chess_move("e1", "g1")) & 63,
+            7 // h1
+        );
+        // not a legal move here
+        assert_eq!(
+            decode_book_move(&board, encode_book_move(&board, chess_move("e1", "e5"))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_book_choice() {
+        let start = Board::new();
+        let after_e4 = start.try_move(chess_move("e2", "e4"));
+        let entries = [
+            entry(&start, chess_move("e2", "e4"), 10),
+            entry(&start, chess_move("d2", "d4"), 30),
+            entry(&start, chess_move("b1", "a3"), 0),
+            entry(&after_e4, chess_move("c7", "c5"), 1),
+        ];
+        let path = std::env::temp_dir().join(format!("rust-chess-book-{}.bin", std::process::id()));
+        fs::write(&path, OpeningBook::write_entries(&entries)).unwrap();
+        let mut book = OpeningBook::open(&path, PolyglotKeys::builtin()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(book.len(), 4);
+
+        assert_eq!(book.moves(&start).len(), 3);
+        for _ in 0..20 {
+            let choice = book.choose(&start).unwrap();
+            assert!([chess_move("e2", "e4"), chess_move("d2", "d4")].contains(&choice));
+        }
+        book.selection = BookSelection::Best;
+        assert_eq!(book.choose(&start), Some(chess_move("d2", "d4")));
+        assert_eq!(book.choose(&after_e4), Some(chess_move("c7", "c5")));
+        assert_eq!(
+            book.choose(&after_e4.try_move(chess_move("c7", "c5"))),
+            None
+        );
+
+        book.max_depth = 1;
+        assert_eq!(book.choose(&after_e4), None);
+    }
+
+    #[test]
+    fn test_builtin_keys_are_standard() {
+        // no key file needed
+        assert_eq!(
+            PolyglotKeys::builtin().hash(&Board::new()),
+            POLYGLOT_START_POSITION_KEY
+        );
+        let path = std::env::temp_dir().join(format!("rust-chess-std-{}.bin", std::process::id()));
+        let bytes: Vec<u8> = KEYS.iter().flat_map(|key| key.to_be_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+        let loaded = PolyglotKeys::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, PolyglotKeys::builtin());
+    }
+
+    #[test]
+    fn test_keys_are_checked() {
+        let path = std::env::temp_dir().join(format!("rust-chess-keys-{}.txt", std::process::id()));
+        let text: Vec<String> = (0..781).map(|i| format!("0x{:016X},", i)).collect();
+        fs::write(&path, text.join("\n")).unwrap();
+        // the right number of keys, but not the standard ones
+        let error = PolyglotKeys::load(&path).unwrap_err();
+        assert!(error.to_string().contains("standard Polyglot keys"));
+        fs::write(&path, "0x1, 0x2").unwrap();
+        assert!(PolyglotKeys::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
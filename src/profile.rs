@@ -0,0 +1,387 @@
+use crate::bot::{ChessBot, EvalWeights, SkillLevel, Term};
+use crate::pieces::PieceKind;
+use crate::polyglot::{OpeningBook, PolyglotKeys};
+
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Read when `CHESS_PROFILES` isn't set, if it exists.
+pub const DEFAULT_PROFILES_PATH: &str = "./profiles.toml";
+
+/// A bot personality: how it weighs the position, how far it searches and what it plays from.
+/// Anything a profile leaves out stays as the bot has it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BotProfile {
+    /// The section name in the file.
+    pub id: String,
+    /// What the GUI shows, the id without one.
+    pub name: String,
+    pub weights: EvalWeights,
+    pub contempt: i32,
+    pub book: Option<PathBuf>,
+    pub depth: Option<u8>,
+    pub move_time: Option<Duration>,
+    pub skill: Option<SkillLevel>,
+    pub threads: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+fn invalid_profile(line_number: usize, message: impl std::fmt::Display) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid profile on line {}: {}", line_number, message),
+    )
+}
+
+/// Cuts a `#` comment off the line, unless it is inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '#' if !in_quotes => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    if let Some(string) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        return Some(Value::String(string.to_owned()));
+    }
+    match value {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+    let number = value.replace('_', "");
+    if let Ok(integer) = number.parse() {
+        return Some(Value::Integer(integer));
+    }
+    number.parse().ok().map(Value::Float)
+}
+
+impl Value {
+    fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    fn as_seconds(&self) -> Option<Duration> {
+        let seconds = match self {
+            Value::Integer(integer) => *integer as f64,
+            Value::Float(float) => *float,
+            _ => return None,
+        };
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+}
+
+impl BotProfile {
+    pub fn new(id: &str) -> BotProfile {
+        BotProfile {
+            id: id.to_owned(),
+            name: id.to_owned(),
+            weights: EvalWeights::default(),
+            contempt: 0,
+            book: None,
+            depth: None,
+            move_time: None,
+            skill: None,
+            threads: None,
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        let integer = || {
+            value
+                .as_integer()
+                .ok_or_else(|| format!("{} takes a whole number", key))
+        };
+        let int_in = |range: std::ops::RangeInclusive<i64>| {
+            let integer = integer()?;
+            if range.contains(&integer) {
+                Ok(integer)
+            } else {
+                Err(format!("{} out of range {:?}", key, range))
+            }
+        };
+        let piece = match key {
+            "pawn" => Some(PieceKind::P),
+            "knight" => Some(PieceKind::N),
+            "bishop" => Some(PieceKind::B),
+            "rook" => Some(PieceKind::R),
+            "queen" => Some(PieceKind::Q),
+            _ => None,
+        };
+        if let Some(kind) = piece {
+            self.weights
+                .set_piece_value(kind, int_in(0..=10_000)? as i32);
+            return Ok(());
+        }
+        let term = match key {
+            "piece_square" => Some(Term::PieceSquare),
+            "mobility" => Some(Term::Mobility),
+            "pawn_structure" => Some(Term::PawnStructure),
+            "king_safety" => Some(Term::KingSafety),
+            "tempo" => Some(Term::Tempo),
+            _ => None,
+        };
+        if let Some(term) = term {
+            self.weights.set_term_weight(term, int_in(0..=1000)? as i32);
+            return Ok(());
+        }
+        match key {
+            "name" => match value {
+                Value::String(name) => self.name = name.clone(),
+                _ => return Err("name takes a string".to_owned()),
+            },
+            "book" => match value {
+                Value::String(path) => self.book = Some(PathBuf::from(path)),
+                _ => return Err("book takes a path".to_owned()),
+            },
+            "depth" => self.depth = Some(int_in(1..=u8::MAX as i64)? as u8),
+            "move_time" => {
+                self.move_time = Some(
+                    value
+                        .as_seconds()
+                        .ok_or("move_time takes seconds".to_owned())?,
+                )
+            }
+            "skill" => self.skill = Some(SkillLevel::new(int_in(1..=u8::MAX as i64)? as u8)),
+            "elo" => self.skill = Some(SkillLevel::from_elo(int_in(0..=u32::MAX as i64)? as u32)),
+            "threads" => self.threads = Some(int_in(1..=256)? as usize),
+            "contempt" => self.contempt = int_in(-1000..=1000)? as i32,
+            "aggression" => self.weights.aggression = int_in(0..=1000)? as i32,
+            _ => return Err(format!("unknown key {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Sets the bot up as the profile says. The book is keyed like `OpeningBook::from_env` does.
+    pub fn apply(&self, bot: &mut ChessBot) -> Result<(), Error> {
+        if let Some(path) = &self.book {
+            bot.book = Some(OpeningBook::open(path, PolyglotKeys::from_env()?)?);
+        }
+        bot.weights = self.weights;
+        bot.contempt = self.contempt;
+        if let Some(depth) = self.depth {
+            bot.max_depth = depth;
+        }
+        if let Some(move_time) = self.move_time {
+            bot.move_time = Some(move_time);
+        }
+        if let Some(skill) = self.skill {
+            bot.skill = skill;
+        }
+        if let Some(threads) = self.threads {
+            bot.threads = threads;
+        }
+        Ok(())
+    }
+}
+
+/// Reads profiles from a small subset of TOML: one `[id]` section per profile with
+/// `key = value` lines, the values strings, numbers or booleans, and `#` comments.
+///
+/// Piece values (`pawn`, `knight`, `bishop`, `rook`, `queen`) are in centipawns, the
+/// evaluation terms (`piece_square`, `mobility`, `pawn_structure`, `king_safety`, `tempo`)
+/// and `aggression` in percent, `contempt` in centipawns and `move_time` in seconds.
+pub fn parse_profiles(text: &str) -> Result<Vec<BotProfile>, Error> {
+    let mut profiles: Vec<BotProfile> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[') {
+            let id = section
+                .strip_suffix(']')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .ok_or_else(|| invalid_profile(line_number, line))?;
+            if profiles.iter().any(|profile| profile.id == id) {
+                return Err(invalid_profile(
+                    line_number,
+                    format!("{} defined twice", id),
+                ));
+            }
+            profiles.push(BotProfile::new(id));
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid_profile(line_number, line))?;
+        let value =
+            parse_value(value.trim()).ok_or_else(|| invalid_profile(line_number, value.trim()))?;
+        let profile = profiles
+            .last_mut()
+            .ok_or_else(|| invalid_profile(line_number, "key outside a [profile] section"))?;
+        profile
+            .set(key.trim(), &value)
+            .map_err(|message| invalid_profile(line_number, message))?;
+    }
+    Ok(profiles)
+}
+
+/// Book paths are taken relative to the file.
+pub fn load_profiles(path: impl AsRef<Path>) -> Result<Vec<BotProfile>, Error> {
+    let path = path.as_ref();
+    let mut profiles = parse_profiles(&fs::read_to_string(path)?)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for profile in profiles.iter_mut() {
+        if let Some(book) = profile.book.as_mut() {
+            *book = dir.join(&book);
+        }
+    }
+    Ok(profiles)
+}
+
+/// The profiles at `CHESS_PROFILES`, or at `DEFAULT_PROFILES_PATH` if there is a file; none
+/// otherwise.
+pub fn profiles_from_env() -> Result<Vec<BotProfile>, Error> {
+    match env::var("CHESS_PROFILES") {
+        Ok(path) => load_profiles(path),
+        Err(_) if Path::new(DEFAULT_PROFILES_PATH).exists() => load_profiles(DEFAULT_PROFILES_PATH),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod test_profile {
+    use crate::board::Board;
+    use crate::bot::{evaluate_with, ChessBot, EvalWeights, SkillLevel, Term};
+    use crate::helpers::chess_move;
+    use crate::pieces::{Color, PieceKind};
+    use crate::polyglot::{encode_book_move, BookEntry, OpeningBook, POLYGLOT_START_POSITION_KEY};
+    use crate::profile::{load_profiles, parse_profiles, BotProfile};
+    use std::fs;
+    use std::time::Duration;
+
+    const PROFILES: &str = r#"
+# two sparring partners
+[positional]
+name = "Petrosian # not a comment"
+mobility = 150
+pawn_structure = 150
+king_safety = 80
+contempt = -10
+skill = 15
+
+[attacking]
+name = "Tal"
+knight = 330   # likes its knights
+aggression = 200
+contempt = 25
+depth = 6
+move_time = 1.5
+threads = 2
+"#;
+
+    #[test]
+    fn test_parse_profiles() {
+        let profiles = parse_profiles(PROFILES).unwrap();
+        assert_eq!(profiles.len(), 2);
+
+        let positional = &profiles[0];
+        assert_eq!(positional.id, "positional");
+        assert_eq!(positional.name, "Petrosian # not a comment");
+        assert_eq!(positional.weights.term_weight(Term::Mobility), 150);
+        assert_eq!(positional.weights.term_weight(Term::Tempo), 100);
+        assert_eq!(positional.contempt, -10);
+        assert_eq!(positional.skill, Some(SkillLevel::new(15)));
+        assert_eq!(positional.depth, None);
+
+        let attacking = &profiles[1];
+        assert_eq!(attacking.weights.piece_value(PieceKind::N), 330);
+        assert_eq!(attacking.weights.piece_value(PieceKind::Q), 900);
+        assert_eq!(attacking.weights.aggression, 200);
+        assert_eq!(attacking.move_time, Some(Duration::from_millis(1500)));
+
+        let mut bot = ChessBot::new(Color::White, 3);
+        attacking.apply(&mut bot).unwrap();
+        assert_eq!(bot.max_depth, 6);
+        assert_eq!(bot.threads, 2);
+        assert_eq!(bot.contempt, 25);
+        assert_eq!(bot.weights, attacking.weights);
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        for text in [
+            "name = \"no section\"",
+            "[bot]\nspeed = 3",
+            "[bot]\ndepth = deep",
+            "[bot]\ndepth = 0",
+            "[bot]\nmobility = \"high\"",
+            "[bot\ndepth = 3",
+            "[bot]\n[bot]",
+        ] {
+            assert!(parse_profiles(text).is_err(), "{}", text);
+        }
+        let error = parse_profiles("[bot]\n\ndepth = 0").unwrap_err();
+        assert!(error.to_string().contains("line 3"));
+    }
+
+    #[test]
+    fn test_weights_change_the_evaluation() {
+        // white is a pawn up
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let mut weights = EvalWeights::default();
+        let plain = evaluate_with(&board, &weights).term(Term::Material);
+        weights.set_piece_value(PieceKind::P, 50);
+        assert_eq!(
+            evaluate_with(&board, &weights).term(Term::Material),
+            plain - 50
+        );
+    }
+
+    #[test]
+    fn test_profile_books_use_the_standard_keys() {
+        // a book from another tool, keyed with the standard table
+        let e4 = chess_move("e2", "e4");
+        let entry = BookEntry {
+            key: POLYGLOT_START_POSITION_KEY,
+            le_move: encode_book_move(&Board::new(), e4),
+            weight: 1,
+            learn: 0,
+        };
+        let path =
+            std::env::temp_dir().join(format!("rust-chess-profile-{}.bin", std::process::id()));
+        fs::write(&path, OpeningBook::write_entries(&[entry])).unwrap();
+        let mut profile = BotProfile::new("booked");
+        profile.book = Some(path.clone());
+        let mut bot = ChessBot::new(Color::White, 2);
+        profile.apply(&mut bot).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(bot.book.unwrap().choose(&Board::new()), Some(e4));
+    }
+
+    #[test]
+    fn test_example_profiles() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/profiles.toml");
+        let profiles = load_profiles(path).unwrap();
+        let ids: Vec<&str> = profiles.iter().map(|profile| profile.id.as_str()).collect();
+        assert!(ids.contains(&"positional") && ids.contains(&"attacking"));
+        for profile in profiles {
+            profile.apply(&mut ChessBot::new(Color::Black, 2)).unwrap();
+        }
+    }
+}
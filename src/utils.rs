@@ -1,48 +1,37 @@
-use crate::helpers::Position;
+use crate::helpers::Square;
 use crate::pieces::PieceKind;
 
 use std::io;
 
-pub fn chess_coord_to_position(coord: String) -> Option<Position> {
-    if coord.trim().chars().count() != 2 {
-        return None;
-    }
-    let col = 104 - coord.chars().next().unwrap().to_ascii_lowercase() as i32;
-    let row = (coord.chars().nth(1).unwrap() as i32 - '0' as i32) - 1;
-
-    Position::get_valid_position(col, row)
-}
-
 #[allow(dead_code)]
-pub fn get_user_input(message: &str) -> Option<Position> {
+pub fn get_user_input(message: &str) -> Option<Square> {
     println!("{}", message);
     let mut from_input = String::new();
     io::stdin()
         .read_line(&mut from_input)
         .expect("Failed to read line");
 
-    chess_coord_to_position(from_input)
+    from_input.parse::<Square>().ok()
 }
 
 pub fn get_en_passant(
     piece_kind: &PieceKind,
-    from_position: &Position,
-    to_position: &Position,
-) -> Option<Position> {
-    if (piece_kind == &PieceKind::P) & ((from_position.y as i32 - to_position.y as i32).abs() == 2)
-    {
-        if to_position.y == 3 {
-            return Some(Position::new(to_position.x, 2));
+    from_position: &Square,
+    to_position: &Square,
+) -> Option<Square> {
+    if (piece_kind == &PieceKind::P) & (from_position.rank().abs_diff(to_position.rank()) == 2) {
+        if to_position.rank() == 3 {
+            return Some(Square::from_file_rank(to_position.file(), 2));
         }
-        return Some(Position::new(to_position.x, 5));
+        return Some(Square::from_file_rank(to_position.file(), 5));
     }
     None
 }
 
 pub fn was_en_passant_played(
     piece_kind: &PieceKind,
-    position: &Position,
-    en_passant: &Option<Position>,
+    position: &Square,
+    en_passant: &Option<Square>,
 ) -> bool {
     match en_passant {
         Some(en_passant_position) => {
@@ -54,30 +43,38 @@ pub fn was_en_passant_played(
 
 #[cfg(test)]
 mod test_board {
-    use crate::helpers::Position;
+    use crate::helpers::Square;
     use crate::pieces::PieceKind;
-    use crate::utils::{chess_coord_to_position, get_en_passant};
-
-    #[test]
-    fn test_chess_coord_to_array_coord() {
-        assert_eq!(
-            Position::get_valid_position(6, 2),
-            chess_coord_to_position(String::from("b3"))
-        );
-    }
+    use crate::utils::get_en_passant;
 
     #[test]
     fn test_get_en_passant() {
-        let output = get_en_passant(&PieceKind::P, &Position::new(2, 1), &Position::new(2, 3));
-        assert_eq!(Some(Position::new(2, 2)), output);
+        let output = get_en_passant(
+            &PieceKind::P,
+            &Square::from_file_rank(2, 1),
+            &Square::from_file_rank(2, 3),
+        );
+        assert_eq!(Some(Square::from_file_rank(2, 2)), output);
 
-        let output = get_en_passant(&PieceKind::P, &Position::new(2, 7), &Position::new(2, 5));
-        assert_eq!(Some(Position::new(2, 5)), output);
+        let output = get_en_passant(
+            &PieceKind::P,
+            &Square::from_file_rank(2, 7),
+            &Square::from_file_rank(2, 5),
+        );
+        assert_eq!(Some(Square::from_file_rank(2, 5)), output);
 
-        let output = get_en_passant(&PieceKind::P, &Position::new(2, 7), &Position::new(2, 6));
+        let output = get_en_passant(
+            &PieceKind::P,
+            &Square::from_file_rank(2, 7),
+            &Square::from_file_rank(2, 6),
+        );
         assert_eq!(None, output);
 
-        let output = get_en_passant(&PieceKind::R, &Position::new(2, 7), &Position::new(2, 5));
+        let output = get_en_passant(
+            &PieceKind::R,
+            &Square::from_file_rank(2, 7),
+            &Square::from_file_rank(2, 5),
+        );
         assert_eq!(None, output);
     }
 }